@@ -0,0 +1,537 @@
+use clap::{Parser, Subcommand};
+use serde_json::{json, Value};
+use srr::{Mismatch, RarCompressor, RarExecutable, Reconstructor, Srr, Srs, VolumeName};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const APP_NAME: &str = concat!("srr ", env!("CARGO_PKG_VERSION"));
+
+type Error = Box<dyn std::error::Error>;
+
+#[derive(Parser)]
+#[command(version, about = "Inspect, verify, reconstruct and create srr files")]
+struct Cli {
+    /// Print machine readable JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the header, volumes, archived files and stored files.
+    Info { srr: PathBuf },
+    /// Print every parsed block as JSON.
+    Dump { srr: PathBuf },
+    /// Write the stored files to disk.
+    Extract {
+        srr: PathBuf,
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Only extract these stored files.
+        files: Vec<String>,
+    },
+    /// Check RAR volumes or extracted files against the srr. Without options the
+    /// volumes are looked for next to the srr.
+    Verify {
+        srr: PathBuf,
+        /// Directory holding the RAR volumes.
+        #[arg(long)]
+        volumes: Option<PathBuf>,
+        /// Directory holding the files extracted from the volumes.
+        #[arg(long)]
+        files: Option<PathBuf>,
+    },
+    /// Rebuild the RAR volumes from the srr and the archived files.
+    Reconstruct {
+        srr: PathBuf,
+        /// Directory holding the archived files.
+        #[arg(short, long)]
+        input: PathBuf,
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// RAR executable used for compressed archives, as VERSION=PATH, e.g. 39=/opt/rar390/rar.
+        #[arg(long = "rar", value_parser = parse_rar_executable)]
+        executables: Vec<RarExecutable>,
+    },
+    /// Rebuild a sample from its SRS file and the media file it was cut from.
+    Sample {
+        srs: PathBuf,
+        media: PathBuf,
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// Create an srr from RAR volumes. Given only the first volume, the following
+    /// volumes are found by name.
+    Create {
+        #[arg(required = true)]
+        volumes: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Files to store in the srr, like .sfv and .nfo files.
+        #[arg(short, long)]
+        store: Vec<PathBuf>,
+        /// Samples to store as .srs files, in Sample/ when they are in a sample directory.
+        #[arg(long)]
+        sample: Vec<PathBuf>,
+    },
+}
+
+fn parse_rar_executable(arg: &str) -> Result<RarExecutable, String> {
+    let (version, path) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected VERSION=PATH, got {arg:?}"))?;
+    let version = version
+        .parse()
+        .map_err(|_| format!("{version:?} is not a version like 39"))?;
+    Ok(RarExecutable {
+        path: path.into(),
+        version,
+    })
+}
+
+/// The result of a command, printed as text or JSON.
+struct Report {
+    text: String,
+    json: Value,
+    success: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Info { srr } => info(&srr),
+        Command::Dump { srr } => dump(&srr),
+        Command::Extract { srr, output, files } => extract(&srr, &output, &files),
+        Command::Verify {
+            srr,
+            volumes,
+            files,
+        } => verify(&srr, volumes, files),
+        Command::Reconstruct {
+            srr,
+            input,
+            output,
+            executables,
+        } => reconstruct(&srr, &input, &output, executables),
+        Command::Sample { srs, media, output } => sample(&srs, &media, &output),
+        Command::Create {
+            volumes,
+            output,
+            store,
+            sample,
+        } => create(&volumes, &output, &store, &sample),
+    };
+
+    match result {
+        Ok(report) => {
+            if cli.json {
+                println!("{:#}", report.json);
+            } else {
+                print!("{}", report.text);
+            }
+            if report.success {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(error) => {
+            if cli.json {
+                println!("{:#}", json!({ "error": error.to_string() }));
+            } else {
+                eprintln!("error: {error}");
+            }
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<Srr, Error> {
+    let input = std::fs::read(path)
+        .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+    let (_rest, srr) =
+        Srr::new(&input).map_err(|_| format!("{} is not a valid srr file", path.display()))?;
+    Ok(srr)
+}
+
+fn info(path: &Path) -> Result<Report, Error> {
+    let srr = load(path)?;
+    let settings = srr.rar_settings();
+    let volumes = srr
+        .rar_volumes()
+        .into_iter()
+        .map(|(file, blocks)| (file.file_name.clone(), srr::volume_size(blocks)))
+        .collect::<Vec<_>>();
+    let archived = srr.archived_files();
+    let stored = srr
+        .stored_files()
+        .map(|(file, data)| (file.file_name.clone(), data.len()))
+        .collect::<Vec<_>>();
+    let samples = srr.samples().collect::<Vec<_>>();
+
+    let mut text = String::new();
+    text += &format!(
+        "Created by: {}\n",
+        srr.app_name().as_deref().unwrap_or("unknown")
+    );
+    text += &format!("Created with: {settings}\n");
+    text += &format!("\nRAR volumes ({}):\n", volumes.len());
+    for (name, size) in &volumes {
+        text += &format!("  {name} ({size} bytes)\n");
+    }
+    text += &format!("\nArchived files ({}):\n", archived.len());
+    for file in &archived {
+        let crc = file.crc.map(|crc| format!("{crc:08X}"));
+        text += &format!(
+            "  {} ({} bytes, CRC32 {})\n",
+            file.file_name,
+            file.size,
+            crc.as_deref().unwrap_or("unknown")
+        );
+    }
+    text += &format!("\nStored files ({}):\n", stored.len());
+    for (name, size) in &stored {
+        text += &format!("  {name} ({size} bytes)\n");
+    }
+    if !samples.is_empty() {
+        text += &format!("\nSamples ({}):\n", samples.len());
+    }
+    for (file, sample) in &samples {
+        match sample {
+            Ok(sample) => {
+                text += &format!(
+                    "  {}: {} ({} bytes, CRC32 {:08X}, {} tracks)\n",
+                    file.file_name,
+                    sample.file.file_name,
+                    sample.file.size,
+                    sample.file.crc,
+                    sample.tracks.len()
+                )
+            }
+            Err(error) => text += &format!("  {}: {error}\n", file.file_name),
+        }
+    }
+
+    let json = json!({
+        "app_name": srr.app_name(),
+        "rar_versions": {
+            "min": settings.versions.start(),
+            "max": Some(*settings.versions.end()).filter(|max| *max != u8::MAX),
+        },
+        "command_line": settings.command_line(),
+        "volumes": volumes
+            .iter()
+            .map(|(name, size)| json!({ "name": name, "size": size }))
+            .collect::<Vec<_>>(),
+        "archived_files": archived
+            .iter()
+            .map(|file| json!({
+                "name": file.file_name,
+                "size": file.size,
+                "crc": file.crc.map(|crc| format!("{crc:08X}")),
+            }))
+            .collect::<Vec<_>>(),
+        "stored_files": stored
+            .iter()
+            .map(|(name, size)| json!({ "name": name, "size": size }))
+            .collect::<Vec<_>>(),
+        "samples": samples
+            .iter()
+            .map(|(file, sample)| match sample {
+                Ok(sample) => json!({
+                    "srs": file.file_name,
+                    "container": sample.container,
+                    "name": sample.file.file_name,
+                    "size": sample.file.size,
+                    "crc": format!("{:08X}", sample.file.crc),
+                    "tracks": sample
+                        .tracks
+                        .iter()
+                        .map(|track| json!({
+                            "number": track.track_number,
+                            "data_length": track.data_length,
+                            "match_offset": track.match_offset,
+                        }))
+                        .collect::<Vec<_>>(),
+                }),
+                Err(error) => json!({ "srs": file.file_name, "error": error.to_string() }),
+            })
+            .collect::<Vec<_>>(),
+    });
+    Ok(Report {
+        text,
+        json,
+        success: true,
+    })
+}
+
+fn dump(path: &Path) -> Result<Report, Error> {
+    let srr = load(path)?;
+    let json = serde_json::to_value(&srr)?;
+    Ok(Report {
+        text: format!("{json:#}\n"),
+        json,
+        success: true,
+    })
+}
+
+/// Stored file names come from untrusted srrs, only relative paths inside the output
+/// directory are written.
+fn is_safe_path(name: &str) -> bool {
+    let path = Path::new(name);
+    !name.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
+fn extract(path: &Path, output: &Path, names: &[String]) -> Result<Report, Error> {
+    let srr = load(path)?;
+    for name in names {
+        if srr.stored_file(name).is_none() {
+            return Err(format!("no stored file named {name:?}").into());
+        }
+    }
+
+    let mut extracted = vec![];
+    for (file, data) in srr.stored_files() {
+        if !names.is_empty() && !names.contains(&file.file_name) {
+            continue;
+        }
+        if !is_safe_path(&file.file_name) {
+            return Err(format!("refusing to extract {:?}", file.file_name).into());
+        }
+        let target = output.join(&file.file_name);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, data)
+            .map_err(|error| format!("could not write {}: {error}", target.display()))?;
+        extracted.push(target);
+    }
+
+    let text = extracted
+        .iter()
+        .map(|path| format!("{}\n", path.display()))
+        .collect();
+    let json = json!({ "extracted": extracted });
+    Ok(Report {
+        text,
+        json,
+        success: true,
+    })
+}
+
+fn verify(path: &Path, volumes: Option<PathBuf>, files: Option<PathBuf>) -> Result<Report, Error> {
+    let srr = load(path)?;
+    let volumes = match (&volumes, &files) {
+        (None, None) => Some(
+            path.parent()
+                .map(Path::to_owned)
+                .unwrap_or_else(|| PathBuf::from(".")),
+        ),
+        _ => volumes,
+    };
+
+    let mut mismatches = vec![];
+    if let Some(dir) = &volumes {
+        mismatches.extend(srr.verify_volumes(dir)?);
+    }
+    if let Some(dir) = &files {
+        mismatches.extend(srr.verify_files(dir)?);
+    }
+
+    let text = if mismatches.is_empty() {
+        "OK\n".to_owned()
+    } else {
+        mismatches
+            .iter()
+            .map(|mismatch| format!("{mismatch}\n"))
+            .collect()
+    };
+    let json = json!({
+        "ok": mismatches.is_empty(),
+        "mismatches": mismatches.iter().map(mismatch_json).collect::<Vec<_>>(),
+    });
+    Ok(Report {
+        text,
+        json,
+        success: mismatches.is_empty(),
+    })
+}
+
+fn mismatch_json(mismatch: &Mismatch) -> Value {
+    match mismatch {
+        Mismatch::Missing(name) => json!({ "kind": "missing", "name": name }),
+        Mismatch::Size {
+            file_name,
+            expected,
+            actual,
+        } => json!({ "kind": "size", "name": file_name, "expected": expected, "actual": actual }),
+        Mismatch::Crc {
+            file_name,
+            expected,
+            actual,
+        } => json!({
+            "kind": "crc",
+            "name": file_name,
+            "expected": format!("{expected:08X}"),
+            "actual": format!("{actual:08X}"),
+        }),
+        Mismatch::Headers(name) => json!({ "kind": "headers", "name": name }),
+        Mismatch::UnsafePath(name) => json!({ "kind": "unsafe_path", "name": name }),
+    }
+}
+
+fn reconstruct(
+    path: &Path,
+    input: &Path,
+    output: &Path,
+    executables: Vec<RarExecutable>,
+) -> Result<Report, Error> {
+    let srr = load(path)?;
+    if executables.is_empty() {
+        Reconstructor::new(input).reconstruct_to_dir(&srr, output)?;
+    } else {
+        Reconstructor::new(input)
+            .with_compressor(RarCompressor::new(executables))
+            .reconstruct_to_dir(&srr, output)?;
+    }
+
+    let mut written = srr
+        .rar_volumes()
+        .into_iter()
+        .map(|(file, _blocks)| output.join(file.file_name.replace('\\', "/")))
+        .collect::<Vec<_>>();
+    srr::sort_volumes_by_key(&mut written, |path| path.to_str().unwrap_or_default());
+    let text = written
+        .iter()
+        .map(|path| format!("{}\n", path.display()))
+        .collect();
+    let json = json!({ "volumes": written });
+    Ok(Report {
+        text,
+        json,
+        success: true,
+    })
+}
+
+fn sample(path: &Path, media: &Path, output: &Path) -> Result<Report, Error> {
+    let srs = std::fs::read(path)?;
+    let parsed = srr::Srs::parse(&srs)?;
+    let file_name = Path::new(&parsed.file.file_name)
+        .file_name()
+        .ok_or("the srs has no sample file name")?;
+    let written = output.join(file_name);
+    let media = std::fs::File::open(media)?;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&written)?);
+    if let Err(error) = srr::rebuild_sample(&srs, media, &mut out) {
+        drop(out);
+        let _ = std::fs::remove_file(&written);
+        return Err(error.into());
+    }
+
+    let text = format!("{}\n", written.display());
+    let json = json!({
+        "sample": written,
+        "size": parsed.file.size,
+        "crc": format!("{:08X}", parsed.file.crc),
+    });
+    Ok(Report {
+        text,
+        json,
+        success: true,
+    })
+}
+
+/// The volumes following `first` that exist next to it.
+fn find_volumes(first: &Path) -> Vec<PathBuf> {
+    let mut volumes = vec![first.to_owned()];
+    let Some(mut volume) = first.to_str().and_then(VolumeName::parse) else {
+        return volumes;
+    };
+    while let Some(next) = volume.next() {
+        let path = PathBuf::from(next.to_string());
+        if !path.is_file() {
+            break;
+        }
+        volumes.push(path);
+        volume = next;
+    }
+    volumes
+}
+
+fn create(
+    volumes: &[PathBuf],
+    output: &Path,
+    store: &[PathBuf],
+    samples: &[PathBuf],
+) -> Result<Report, Error> {
+    let volumes = match volumes {
+        [first] => find_volumes(first),
+        volumes => volumes.to_vec(),
+    };
+
+    let mut readers = vec![];
+    for path in &volumes {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("{} is not a valid volume name", path.display()))?;
+        let file = std::fs::File::open(path)
+            .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+        readers.push((name, std::io::BufReader::new(file)));
+    }
+    let names = readers.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    let mut srr = Srr::create_from_readers(APP_NAME, readers)?;
+
+    for path in store {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("{} is not a valid file name", path.display()))?;
+        let data = std::fs::read(path)
+            .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+        srr.add_stored_file(name, &data)?;
+    }
+
+    for path in samples {
+        let (Some(name), Some(stem)) = (
+            path.file_name().and_then(|name| name.to_str()),
+            path.file_stem().and_then(|stem| stem.to_str()),
+        ) else {
+            return Err(format!("{} is not a valid file name", path.display()).into());
+        };
+        let data = std::fs::read(path)
+            .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+        let srs = Srs::create(APP_NAME, name, &data)
+            .map_err(|error| format!("could not create an srs for {name}: {error}"))?;
+        let in_sample_dir = path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .is_some_and(|parent| parent.eq_ignore_ascii_case("sample"));
+        let srs_name = if in_sample_dir {
+            format!("Sample/{stem}.srs")
+        } else {
+            format!("{stem}.srs")
+        };
+        srr.add_stored_file(&srs_name, &srs)?;
+    }
+
+    std::fs::write(output, srr.to_bytes())
+        .map_err(|error| format!("could not write {}: {error}", output.display()))?;
+
+    let text = format!(
+        "Created {} from {} volume(s)\n",
+        output.display(),
+        names.len()
+    );
+    let json = json!({ "srr": output, "volumes": names });
+    Ok(Report {
+        text,
+        json,
+        success: true,
+    })
+}
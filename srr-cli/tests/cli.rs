@@ -0,0 +1,280 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../srr/tests/pyrescene_test_files")
+}
+
+fn srr<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Command::new(env!("CARGO_BIN_EXE_srr"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn json(output: &Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn info() {
+    let path = root().join("store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr");
+    let output = srr([OsStr::new("info"), path.as_os_str()]);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("store_rr_solid_auth.part3.rar (17504 bytes)"));
+    assert!(text.contains("little_file.txt (11 bytes, CRC32 876DBBA3)"));
+
+    let output = srr([OsStr::new("--json"), OsStr::new("info"), path.as_os_str()]);
+    let info = json(&output);
+    assert_eq!(info["app_name"], "ReScene .NET 1.2");
+    assert_eq!(info["volumes"].as_array().unwrap().len(), 3);
+    assert_eq!(info["archived_files"][1]["crc"], "876DBBA3");
+    assert_eq!(info["rar_versions"]["min"], 29);
+}
+
+#[test]
+fn info_samples() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../srr/tests/Bobs.Burgers.S02E08.720p.HDTV.X264-DIMENSION.srr");
+    let output = srr([OsStr::new("info"), path.as_os_str()]);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains(
+        "Sample/boobs.boogers.208.720p-dimension.sample.srs: \
+         boobs.boogers.208.720p-dimension.sample.mkv (11480536 bytes, CRC32 A8C930C1, 2 tracks)"
+    ));
+
+    let output = srr([OsStr::new("--json"), OsStr::new("info"), path.as_os_str()]);
+    let sample = &json(&output)["samples"][0];
+    assert_eq!(sample["container"], "mkv");
+    assert_eq!(sample["crc"], "A8C930C1");
+    assert_eq!(sample["tracks"][1]["match_offset"], 60805142);
+}
+
+#[test]
+fn extract() {
+    let path = root().join("store_split_folder_old_srrsfv_windows/store_split_folder.srr");
+    let output = tempfile::tempdir().unwrap();
+    let result = srr([
+        OsStr::new("extract"),
+        path.as_os_str(),
+        OsStr::new("-o"),
+        output.path().as_os_str(),
+    ]);
+    assert!(result.status.success());
+    let input = std::fs::read(&path).unwrap();
+    let (_rest, parsed) = srr::Srr::new(&input).unwrap();
+    assert_eq!(
+        std::fs::read(output.path().join("store_split_folder.sfv")).unwrap(),
+        parsed.stored_file("store_split_folder.sfv").unwrap()
+    );
+
+    let result = srr([
+        OsStr::new("extract"),
+        path.as_os_str(),
+        OsStr::new("missing.nfo"),
+    ]);
+    assert_eq!(result.status.code(), Some(2));
+}
+
+#[test]
+fn verify() {
+    let path = root().join("store_split_folder_old_srrsfv_windows/store_split_folder.srr");
+    let result = srr([OsStr::new("--json"), OsStr::new("verify"), path.as_os_str()]);
+    assert!(result.status.success());
+    assert_eq!(json(&result)["ok"], true);
+
+    let empty = tempfile::tempdir().unwrap();
+    let result = srr([
+        OsStr::new("--json"),
+        OsStr::new("verify"),
+        path.as_os_str(),
+        OsStr::new("--volumes"),
+        empty.path().as_os_str(),
+    ]);
+    assert_eq!(result.status.code(), Some(1));
+    let report = json(&result);
+    assert_eq!(report["mismatches"].as_array().unwrap().len(), 3);
+    assert_eq!(report["mismatches"][0]["kind"], "missing");
+}
+
+#[test]
+fn create_and_reconstruct() {
+    let dir = root().join("store_rr_solid_auth_unicode_new");
+    let work = tempfile::tempdir().unwrap();
+    let created = work.path().join("created.srr");
+    let first = dir.join("store_rr_solid_auth.part1.rar");
+    let sfv = dir.join("store_rr_solid_auth.sfv");
+    let result = srr([
+        OsStr::new("create"),
+        first.as_os_str(),
+        OsStr::new("-o"),
+        created.as_os_str(),
+        OsStr::new("--store"),
+        sfv.as_os_str(),
+    ]);
+    assert!(result.status.success(), "{result:?}");
+
+    let info = json(&srr([
+        OsStr::new("--json"),
+        OsStr::new("info"),
+        created.as_os_str(),
+    ]));
+    assert_eq!(info["volumes"].as_array().unwrap().len(), 3);
+    assert_eq!(info["stored_files"][0]["name"], "store_rr_solid_auth.sfv");
+
+    // the archive only holds stored files, so the packed data is the files themselves
+    let input = work.path().join("input");
+    for volume in 1..=3 {
+        let name = format!("store_rr_solid_auth.part{volume}.rar");
+        let data = std::fs::read(dir.join(name)).unwrap();
+        let (_rest, volume) = srr::RarVolume::parse(&data).unwrap();
+        for (file, data) in volume.packed_files() {
+            let path = input.join(&file.file_name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let mut contents = std::fs::read(&path).unwrap_or_default();
+            contents.extend_from_slice(data);
+            std::fs::write(path, contents).unwrap();
+        }
+    }
+    let output = work.path().join("output");
+    let result = srr([
+        OsStr::new("reconstruct"),
+        created.as_os_str(),
+        OsStr::new("-i"),
+        input.as_os_str(),
+        OsStr::new("-o"),
+        output.as_os_str(),
+    ]);
+    assert!(result.status.success(), "{result:?}");
+    for volume in 1..=3 {
+        let name = format!("store_rr_solid_auth.part{volume}.rar");
+        let expected = std::fs::read(dir.join(&name)).unwrap();
+        assert!(
+            std::fs::read(output.join(&name)).unwrap() == expected,
+            "{name} differs"
+        );
+    }
+}
+
+#[test]
+fn dump() {
+    let path = root().join("store_little/store_little.srr");
+    let result = srr([OsStr::new("dump"), path.as_os_str()]);
+    assert!(result.status.success());
+    let dump = json(&result);
+    assert_eq!(dump["blocks"][0]["header"]["crc"], "6969");
+    assert_eq!(dump["blocks"][0]["header"]["ty"], "srr_header");
+}
+
+/// A stream SRS of 2000 bytes and a media file holding them.
+fn stream_srs(crc: u32) -> (Vec<u8>, Vec<u8>) {
+    let sample = (0..2000).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+    let mut file = vec![0, 0, 4, 0];
+    file.extend(b"test");
+    file.extend(10u16.to_le_bytes());
+    file.extend(b"sample.vob");
+    file.extend(2000u64.to_le_bytes());
+    file.extend(crc.to_le_bytes());
+    let mut track = vec![0, 0, 1, 0];
+    track.extend(2000u32.to_le_bytes());
+    track.extend(0u64.to_le_bytes());
+    track.extend(256u16.to_le_bytes());
+    track.extend(&sample[..256]);
+
+    let mut srs = b"STRM\x08\0\0\0".to_vec();
+    for (marker, data) in [(b"SRSF", file), (b"SRST", track)] {
+        srs.extend(marker);
+        srs.extend((data.len() as u32 + 8).to_le_bytes());
+        srs.extend(data);
+    }
+    let media = [vec![0xFF; 500], sample, vec![0xFF; 100]].concat();
+    (srs, media)
+}
+
+#[test]
+fn sample() {
+    let work = tempfile::tempdir().unwrap();
+    let (srs, media) = stream_srs(0x34196C80);
+    let srs_path = work.path().join("sample.srs");
+    let media_path = work.path().join("movie.vob");
+    std::fs::write(&srs_path, srs).unwrap();
+    std::fs::write(&media_path, &media).unwrap();
+    let result = srr([
+        OsStr::new("--json"),
+        OsStr::new("sample"),
+        srs_path.as_os_str(),
+        media_path.as_os_str(),
+        OsStr::new("-o"),
+        work.path().as_os_str(),
+    ]);
+    assert!(result.status.success(), "{result:?}");
+    assert_eq!(json(&result)["crc"], "34196C80");
+    assert_eq!(
+        std::fs::read(work.path().join("sample.vob")).unwrap(),
+        &media[500..2500]
+    );
+
+    // a wrong sample is not left behind
+    let (srs, _media) = stream_srs(0x12345678);
+    std::fs::write(&srs_path, srs).unwrap();
+    std::fs::remove_file(work.path().join("sample.vob")).unwrap();
+    let result = srr([
+        OsStr::new("sample"),
+        srs_path.as_os_str(),
+        media_path.as_os_str(),
+        OsStr::new("-o"),
+        work.path().as_os_str(),
+    ]);
+    assert_eq!(result.status.code(), Some(2));
+    assert!(!work.path().join("sample.vob").exists());
+}
+
+#[test]
+fn create_with_sample() {
+    let work = tempfile::tempdir().unwrap();
+    let mut movi = b"movi".to_vec();
+    for i in 0..20u8 {
+        movi.extend(b"00dc");
+        movi.extend(400u32.to_le_bytes());
+        movi.extend((0..400).map(|j| i.wrapping_mul(31) ^ (j % 253) as u8));
+    }
+    let mut avi = b"AVI LIST".to_vec();
+    avi.extend((movi.len() as u32).to_le_bytes());
+    avi.extend(movi);
+    let sample = [
+        b"RIFF".to_vec(),
+        (avi.len() as u32).to_le_bytes().to_vec(),
+        avi,
+    ]
+    .concat();
+    std::fs::create_dir(work.path().join("SAMPLE")).unwrap();
+    let sample_path = work.path().join("SAMPLE/movie-sample.avi");
+    std::fs::write(&sample_path, sample).unwrap();
+
+    let created = work.path().join("created.srr");
+    let first = root().join("store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.rar");
+    let result = srr([
+        OsStr::new("create"),
+        first.as_os_str(),
+        OsStr::new("-o"),
+        created.as_os_str(),
+        OsStr::new("--sample"),
+        sample_path.as_os_str(),
+    ]);
+    assert!(result.status.success(), "{result:?}");
+    let info = json(&srr([
+        OsStr::new("--json"),
+        OsStr::new("info"),
+        created.as_os_str(),
+    ]));
+    assert_eq!(info["samples"][0]["srs"], "Sample/movie-sample.srs");
+    assert_eq!(info["samples"][0]["name"], "movie-sample.avi");
+    assert_eq!(info["samples"][0]["size"], 8184);
+}
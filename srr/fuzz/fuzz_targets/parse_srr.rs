@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_rest, srr)) = srr::Srr::new(data) {
+        assert_eq!(srr.to_bytes(), data);
+        let _ = srr.app_name();
+        let _ = srr.archived_files();
+        let _ = srr.rar_settings();
+        let _ = srr.stored_files().count();
+        let _ = srr.rar_sets();
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(srs) = srr::Srs::parse(data) {
+        let _ = srs.track(1);
+    }
+});
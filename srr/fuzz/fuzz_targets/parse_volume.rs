@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = srr::RarVolume::parse(data);
+    let _ = srr::Rar5Volume::parse_archive(data);
+    if let Ok(srr) = srr::Srr::create("", [("fuzz.rar", data)]) {
+        let _ = srr.archived_files();
+        let _ = srr.rar_settings();
+    }
+});
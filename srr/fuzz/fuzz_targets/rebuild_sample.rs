@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // the first two bytes give the length of the srs, the rest is the media file
+    let Some((len, rest)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    let (srs, media) = rest.split_at(usize::from(u16::from_le_bytes(*len)).min(rest.len()));
+    let _ = srr::rebuild_sample(srs, std::io::Cursor::new(media), std::io::sink());
+});
@@ -0,0 +1,301 @@
+use crate::{Block, BlockImpl, BlockType, HostOS, RarExecutable, RarPackedFile, Srr};
+
+// RarVolumeHeader flags
+const MHD_LOCK: u16 = 0x0004;
+const MHD_SOLID: u16 = 0x0008;
+const MHD_NEWNUMBERING: u16 = 0x0010;
+const MHD_AV: u16 = 0x0020;
+const MHD_FIRSTVOLUME: u16 = 0x0100;
+
+// RarMax flags
+const EARC_VOLNUMBER: u16 = 0x0008;
+
+/// The newest version that still creates RAR 1.5-4.x archives (`-ma4`).
+const LAST_RAR4_CREATOR: u8 = 69;
+
+/// The probable RAR version and command line that produced an archive, inferred from its headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RarSettings {
+    /// Oldest and newest possible version, as 10 * major version + minor version.
+    pub versions: std::ops::RangeInclusive<u8>,
+    pub host_os: Option<HostOS>,
+    /// 0 (store) to 5 (best).
+    pub method: Option<u8>,
+    /// Dictionary size in KiB.
+    pub dictionary_size: Option<u32>,
+    pub solid: bool,
+    /// Size of the first volume, for sets of more than one volume.
+    pub volume_size: Option<u64>,
+    pub switches: Vec<String>,
+    /// Why the version range and switches were chosen.
+    pub reasons: Vec<String>,
+}
+
+impl RarSettings {
+    /// Whether `executable` could have produced the archive.
+    pub fn supports(&self, executable: &RarExecutable) -> bool {
+        self.versions.contains(&executable.version)
+    }
+
+    /// The full command line, e.g. `rar a -m0 -v15000 -vn`.
+    pub fn command_line(&self) -> String {
+        let mut command = String::from("rar a");
+        for switch in &self.switches {
+            command.push(' ');
+            command.push_str(switch);
+        }
+        command
+    }
+
+    fn require(&mut self, min: u8, max: u8, reason: impl Into<String>) {
+        let (start, end) = self.versions.clone().into_inner();
+        self.versions = start.max(min)..=end.min(max);
+        self.reasons.push(reason.into());
+    }
+}
+
+impl std::fmt::Display for RarSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn version(v: u8) -> String {
+            format!("{}.{}", v / 10, v % 10)
+        }
+        let (start, end) = (*self.versions.start(), *self.versions.end());
+        if start > end {
+            f.write_str("unknown RAR version")?;
+        } else if end == u8::MAX {
+            write!(f, "RAR {} or later", version(start))?;
+        } else {
+            write!(f, "RAR {} to {}", version(start), version(end))?;
+        }
+        write!(f, ": {}", self.command_line())
+    }
+}
+
+/// Size of a RAR volume as it was on disk, including the file data and recovery
+/// records left out of the srr.
+pub fn volume_size(blocks: &[Block]) -> u64 {
+    blocks
+        .iter()
+        .map(|block| match &block.inner {
+            Some(BlockImpl::RarPackedFile(file)) => {
+                (block.raw.len() as u64).saturating_add(file.packed_size)
+            }
+            Some(BlockImpl::RarNewSub(sub)) if sub.recovery.is_some() => {
+                (block.raw.len() as u64).saturating_add(sub.file.packed_size)
+            }
+            Some(BlockImpl::RarOldRecovery(_)) => {
+                block.raw.len() as u64 + block.header.add_size as u64
+            }
+            Some(BlockImpl::Rar5(volume)) => {
+                // damaged srrs can claim sizes that overflow
+                volume
+                    .blocks
+                    .iter()
+                    .filter(|block| !block.stores_data())
+                    .fold(block.raw.len() as u64, |size, block| {
+                        size.saturating_add(block.header.data_size)
+                    })
+            }
+            Some(BlockImpl::SrrRarPadding) => block.data().len() as u64,
+            Some(
+                BlockImpl::SrrStoredFile(_) | BlockImpl::SrrRarFile(_) | BlockImpl::SrrOsoHash,
+            ) => 0,
+            _ => block.raw.len() as u64,
+        })
+        .fold(0, u64::saturating_add)
+}
+
+fn volume_switch(size: u64) -> String {
+    if size.is_multiple_of(1024 * 1024) {
+        format!("-v{}m", size / (1024 * 1024))
+    } else if size.is_multiple_of(1000) {
+        format!("-v{}", size / 1000)
+    } else if size.is_multiple_of(1024) {
+        format!("-v{}k", size / 1024)
+    } else {
+        format!("-v{size}b")
+    }
+}
+
+impl Srr {
+    /// Infers the probable RAR version and command line switches that produced the archives.
+    pub fn rar_settings(&self) -> RarSettings {
+        let mut settings = RarSettings {
+            versions: 15..=u8::MAX,
+            host_os: None,
+            method: None,
+            dictionary_size: None,
+            solid: false,
+            volume_size: None,
+            switches: vec![],
+            reasons: vec![],
+        };
+
+        let volumes = self.rar_volumes();
+        let blocks = volumes.iter().flat_map(|(_file, blocks)| blocks.iter());
+        let files = blocks
+            .clone()
+            .filter_map(|block| match &block.inner {
+                Some(BlockImpl::RarPackedFile(file)) => Some((block.header.flags, file)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let main_flags = blocks
+            .clone()
+            .find(|block| block.header.ty == BlockType::RarVolumeHeader)
+            .map(|block| block.header.flags);
+        let rar5 = blocks.clone().find_map(|block| match &block.inner {
+            Some(BlockImpl::Rar5(volume)) => Some(volume),
+            _ => None,
+        });
+
+        if let Some(volume) = rar5 {
+            settings.require(50, u8::MAX, "RAR5 archive format");
+            if let Some(main) = volume.main_archive() {
+                settings.solid = main.is_solid();
+            }
+            if let Some(file) = volume.files().next() {
+                settings.method = Some(file.compression_method());
+                settings.dictionary_size = Some((file.dictionary_size() / 1024) as u32);
+                if file.algorithm_version() > 0 {
+                    settings.require(70, u8::MAX, "RAR 7.0 compression algorithm");
+                }
+            }
+        } else if let Some((flags, file)) = files.first() {
+            settings.host_os = Some(file.os);
+            settings.method = Some(
+                file.compression_method
+                    .saturating_sub(RarPackedFile::METHOD_STORE),
+            );
+            settings.solid = main_flags.is_some_and(|flags| flags & MHD_SOLID != 0)
+                || files
+                    .iter()
+                    .any(|(flags, _)| flags & RarPackedFile::SOLID != 0);
+            if !file.is_stored() {
+                settings.dictionary_size =
+                    Some(64 << ((flags & RarPackedFile::DICTIONARY_MASK) >> 5));
+            }
+            settings.require(15, LAST_RAR4_CREATOR, "RAR 1.5-4.x archive format");
+
+            let unpack_version = files.iter().map(|(_, file)| file.unpack_version).max();
+            let compressed = files.iter().any(|(_, file)| !file.is_stored());
+            match (unpack_version, compressed) {
+                (Some(15), true) => settings.require(15, 19, "packed with the RAR 1.5 algorithm"),
+                (Some(20), true) => settings.require(20, 28, "packed with the RAR 2.0 algorithm"),
+                (Some(26), _) => settings.require(26, u8::MAX, "files larger than 2 GiB"),
+                (Some(29), _) => settings.require(29, u8::MAX, "packed with the RAR 2.9 algorithm"),
+                _ => {}
+            }
+            if settings.dictionary_size.is_some_and(|size| size > 1024) {
+                settings.require(29, u8::MAX, "dictionary larger than 1 MiB");
+            }
+        }
+
+        // features that only exist in some versions
+        let rar3_feature = main_flags
+            .and_then(|flags| {
+                if flags & MHD_FIRSTVOLUME != 0 {
+                    Some("first volume flag")
+                } else if flags & MHD_NEWNUMBERING != 0 {
+                    Some("new volume naming")
+                } else {
+                    None
+                }
+            })
+            .or_else(|| {
+                files.iter().find_map(|(flags, _)| {
+                    if flags & RarPackedFile::EXT_TIME != 0 {
+                        Some("extended file times")
+                    } else if flags & RarPackedFile::UNICODE != 0 {
+                        Some("unicode file names")
+                    } else {
+                        None
+                    }
+                })
+            })
+            .or_else(|| {
+                blocks.clone().find_map(|block| match block.header.ty {
+                    BlockType::RarNewSub => Some("new style subblocks"),
+                    BlockType::RarMax if block.header.flags & EARC_VOLNUMBER != 0 => {
+                        Some("volume number in the end of archive block")
+                    }
+                    _ => None,
+                })
+            });
+        if let Some(feature) = rar3_feature {
+            settings.require(29, u8::MAX, feature);
+        }
+        if let Some(block) = blocks.clone().find(|block| {
+            matches!(
+                block.header.ty,
+                BlockType::RarOldRecovery
+                    | BlockType::OldComment
+                    | BlockType::OldAuthenticity1
+                    | BlockType::OldSubblock
+                    | BlockType::OldAuthenticity2
+            )
+        }) {
+            settings.require(15, 28, format!("old style {:?} block", block.header.ty));
+        }
+
+        // switches
+        if let Some(method) = settings.method {
+            settings.switches.push(format!("-m{method}"));
+        }
+        if let Some(size) = settings.dictionary_size {
+            // RAR5 defaults to megabytes
+            let unit = if *settings.versions.start() >= 50 {
+                "k"
+            } else {
+                ""
+            };
+            settings.switches.push(format!("-md{size}{unit}"));
+        }
+        if settings.solid {
+            settings.switches.push("-s".to_owned());
+        }
+        if volumes.len() > 1 {
+            let size = volume_size(volumes[0].1);
+            settings.volume_size = Some(size);
+            settings.switches.push(volume_switch(size));
+            let new_numbering =
+                rar5.is_some() || main_flags.is_some_and(|flags| flags & MHD_NEWNUMBERING != 0);
+            if !new_numbering && *settings.versions.start() >= 29 {
+                settings.switches.push("-vn".to_owned());
+            }
+        }
+        if !files.is_empty()
+            && files
+                .iter()
+                .all(|(_, file)| !file.file_name.contains(['\\', '/']))
+        {
+            settings.switches.push("-ep".to_owned());
+        }
+        if let Some(file) = files
+            .iter()
+            .find_map(|(flags, file)| (flags & RarPackedFile::EXT_TIME != 0).then_some(file))
+        {
+            for (shift, name) in [(12, 'm'), (8, 'c'), (4, 'a')] {
+                let time = (file.ext_time_flags >> shift) & 0xF;
+                let precision = (time & 0x3) + 1;
+                if time & 0x8 != 0 && (name != 'm' || precision != 4) {
+                    settings.switches.push(format!("-ts{name}{precision}"));
+                }
+            }
+        }
+        if main_flags.is_some_and(|flags| flags & MHD_LOCK != 0) {
+            settings.switches.push("-k".to_owned());
+        }
+        if main_flags.is_some_and(|flags| flags & MHD_AV != 0) {
+            settings.switches.push("-av".to_owned());
+        }
+        if let Some(recovery_sectors) = blocks.clone().find_map(|block| match &block.inner {
+            Some(BlockImpl::RarNewSub(sub)) => sub.recovery.as_ref().map(|rr| rr.recovery_sectors),
+            Some(BlockImpl::RarOldRecovery(rr)) => Some(rr.recovery_sector as u32),
+            _ => None,
+        }) {
+            settings.switches.push(format!("-rr{recovery_sectors}"));
+        }
+        settings
+    }
+}
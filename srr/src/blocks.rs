@@ -1,18 +1,38 @@
-use crate::{take1, BlockHeader};
+use crate::{take1, BlockHeader, BlockType, DateTime, Rar5Volume};
 use nom::Parser as _;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub header: BlockHeader,
     pub inner: Option<BlockImpl>,
+    /// The block exactly as it appears in the srr, header included.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+    pub raw: Vec<u8>,
 }
 
-#[derive(Debug)]
+impl Block {
+    /// Any bytes stored in the srr after the block header, e.g. the contents of a stored file.
+    pub fn data(&self) -> &[u8] {
+        self.raw
+            .get(self.header.size as usize..)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum BlockImpl {
     RarVolumeHeader,
     RarPackedFile(RarPackedFile),
     RarOldRecovery(RarOldRecovery),
-    RarNewSub,
+    RarNewSub(RarNewSub),
+    Rar5(Rar5Volume),
+    OldComment(RarOldComment),
+    OldAuthenticity1(RarOldAuthenticity1),
+    OldSubblock(RarOldSubblock),
+    OldAuthenticity2(RarOldAuthenticity2),
 
     //srr
     SrrHeader,
@@ -24,7 +44,8 @@ pub enum BlockImpl {
     SrrRarPadding,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RarOldRecovery {
     pub rar_version: u8,
     pub recovery_sector: u16,
@@ -48,7 +69,257 @@ impl RarOldRecovery {
     }
 }
 
-#[derive(Debug)]
+/// RAR 2.x archive comment. The packed comment follows the fields, inside the header.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarOldComment {
+    pub unpacked_size: u16,
+    pub unpack_version: u8,
+    pub method: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+    pub comment_crc: u16,
+}
+
+impl RarOldComment {
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, unpacked_size) = nom::number::le_u16().parse(input)?;
+        let (rest, unpack_version) = take1(rest)?;
+        let (rest, method) = take1(rest)?;
+        let (rest, comment_crc) = nom::number::le_u16().parse(rest)?;
+        Ok((
+            rest,
+            Self {
+                unpacked_size,
+                unpack_version,
+                method,
+                comment_crc,
+            },
+        ))
+    }
+}
+
+/// RAR 2.x authenticity verification. The verification data follows the fields,
+/// inside the header.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarOldAuthenticity1 {
+    pub unpack_version: u8,
+    pub method: u8,
+    pub av_version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+    pub av_info_crc: u32,
+}
+
+impl RarOldAuthenticity1 {
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, unpack_version) = take1(input)?;
+        let (rest, method) = take1(rest)?;
+        let (rest, av_version) = take1(rest)?;
+        let (rest, av_info_crc) = nom::number::le_u32().parse(rest)?;
+        Ok((
+            rest,
+            Self {
+                unpack_version,
+                method,
+                av_version,
+                av_info_crc,
+            },
+        ))
+    }
+}
+
+/// RAR 2.x digital signature, followed by the archive and user name inside the header.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarOldAuthenticity2 {
+    pub creation_time: DateTime,
+    pub archive_name: String,
+    pub user_name: String,
+}
+
+impl RarOldAuthenticity2 {
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, creation_time) = nom::number::le_u32().map(DateTime::from_dos).parse(input)?;
+        let (rest, archive_name_length) = nom::number::le_u16().parse(rest)?;
+        let (rest, user_name_length) = nom::number::le_u16().parse(rest)?;
+        let (rest, archive_name) = nom::bytes::complete::take(archive_name_length)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .parse(rest)?;
+        let (rest, user_name) = nom::bytes::complete::take(user_name_length)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .parse(rest)?;
+        Ok((
+            rest,
+            Self {
+                creation_time,
+                archive_name,
+                user_name,
+            },
+        ))
+    }
+}
+
+/// Extended attributes and access control lists, the packed data follows the header.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarOldAttributes {
+    pub unpacked_size: u32,
+    pub unpack_version: u8,
+    pub method: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+    pub crc: u32,
+}
+
+impl RarOldAttributes {
+    fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, unpacked_size) = nom::number::le_u32().parse(input)?;
+        let (rest, unpack_version) = take1(rest)?;
+        let (rest, method) = take1(rest)?;
+        let (rest, crc) = nom::number::le_u32().parse(rest)?;
+        Ok((
+            rest,
+            Self {
+                unpacked_size,
+                unpack_version,
+                method,
+                crc,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RarOldSubblockData {
+    /// OS/2 extended attributes.
+    Os2ExtendedAttributes(RarOldAttributes),
+    UnixOwner {
+        owner: String,
+        group: String,
+    },
+    MacOSInfo {
+        file_type: u32,
+        file_creator: u32,
+    },
+    BeOSExtendedAttributes(RarOldAttributes),
+    NtfsAcl(RarOldAttributes),
+    /// NTFS alternate data stream.
+    NtfsStream {
+        attributes: RarOldAttributes,
+        stream_name: String,
+    },
+    Unknown,
+}
+
+/// RAR 2.x subblock, holding file system metadata of the file before it. The packed
+/// data of the subblock follows the header and is kept in the srr.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarOldSubblock {
+    pub sub_type: u16,
+    pub level: u8,
+    pub data: RarOldSubblockData,
+}
+
+impl RarOldSubblock {
+    pub const OS2_EA: u16 = 0x0100;
+    pub const UNIX_OWNER: u16 = 0x0101;
+    pub const MACOS_INFO: u16 = 0x0102;
+    pub const BEOS_EA: u16 = 0x0103;
+    pub const NTFS_ACL: u16 = 0x0104;
+    pub const NTFS_STREAM: u16 = 0x0105;
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, sub_type) = nom::number::le_u16().parse(input)?;
+        let (rest, level) = take1(rest)?;
+        let (rest, data) = match sub_type {
+            Self::OS2_EA => RarOldAttributes::parse
+                .map(RarOldSubblockData::Os2ExtendedAttributes)
+                .parse(rest)?,
+            Self::UNIX_OWNER => {
+                let (rest, owner_length) = nom::number::le_u16().parse(rest)?;
+                let (rest, group_length) = nom::number::le_u16().parse(rest)?;
+                let (rest, owner) = nom::bytes::complete::take(owner_length)
+                    .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    .parse(rest)?;
+                let (rest, group) = nom::bytes::complete::take(group_length)
+                    .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    .parse(rest)?;
+                (rest, RarOldSubblockData::UnixOwner { owner, group })
+            }
+            Self::MACOS_INFO => {
+                let (rest, file_type) = nom::number::le_u32().parse(rest)?;
+                let (rest, file_creator) = nom::number::le_u32().parse(rest)?;
+                (
+                    rest,
+                    RarOldSubblockData::MacOSInfo {
+                        file_type,
+                        file_creator,
+                    },
+                )
+            }
+            Self::BEOS_EA => RarOldAttributes::parse
+                .map(RarOldSubblockData::BeOSExtendedAttributes)
+                .parse(rest)?,
+            Self::NTFS_ACL => RarOldAttributes::parse
+                .map(RarOldSubblockData::NtfsAcl)
+                .parse(rest)?,
+            Self::NTFS_STREAM => {
+                let (rest, attributes) = RarOldAttributes::parse(rest)?;
+                let (rest, name_length) = nom::number::le_u16().parse(rest)?;
+                let (rest, stream_name) = nom::bytes::complete::take(name_length)
+                    .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    .parse(rest)?;
+                (
+                    rest,
+                    RarOldSubblockData::NtfsStream {
+                        attributes,
+                        stream_name,
+                    },
+                )
+            }
+            _ => (rest, RarOldSubblockData::Unknown),
+        };
+        Ok((
+            rest,
+            Self {
+                sub_type,
+                level,
+                data,
+            },
+        ))
+    }
+}
+
+/// Parses the fields of the RAR 1.5-2.x comment, authenticity and subblock blocks.
+pub(crate) fn parse_old_block(
+    ty: BlockType,
+    fields: &[u8],
+) -> nom::IResult<&[u8], Option<BlockImpl>> {
+    match ty {
+        BlockType::OldComment => RarOldComment::parse
+            .map(BlockImpl::OldComment)
+            .map(Some)
+            .parse(fields),
+        BlockType::OldAuthenticity1 => RarOldAuthenticity1::parse
+            .map(BlockImpl::OldAuthenticity1)
+            .map(Some)
+            .parse(fields),
+        BlockType::OldSubblock => RarOldSubblock::parse
+            .map(BlockImpl::OldSubblock)
+            .map(Some)
+            .parse(fields),
+        BlockType::OldAuthenticity2 => RarOldAuthenticity2::parse
+            .map(BlockImpl::OldAuthenticity2)
+            .map(Some)
+            .parse(fields),
+        _ => Ok((fields, None)),
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SrrStoredFile {
     pub file_name: String,
 }
@@ -63,7 +334,8 @@ impl SrrStoredFile {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SrrRarFile {
     pub file_name: String,
 }
@@ -78,40 +350,9 @@ impl SrrRarFile {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct DateTime {
-    pub year: u16,
-    pub month: u16,
-    pub day: u16,
-    pub hour: u16,
-    pub minute: u16,
-    pub second: u16,
-}
-
-impl DateTime {
-    fn parse(stamp: u32) -> Self {
-        let second = (stamp & 0x1F) * 2;
-        let stamp = stamp >> 5;
-        let minute = stamp & 0x3F;
-        let stamp = stamp >> 6;
-        let hour = stamp & 0x1F;
-        let stamp = stamp >> 5;
-        let day = stamp & 0x1F;
-        let stamp = stamp >> 5;
-        let month = stamp & 0x0F;
-        let year = ((stamp >> 4) & 0x7F) + 1980;
-        Self {
-            year: year as u16,
-            month: month as u16,
-            day: day as u16,
-            hour: hour as u16,
-            minute: minute as u16,
-            second: second as u16,
-        }
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum HostOS {
     MsDOS = 0,
     OS2 = 1,
@@ -139,10 +380,14 @@ impl TryFrom<u8> for HostOS {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RarPackedFile {
-    pub unpacked_size: u32,
+    /// Size of the file data following the header, including the high 32 bits.
+    pub packed_size: u64,
+    pub unpacked_size: u64,
     pub os: HostOS,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
     pub file_crc: u32,
     pub datetime: DateTime,
     pub unpack_version: u8,
@@ -150,6 +395,9 @@ pub struct RarPackedFile {
     pub file_attributes: u32,
     pub file_name: String,
     pub salt: u64,
+    /// 4 bits each for modification, creation, access and archive time, highest first:
+    /// 0x8 the time is stored, 0x4 one second is added, 0x3 number of extra precision bytes.
+    pub ext_time_flags: u16,
 }
 
 impl RarPackedFile {
@@ -163,7 +411,7 @@ impl RarPackedFile {
                 let (mut data, mut dostime) = if let Some(dostime) = dostime {
                     (data, dostime)
                 } else {
-                    nom::number::le_u32().map(DateTime::parse).parse(data)?
+                    nom::number::le_u32().map(DateTime::from_dos).parse(data)?
                 };
 
                 let mut rem: u32 = 0;
@@ -194,13 +442,13 @@ impl RarPackedFile {
             Ok((rest, os))
         })?;
         let (rest, file_crc) = nom::number::le_u32().parse(rest)?;
-        let (rest, datetime) = nom::number::le_u32().map(DateTime::parse).parse(rest)?;
+        let (rest, datetime) = nom::number::le_u32().map(DateTime::from_dos).parse(rest)?;
         let (rest, unpack_version) = take1(rest)?;
         let (rest, compression_method) = take1(rest)?;
         let (rest, name_length) = nom::number::le_u16().parse(rest)?;
         let (rest, file_attributes) = nom::number::le_u32().parse(rest)?;
 
-        let (rest, _) = if (header.flags & 0x100) != 0 {
+        let (rest, (high_packed_size, high_unpacked_size)) = if (header.flags & 0x100) != 0 {
             let (rest, high_packed_size) = nom::number::le_u32().parse(rest)?;
             let (rest, high_unpacked_size) = nom::number::le_u32().parse(rest)?;
 
@@ -236,7 +484,7 @@ impl RarPackedFile {
             (rest, 0)
         };
 
-        let (rest, _time) = if header.flags & 0x1000 != 0 {
+        let (rest, ext_time_flags) = if header.flags & 0x1000 != 0 {
             let (rest, flags) = if rest.len() >= 2 {
                 // println!("{:0>2X?}", &rest[..2]);
                 nom::number::le_u16().parse(rest)?
@@ -248,7 +496,7 @@ impl RarPackedFile {
             let (rest, _creation_time) = parse_xtime(flags >> 8, rest, None)?;
             let (rest, _last_access_time) = parse_xtime(flags >> 4, rest, None)?;
             let (rest, _archival_time) = parse_xtime(flags, rest, None)?;
-            (rest, 0)
+            (rest, flags)
         } else {
             (rest, 0)
         };
@@ -256,7 +504,8 @@ impl RarPackedFile {
         Ok((
             rest,
             Self {
-                unpacked_size,
+                packed_size: high_packed_size + header.add_size as u64,
+                unpacked_size: high_unpacked_size + unpacked_size as u64,
                 os,
                 file_crc,
                 datetime,
@@ -265,7 +514,64 @@ impl RarPackedFile {
                 file_attributes,
                 file_name,
                 salt,
+                ext_time_flags,
             },
         ))
     }
 }
+
+impl RarPackedFile {
+    pub const SPLIT_BEFORE: u16 = 0x0001;
+    pub const SPLIT_AFTER: u16 = 0x0002;
+    pub const PASSWORD: u16 = 0x0004;
+    pub const SOLID: u16 = 0x0010;
+    pub const UNICODE: u16 = 0x0200;
+    pub const EXT_TIME: u16 = 0x1000;
+    pub const DIRECTORY: u16 = 0x00E0;
+    /// The dictionary size bits, all set for directories.
+    pub const DICTIONARY_MASK: u16 = 0x00E0;
+    pub const METHOD_STORE: u8 = 0x30;
+
+    pub fn is_stored(&self) -> bool {
+        self.compression_method == Self::METHOD_STORE
+    }
+}
+
+/// New style recovery record, stored in a RarNewSub block named "RR".
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarNewRecovery {
+    pub recovery_sectors: u32,
+    pub data_sectors: u64,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RarNewSub {
+    /// Subblocks share the layout of a packed file, the file name is the subblock type (RR, CMT, AV).
+    pub file: RarPackedFile,
+    pub recovery: Option<RarNewRecovery>,
+}
+
+impl RarNewSub {
+    pub fn parse<'a>(input: &'a [u8], header: &BlockHeader) -> nom::IResult<&'a [u8], Self> {
+        let (rest, file) = RarPackedFile::parse(input, header)?;
+        let recovery = if file.file_name == "RR" {
+            // the recovery record fields are always the last 20 bytes of the header
+            let tail = &input[input.len().saturating_sub(8 + 4 + 8)..];
+            let (_rest, (_tag, recovery_sectors, data_sectors)) = (
+                nom::bytes::tag(&b"Protect+"[..]),
+                nom::number::le_u32(),
+                nom::number::le_u64(),
+            )
+                .parse(tail)?;
+            Some(RarNewRecovery {
+                recovery_sectors,
+                data_sectors,
+            })
+        } else {
+            None
+        };
+        Ok((rest, Self { file, recovery }))
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    vint, BlockHeader, BlockImpl, BlockType, Budget, Rar5Block, Rar5BlockImpl, RarVolumeBlock, Srr,
+    RAR5_SIGNATURE,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+#[derive(Debug)]
+pub enum CreateError {
+    /// The volume is not a RAR archive, or is damaged.
+    InvalidVolume(String),
+    NameTooLong(String),
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for CreateError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl std::fmt::Display for CreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateError::InvalidVolume(name) => write!(f, "{name:?} is not a valid RAR volume"),
+            CreateError::NameTooLong(name) => write!(f, "the name {name:?} is too long"),
+            CreateError::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CreateError {}
+
+/// Header, name length and name of the blocks that only hold a name.
+fn name_block(
+    crc: u16,
+    ty: BlockType,
+    flags: u16,
+    name: &str,
+    out: &mut Vec<u8>,
+) -> Result<(), CreateError> {
+    let size =
+        u16::try_from(7 + 2 + name.len()).map_err(|_| CreateError::NameTooLong(name.to_owned()))?;
+    BlockHeader {
+        crc,
+        ty,
+        flags,
+        size,
+        add_size: 0,
+    }
+    .write(out);
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    out.extend_from_slice(name.as_bytes());
+    Ok(())
+}
+
+/// A volume read a header at a time, seeking past the data left out of srrs.
+struct VolumeReader<'a, R> {
+    name: &'a str,
+    reader: R,
+    start: u64,
+    len: u64,
+    position: u64,
+}
+
+impl<'a, R: Read + Seek> VolumeReader<'a, R> {
+    fn new(name: &'a str, mut reader: R) -> Result<Self, CreateError> {
+        let start = reader.stream_position()?;
+        let len = reader.seek(SeekFrom::End(0))?.saturating_sub(start);
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            name,
+            reader,
+            start,
+            len,
+            position: 0,
+        })
+    }
+
+    fn invalid(&self) -> CreateError {
+        CreateError::InvalidVolume(self.name.to_owned())
+    }
+
+    fn remaining(&self) -> u64 {
+        self.len - self.position
+    }
+
+    /// The next `size` bytes, which must all be in the volume.
+    fn read(&mut self, size: u64) -> Result<Vec<u8>, CreateError> {
+        if size > self.remaining() {
+            return Err(self.invalid());
+        }
+        let mut buffer = vec![0; size as usize];
+        self.reader.read_exact(&mut buffer)?;
+        self.position += size;
+        Ok(buffer)
+    }
+
+    fn skip(&mut self, size: u64) -> Result<(), CreateError> {
+        if size > self.remaining() {
+            return Err(self.invalid());
+        }
+        self.position += size;
+        self.reader
+            .seek(SeekFrom::Start(self.start + self.position))?;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CreateError> {
+        self.position = 0;
+        self.reader.seek(SeekFrom::Start(self.start))?;
+        Ok(())
+    }
+
+    /// The header of the next RAR 1.5-4.x block and the size of the data following it.
+    fn rar_block(&mut self) -> Result<(Vec<u8>, BlockHeader, Option<BlockImpl>, u64), CreateError> {
+        let mut raw = self.read(7)?;
+        let size = u16::from_le_bytes([raw[5], raw[6]]) as u64;
+        raw.extend(self.read(size.saturating_sub(7))?);
+        let (_rest, (header, inner, data_size)) =
+            RarVolumeBlock::parse_header(&raw).map_err(|_| self.invalid())?;
+        Ok((raw, header, inner, data_size))
+    }
+
+    /// The next RAR5 block with the data an srr keeps, skipping the rest.
+    fn rar5_block(&mut self) -> Result<Rar5Block, CreateError> {
+        let mut raw = self.read(4)?;
+        // the header size is a vint of at most 10 bytes
+        loop {
+            let byte = self.read(1)?[0];
+            raw.push(byte);
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if raw.len() == 4 + 10 {
+                return Err(self.invalid());
+            }
+        }
+        let (_rest, size) = vint(&raw[4..]).map_err(|_| self.invalid())?;
+        raw.extend(self.read(size)?);
+        let (_rest, mut block) =
+            Rar5Block::parse_header(&raw, &mut Budget::unlimited()).map_err(|_| self.invalid())?;
+        let stored = block.stored_data_len() as u64;
+        block.raw.extend(self.read(stored)?);
+        self.skip(block.header.data_size - stored)?;
+        Ok(block)
+    }
+}
+
+/// Copies the blocks of a volume the way they are kept in an srr: without file
+/// data and recovery records.
+fn strip_volume<R: Read + Seek>(
+    name: &str,
+    volume: R,
+    out: &mut Vec<u8>,
+) -> Result<(), CreateError> {
+    let mut volume = VolumeReader::new(name, volume)?;
+    let signature = volume.read(volume.remaining().min(RAR5_SIGNATURE.len() as u64))?;
+    if signature == RAR5_SIGNATURE {
+        out.extend_from_slice(RAR5_SIGNATURE);
+        while volume.remaining() > 0 {
+            let block = volume.rar5_block()?;
+            out.extend_from_slice(&block.raw);
+            if matches!(block.inner, Rar5BlockImpl::EndOfArchive(_)) {
+                break;
+            }
+        }
+    } else {
+        volume.rewind()?;
+        let mut first = true;
+        while volume.remaining() > 0 {
+            let (raw, header, inner, data_size) = volume.rar_block()?;
+            if first && header.ty != BlockType::RarMin {
+                return Err(volume.invalid());
+            }
+            first = false;
+            out.extend_from_slice(&raw);
+            match &inner {
+                // comments and the metadata of old style blocks are the only data kept
+                Some(BlockImpl::RarNewSub(sub)) if sub.file.file_name == "CMT" => {
+                    out.extend(volume.read(data_size)?)
+                }
+                Some(
+                    BlockImpl::OldComment(_)
+                    | BlockImpl::OldAuthenticity1(_)
+                    | BlockImpl::OldSubblock(_)
+                    | BlockImpl::OldAuthenticity2(_),
+                ) => out.extend(volume.read(data_size)?),
+                _ => volume.skip(data_size)?,
+            }
+            if header.ty == BlockType::RarMax {
+                break;
+            }
+        }
+        if first {
+            return Err(volume.invalid());
+        }
+    }
+
+    let rest = volume.read(volume.remaining())?;
+    if !rest.is_empty() {
+        // anything after the end of the archive
+        BlockHeader {
+            crc: 0x6C6C,
+            ty: BlockType::SrrRarPadding,
+            flags: 0x8000,
+            size: 7 + 4,
+            add_size: u32::try_from(rest.len()).map_err(|_| volume.invalid())?,
+        }
+        .write(out);
+        out.extend_from_slice(&rest);
+    }
+    Ok(())
+}
+
+impl Srr {
+    /// Builds an srr from the volumes of a RAR set, given in order as their file name
+    /// and contents. `app_name` is recorded in the srr header.
+    pub fn create<'a, I>(app_name: &str, volumes: I) -> Result<Self, CreateError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        Self::create_from_readers(
+            app_name,
+            volumes
+                .into_iter()
+                .map(|(name, volume)| (name, Cursor::new(volume))),
+        )
+    }
+
+    /// Like [`Srr::create`], but reads each volume a header at a time and seeks past
+    /// the packed data, so volumes never have to fit in memory.
+    pub fn create_from_readers<'a, I, R>(app_name: &str, volumes: I) -> Result<Self, CreateError>
+    where
+        I: IntoIterator<Item = (&'a str, R)>,
+        R: Read + Seek,
+    {
+        let mut out = vec![];
+        if app_name.is_empty() {
+            BlockHeader {
+                crc: 0x6969,
+                ty: BlockType::SrrHeader,
+                flags: 0,
+                size: 7,
+                add_size: 0,
+            }
+            .write(&mut out);
+        } else {
+            name_block(0x6969, BlockType::SrrHeader, 0x0001, app_name, &mut out)?;
+        }
+
+        for (name, volume) in volumes {
+            let name = name.replace('\\', "/");
+            // recovery records are always removed
+            name_block(0x7171, BlockType::SrrRarFile, 0x0001, &name, &mut out)?;
+            strip_volume(&name, volume, &mut out)?;
+        }
+
+        let (_rest, srr) =
+            Srr::new(&out).map_err(|_| CreateError::InvalidVolume("the created srr".to_owned()))?;
+        Ok(srr)
+    }
+}
@@ -0,0 +1,366 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A timestamp without a timezone, as RAR stores them in the MS-DOS format.
+///
+/// The fields are kept as read, so the invalid dates some old releases have (month 0,
+/// day 0) survive a round trip through [`DateTime::from_dos`] and [`DateTime::to_dos`].
+/// Use [`DateTime::is_valid`] before relying on them as a calendar date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateTimeError {
+    /// A field is out of range, e.g. month 0 or April 31.
+    Invalid,
+    /// The date is outside the years 1980 to 2107 a DOS timestamp can hold.
+    OutOfRange,
+}
+
+impl std::fmt::Display for DateTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateTimeError::Invalid => write!(f, "not a valid date and time"),
+            DateTimeError::OutOfRange => {
+                write!(f, "only the years 1980 to 2107 fit a DOS timestamp")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DateTimeError {}
+
+impl DateTime {
+    pub const MIN_YEAR: u16 = 1980;
+    pub const MAX_YEAR: u16 = 1980 + 0x7F;
+
+    pub fn new(
+        year: u16,
+        month: u16,
+        day: u16,
+        hour: u16,
+        minute: u16,
+        second: u16,
+    ) -> Result<Self, DateTimeError> {
+        let datetime = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        if !(Self::MIN_YEAR..=Self::MAX_YEAR).contains(&year) {
+            Err(DateTimeError::OutOfRange)
+        } else if !datetime.is_valid() {
+            Err(DateTimeError::Invalid)
+        } else {
+            Ok(datetime)
+        }
+    }
+
+    /// Decodes a DOS timestamp: the date in the high 16 bits, the time in the low 16 bits.
+    pub fn from_dos(stamp: u32) -> Self {
+        let second = (stamp & 0x1F) * 2;
+        let stamp = stamp >> 5;
+        let minute = stamp & 0x3F;
+        let stamp = stamp >> 6;
+        let hour = stamp & 0x1F;
+        let stamp = stamp >> 5;
+        let day = stamp & 0x1F;
+        let stamp = stamp >> 5;
+        let month = stamp & 0x0F;
+        let year = ((stamp >> 4) & 0x7F) + 1980;
+        Self {
+            year: year as u16,
+            month: month as u16,
+            day: day as u16,
+            hour: hour as u16,
+            minute: minute as u16,
+            second: second as u16,
+        }
+    }
+
+    /// Encodes the timestamp the way [`DateTime::from_dos`] reads it. DOS timestamps have
+    /// a two second resolution, odd seconds are rounded down.
+    pub fn to_dos(&self) -> u32 {
+        let year = self.year.saturating_sub(Self::MIN_YEAR) as u32 & 0x7F;
+        (year << 25)
+            | ((self.month as u32 & 0x0F) << 21)
+            | ((self.day as u32 & 0x1F) << 16)
+            | ((self.hour as u32 & 0x1F) << 11)
+            | ((self.minute as u32 & 0x3F) << 5)
+            | ((self.second as u32 / 2) & 0x1F)
+    }
+
+    /// Whether the fields form a real date and time, leap years included.
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u16, month: u16) -> u16 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// ISO 8601 without an offset, e.g. `2012-08-01T21:04:36`.
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// RAR stores local time without saying which timezone, the timestamp is taken as UTC.
+impl TryFrom<DateTime> for SystemTime {
+    type Error = DateTimeError;
+
+    fn try_from(datetime: DateTime) -> Result<Self, Self::Error> {
+        if !datetime.is_valid() {
+            return Err(DateTimeError::Invalid);
+        }
+        let days = days_from_civil(
+            datetime.year.into(),
+            datetime.month.into(),
+            datetime.day.into(),
+        );
+        let seconds = days * 86_400
+            + i64::from(datetime.hour) * 3600
+            + i64::from(datetime.minute) * 60
+            + i64::from(datetime.second);
+        let seconds = u64::try_from(seconds).map_err(|_| DateTimeError::OutOfRange)?;
+        Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+}
+
+/// The time as UTC, fractions of a second are dropped.
+impl TryFrom<SystemTime> for DateTime {
+    type Error = DateTimeError;
+
+    fn try_from(time: SystemTime) -> Result<Self, Self::Error> {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| DateTimeError::OutOfRange)?
+            .as_secs();
+        let days = i64::try_from(seconds / 86_400).map_err(|_| DateTimeError::OutOfRange)?;
+        let (year, month, day) = civil_from_days(days);
+        let year = u16::try_from(year).map_err(|_| DateTimeError::OutOfRange)?;
+        let seconds = seconds % 86_400;
+        DateTime::new(
+            year,
+            month as u16,
+            day as u16,
+            (seconds / 3600) as u16,
+            (seconds / 60 % 60) as u16,
+            (seconds % 60) as u16,
+        )
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<DateTime> for chrono::NaiveDateTime {
+    type Error = DateTimeError;
+
+    fn try_from(datetime: DateTime) -> Result<Self, Self::Error> {
+        chrono::NaiveDate::from_ymd_opt(
+            datetime.year.into(),
+            datetime.month.into(),
+            datetime.day.into(),
+        )
+        .and_then(|date| {
+            date.and_hms_opt(
+                datetime.hour.into(),
+                datetime.minute.into(),
+                datetime.second.into(),
+            )
+        })
+        .filter(|_| datetime.is_valid())
+        .ok_or(DateTimeError::Invalid)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::NaiveDateTime> for DateTime {
+    type Error = DateTimeError;
+
+    fn try_from(datetime: chrono::NaiveDateTime) -> Result<Self, Self::Error> {
+        use chrono::{Datelike as _, Timelike as _};
+        let year = u16::try_from(datetime.year()).map_err(|_| DateTimeError::OutOfRange)?;
+        // a leap second is folded into the second before it
+        DateTime::new(
+            year,
+            datetime.month() as u16,
+            datetime.day() as u16,
+            datetime.hour() as u16,
+            datetime.minute() as u16,
+            datetime.second() as u16,
+        )
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<DateTime> for time::PrimitiveDateTime {
+    type Error = DateTimeError;
+
+    fn try_from(datetime: DateTime) -> Result<Self, Self::Error> {
+        if !datetime.is_valid() {
+            return Err(DateTimeError::Invalid);
+        }
+        let invalid = |_| DateTimeError::Invalid;
+        let month = time::Month::try_from(datetime.month as u8).map_err(invalid)?;
+        let date = time::Date::from_calendar_date(datetime.year.into(), month, datetime.day as u8)
+            .map_err(invalid)?;
+        let time = time::Time::from_hms(
+            datetime.hour as u8,
+            datetime.minute as u8,
+            datetime.second as u8,
+        )
+        .map_err(invalid)?;
+        Ok(time::PrimitiveDateTime::new(date, time))
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<time::PrimitiveDateTime> for DateTime {
+    type Error = DateTimeError;
+
+    fn try_from(datetime: time::PrimitiveDateTime) -> Result<Self, Self::Error> {
+        let year = u16::try_from(datetime.year()).map_err(|_| DateTimeError::OutOfRange)?;
+        DateTime::new(
+            year,
+            u8::from(datetime.month()).into(),
+            datetime.day().into(),
+            datetime.hour().into(),
+            datetime.minute().into(),
+            datetime.second().into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dos_roundtrip() {
+        let datetime = DateTime::from_dos(0x4101_A8D2);
+        assert_eq!(datetime, DateTime::new(2012, 8, 1, 21, 6, 36).unwrap());
+        assert_eq!(datetime.to_string(), "2012-08-01T21:06:36");
+        assert_eq!(datetime.to_dos(), 0x4101_A8D2);
+
+        // every field of the stamp survives, valid or not
+        for stamp in (0..=u32::MAX).step_by(0x0001_0003) {
+            assert_eq!(DateTime::from_dos(stamp).to_dos(), stamp);
+        }
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let zero = DateTime::from_dos(0);
+        assert!(!zero.is_valid());
+        assert_eq!(zero.to_string(), "1980-00-00T00:00:00");
+        assert_eq!(SystemTime::try_from(zero), Err(DateTimeError::Invalid));
+
+        assert_eq!(
+            DateTime::new(2001, 2, 29, 0, 0, 0),
+            Err(DateTimeError::Invalid)
+        );
+        assert!(DateTime::new(2000, 2, 29, 0, 0, 0).is_ok());
+        assert_eq!(
+            DateTime::new(1979, 12, 31, 0, 0, 0),
+            Err(DateTimeError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn system_time() {
+        let datetime = DateTime::new(2012, 8, 1, 21, 6, 37).unwrap();
+        let time = SystemTime::try_from(datetime).unwrap();
+        assert_eq!(
+            time.duration_since(UNIX_EPOCH).unwrap(),
+            Duration::from_secs(1_343_855_197)
+        );
+        assert_eq!(DateTime::try_from(time), Ok(datetime));
+
+        let last = DateTime::new(DateTime::MAX_YEAR, 12, 31, 23, 59, 59).unwrap();
+        let time = SystemTime::try_from(last).unwrap();
+        assert_eq!(DateTime::try_from(time), Ok(last));
+        assert_eq!(
+            DateTime::try_from(time + Duration::from_secs(1)),
+            Err(DateTimeError::OutOfRange)
+        );
+        assert_eq!(
+            DateTime::try_from(UNIX_EPOCH),
+            Err(DateTimeError::OutOfRange)
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono() {
+        let datetime = DateTime::new(2012, 8, 1, 21, 6, 36).unwrap();
+        let naive = chrono::NaiveDateTime::try_from(datetime).unwrap();
+        assert_eq!(naive.to_string(), "2012-08-01 21:06:36");
+        assert_eq!(DateTime::try_from(naive), Ok(datetime));
+        assert!(chrono::NaiveDateTime::try_from(DateTime::from_dos(0)).is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time() {
+        let datetime = DateTime::new(2012, 8, 1, 21, 6, 36).unwrap();
+        let primitive = time::PrimitiveDateTime::try_from(datetime).unwrap();
+        assert_eq!(primitive.year(), 2012);
+        assert_eq!(primitive.month(), time::Month::August);
+        assert_eq!(DateTime::try_from(primitive), Ok(datetime));
+        assert!(time::PrimitiveDateTime::try_from(DateTime::from_dos(0)).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{Block, BlockHeader, BlockImpl, BlockType, Srr, SrrStoredFile};
+
+/// The maximum length of a stored file's path and name.
+/// 0xFFFF - 7 - 4 - 2 = 65522 (0xFFF2)
+const MAX_STORED_NAME_LENGTH: usize = 0xFFF2;
+
+#[derive(Debug)]
+pub enum EditError {
+    NotFound(String),
+    AlreadyExists(String),
+    NameTooLong(usize),
+    FileTooLarge(usize),
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::NotFound(name) => write!(f, "no stored file named {name:?}"),
+            EditError::AlreadyExists(name) => write!(f, "a file named {name:?} is already stored"),
+            EditError::NameTooLong(len) => write!(
+                f,
+                "stored file name is {len} bytes, the maximum is {MAX_STORED_NAME_LENGTH}"
+            ),
+            EditError::FileTooLarge(len) => {
+                write!(f, "stored file is {len} bytes, the maximum is {}", u32::MAX)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl SrrStoredFile {
+    /// Builds a complete stored file block the way ReScene .NET writes them.
+    /// Paths always use "/" as the separator.
+    pub fn to_block(file_name: &str, data: &[u8]) -> Result<Block, EditError> {
+        let file_name = file_name.replace('\\', "/");
+        if file_name.len() > MAX_STORED_NAME_LENGTH {
+            return Err(EditError::NameTooLong(file_name.len()));
+        }
+        let add_size =
+            u32::try_from(data.len()).map_err(|_| EditError::FileTooLarge(data.len()))?;
+
+        let header = BlockHeader {
+            crc: 0x6A6A,
+            ty: BlockType::SrrStoredFile,
+            flags: 0x8000,
+            size: (7 + 4 + 2 + file_name.len()) as u16,
+            add_size,
+        };
+        let mut raw = Vec::with_capacity(header.full_size());
+        header.write(&mut raw);
+        raw.extend_from_slice(&(file_name.len() as u16).to_le_bytes());
+        raw.extend_from_slice(file_name.as_bytes());
+        raw.extend_from_slice(data);
+
+        Ok(Block {
+            header,
+            inner: Some(BlockImpl::SrrStoredFile(SrrStoredFile { file_name })),
+            raw,
+        })
+    }
+}
+
+impl Srr {
+    /// All stored files along with their contents, in the order they appear.
+    pub fn stored_files(&self) -> impl Iterator<Item = (&SrrStoredFile, &[u8])> {
+        self.blocks.iter().filter_map(|block| match &block.inner {
+            Some(BlockImpl::SrrStoredFile(file)) => Some((file, block.data())),
+            _ => None,
+        })
+    }
+
+    pub fn stored_file(&self, file_name: &str) -> Option<&[u8]> {
+        self.stored_files()
+            .find_map(|(file, data)| (file.file_name == file_name).then_some(data))
+    }
+
+    fn stored_file_index(&self, file_name: &str) -> Option<usize> {
+        self.blocks.iter().position(|block| {
+            matches!(&block.inner, Some(BlockImpl::SrrStoredFile(file)) if file.file_name == file_name)
+        })
+    }
+
+    /// Stores an additional file (e.g. a .srs sample or a proof image) after
+    /// any files already stored. The RAR blocks are left untouched.
+    pub fn add_stored_file(&mut self, file_name: &str, data: &[u8]) -> Result<(), EditError> {
+        let file_name = file_name.replace('\\', "/");
+        if self.stored_file_index(&file_name).is_some() {
+            return Err(EditError::AlreadyExists(file_name));
+        }
+        let block = SrrStoredFile::to_block(&file_name, data)?;
+
+        let index = self
+            .blocks
+            .iter()
+            .rposition(|block| {
+                matches!(
+                    block.header.ty,
+                    BlockType::SrrHeader | BlockType::SrrStoredFile
+                )
+            })
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        self.blocks.insert(index, block);
+        Ok(())
+    }
+
+    /// Removes a stored file, returning its contents.
+    pub fn remove_stored_file(&mut self, file_name: &str) -> Result<Vec<u8>, EditError> {
+        let file_name = file_name.replace('\\', "/");
+        let index = self
+            .stored_file_index(&file_name)
+            .ok_or(EditError::NotFound(file_name))?;
+        let block = self.blocks.remove(index);
+        Ok(block.data().to_vec())
+    }
+
+    /// Renames a stored file in place, keeping its contents and position.
+    pub fn rename_stored_file(&mut self, from: &str, to: &str) -> Result<(), EditError> {
+        let from = from.replace('\\', "/");
+        let index = self
+            .stored_file_index(&from)
+            .ok_or(EditError::NotFound(from.clone()))?;
+        let to = to.replace('\\', "/");
+        if to != from && self.stored_file_index(&to).is_some() {
+            return Err(EditError::AlreadyExists(to));
+        }
+        self.blocks[index] = SrrStoredFile::to_block(&to, self.blocks[index].data())?;
+        Ok(())
+    }
+}
@@ -1,9 +1,38 @@
+mod analyze;
 mod blocks;
+mod create;
+mod datetime;
+mod edit;
+mod options;
+mod rar;
+mod rar5;
+mod reconstruct;
+mod sample;
+#[cfg(feature = "serde")]
+mod serde_hex;
+mod sets;
+mod srs;
+mod verify;
+mod volume;
 
+pub use analyze::*;
 pub use blocks::*;
+pub use create::*;
+pub use datetime::*;
+pub use edit::*;
 use nom::Parser as _;
+pub use options::*;
+pub use rar::*;
+pub use rar5::*;
+pub use reconstruct::*;
+pub use sample::*;
+pub use srs::*;
+pub use verify::*;
+pub use volume::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u8)]
 pub enum BlockType {
     RarVolumeHeader = 0x73,
@@ -55,8 +84,10 @@ impl TryFrom<u8> for BlockType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockHeader {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
     pub crc: u16,
     pub ty: BlockType,
     pub flags: u16,
@@ -64,6 +95,14 @@ pub struct BlockHeader {
     pub add_size: u32,
 }
 
+/// Splits off the first `count` bytes, failing instead of panicking when there are fewer.
+fn take(count: usize, i: &[u8]) -> nom::IResult<&[u8], &[u8]> {
+    match i.split_at_checked(count) {
+        Some((taken, rest)) => Ok((rest, taken)),
+        None => Err(nom::Err::Incomplete(nom::Needed::new(count - i.len()))),
+    }
+}
+
 fn take1(i: &[u8]) -> nom::IResult<&[u8], u8> {
     match i.split_first() {
         Some((v, i)) => Ok((i, *v)),
@@ -76,14 +115,34 @@ impl BlockHeader {
         self.size as usize + self.add_size as usize
     }
 
+    pub fn has_add_size(&self) -> bool {
+        (self.flags & 0x8000) > 0
+            || matches!(
+                self.ty,
+                BlockType::RarPackedFile | BlockType::RarNewSub | BlockType::OldSubblock
+            )
+    }
+
+    /// Encodes the header fields in the same layout `parse` reads them.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.crc.to_le_bytes());
+        out.push(self.ty as u8);
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        if self.has_add_size() {
+            out.extend_from_slice(&self.add_size.to_le_bytes());
+        }
+    }
+
     pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
         fn parse_block_type(b: &[u8]) -> nom::IResult<&[u8], BlockType> {
-            match BlockType::try_from(b[0]) {
-                Ok(ty) => Ok((b, ty)),
-                Err(_err) => Err(nom::Err::Error(nom::error_position!(
+            match b.first().map(|&ty| BlockType::try_from(ty)) {
+                Some(Ok(ty)) => Ok((b, ty)),
+                Some(Err(_err)) => Err(nom::Err::Error(nom::error_position!(
                     b,
                     nom::error::ErrorKind::Tag
                 ))),
+                None => Err(nom::Err::Incomplete(nom::Needed::new(1))),
             }
         }
 
@@ -94,11 +153,22 @@ impl BlockHeader {
         let (rest, flags) = nom::number::le_u16().parse(rest)?;
         let (rest, size) = nom::number::le_u16().parse(rest)?;
 
-        let has_add_size =
-            (flags & 0x8000) > 0 || matches!(ty, BlockType::RarPackedFile | BlockType::RarNewSub);
+        // subblocks always have a data size, even when the flag is missing
+        let has_add_size = (flags & 0x8000) > 0
+            || matches!(
+                ty,
+                BlockType::RarPackedFile | BlockType::RarNewSub | BlockType::OldSubblock
+            );
         let (rest, add_size) = nom::combinator::cond(has_add_size, nom::number::le_u32())
             .map(|add_size| add_size.unwrap_or(0))
             .parse(rest)?;
+        if (size as usize) < input.len() - rest.len() {
+            // the size includes the fields above, anything smaller is garbage
+            return Err(nom::Err::Error(nom::error_position!(
+                input,
+                nom::error::ErrorKind::Verify
+            )));
+        }
 
         Ok((
             rest,
@@ -114,101 +184,191 @@ impl BlockHeader {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Srr {
     pub blocks: Vec<Block>,
 }
 
 impl Srr {
     pub fn new(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        match Self::parse_blocks(input, &mut Budget::unlimited()) {
+            Ok(srr) => Ok((&input[input.len()..], srr)),
+            Err(Failure::Nom(err)) => Err(err),
+            Err(Failure::Limit(_)) => Err(nom::Err::Failure(nom::error::make_error(
+                input,
+                nom::error::ErrorKind::TooLarge,
+            ))),
+        }
+    }
+
+    /// Parses an srr from an untrusted source, stopping as soon as it exceeds a limit.
+    pub fn parse_with_options(input: &[u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        Self::parse_with_budget(input, &mut Budget::new(options))
+    }
+
+    /// Like [`Srr::parse_with_options`], with a budget that may be shared with other srrs.
+    pub(crate) fn parse_with_budget(input: &[u8], budget: &mut Budget) -> Result<Self, ParseError> {
+        Self::parse_blocks(input, budget).map_err(|failure| match failure {
+            Failure::Nom(nom::Err::Incomplete(_)) => ParseError::Incomplete,
+            Failure::Nom(nom::Err::Error(err) | nom::Err::Failure(err)) => ParseError::Invalid {
+                offset: nom::Offset::offset(input, err.input),
+            },
+            Failure::Limit(err) => err,
+        })
+    }
+
+    fn parse_blocks<'a>(input: &'a [u8], budget: &mut Budget) -> Result<Self, Failure<'a>> {
         let mut offset = 0;
         let mut blocks = vec![];
         while offset < input.len() {
-            let (rest, header) = BlockHeader::parse(&input[offset..])?;
+            let start = offset;
+            let (rest, mut header) = BlockHeader::parse(&input[offset..])?;
             let consumed = input[offset..].len() - rest.len();
 
-            match header.ty {
+            let inner = match header.ty {
+                BlockType::RarMin if input[offset..].starts_with(RAR5_SIGNATURE) => {
+                    // the RAR5 signature is one byte longer than the old marker block
+                    header.size = RAR5_SIGNATURE.len() as u16;
+                    offset += RAR5_SIGNATURE.len();
+                    let (rest, volume) = Rar5Volume::parse_with_budget(&input[offset..], budget)?;
+                    offset += input[offset..].len() - rest.len();
+                    Some(BlockImpl::Rar5(volume))
+                }
                 BlockType::RarVolumeHeader => {
                     offset += header.size as usize;
+                    Some(BlockImpl::RarVolumeHeader)
                 }
                 BlockType::RarPackedFile => {
                     offset += consumed;
-                    let size = header.size as usize - consumed;
-                    let (_rest, block) = RarPackedFile::parse(&input[offset..][..size], &header)?;
-                    offset += size;
-                    blocks.push(Block {
-                        header,
-                        inner: Some(BlockImpl::RarPackedFile(block)),
-                    });
+                    let (_rest, fields) = take(header.size as usize - consumed, &input[offset..])?;
+                    let (_rest, block) = RarPackedFile::parse(fields, &header)?;
+                    offset += fields.len();
+                    Some(BlockImpl::RarPackedFile(block))
                 }
                 BlockType::RarOldRecovery => {
                     offset += consumed;
                     let (rest, block) = RarOldRecovery::parse(&input[offset..])?;
                     offset += input[offset..].len() - rest.len();
-                    blocks.push(Block {
-                        header,
-                        inner: Some(BlockImpl::RarOldRecovery(block)),
-                    });
+                    Some(BlockImpl::RarOldRecovery(block))
                 }
                 BlockType::RarNewSub => {
                     offset += consumed;
-                    let size = header.size as usize - consumed;
-                    let (_rest, block) = RarPackedFile::parse(&input[offset..], &header)?;
-                    offset += size;
-                    if block.file_name == "RR" {
-                        offset -= 8 + 4 + 8;
-                        let (rest, (_tag, _recovery_sectors, _data_sectors)) = (
-                            nom::bytes::tag(&b"Protect+"[..]),
-                            nom::number::le_u32(),
-                            nom::number::le_u64(),
-                        )
-                            .parse(&input[offset..])?;
-                        offset += input[offset..].len() - rest.len();
-                    } else if block.file_name == "CMT" {
-                        offset += 8 + 4 + 8 + 4;
+                    let (_rest, fields) = take(header.size as usize - consumed, &input[offset..])?;
+                    let (_rest, block) = RarNewSub::parse(fields, &header)?;
+                    offset += fields.len();
+                    if block.file.file_name == "CMT" {
+                        // the comment data is kept in the srr
+                        offset += header.add_size as usize;
                     }
+                    Some(BlockImpl::RarNewSub(block))
                 }
                 BlockType::SrrHeader => {
                     offset += header.full_size();
-                    blocks.push(Block {
-                        header,
-                        inner: None,
-                    });
+                    None
                 }
                 BlockType::SrrStoredFile => {
+                    budget
+                        .stored_file(header.add_size as u64)
+                        .map_err(Failure::Limit)?;
                     offset += consumed;
-                    let size = header.size as usize - consumed;
-                    let (_rest, block) = SrrStoredFile::new(&input[offset..][..size])?;
-                    offset += size + header.add_size as usize;
-                    blocks.push(Block {
-                        header,
-                        inner: Some(BlockImpl::SrrStoredFile(block)),
-                    });
+                    let (_rest, fields) = take(header.size as usize - consumed, &input[offset..])?;
+                    let (_rest, block) = SrrStoredFile::new(fields)?;
+                    offset += fields.len() + header.add_size as usize;
+                    Some(BlockImpl::SrrStoredFile(block))
                 }
                 BlockType::SrrRarFile => {
                     offset += consumed;
                     let (rest, block) = SrrRarFile::new(&input[offset..])?;
                     let consumed = input[offset..].len() - rest.len();
                     offset += consumed;
-                    blocks.push(Block {
-                        header,
-                        inner: Some(BlockImpl::SrrRarFile(block)),
-                    })
+                    Some(BlockImpl::SrrRarFile(block))
+                }
+                BlockType::SrrRarPadding => {
+                    offset += header.full_size();
+                    Some(BlockImpl::SrrRarPadding)
                 }
-                BlockType::SrrRarPadding => todo!(),
-                BlockType::SrrOsoHash
-                | BlockType::OldComment
+                BlockType::OldComment
                 | BlockType::OldAuthenticity1
                 | BlockType::OldSubblock
-                | BlockType::OldAuthenticity2
-                | BlockType::RarMin
-                | BlockType::RarMax => {
+                | BlockType::OldAuthenticity2 => {
+                    offset += consumed;
+                    let (_rest, fields) = take(header.size as usize - consumed, &input[offset..])?;
+                    let (_rest, block) = parse_old_block(header.ty, fields)?;
+                    // unlike recovery records, the data of old blocks is kept in the srr
+                    offset += fields.len() + header.add_size as usize;
+                    block
+                }
+                BlockType::SrrOsoHash | BlockType::RarMin | BlockType::RarMax => {
                     // won't implement
                     offset += header.size as usize;
+                    None
                 }
-            }
+            };
+
+            let Some(raw) = input.get(start..offset) else {
+                // the sizes point past the end of the srr
+                return Err(nom::Err::Incomplete(nom::Needed::new(offset - input.len())).into());
+            };
+            budget.block(raw, inner.as_ref()).map_err(Failure::Limit)?;
+            blocks.push(Block {
+                header,
+                inner,
+                raw: raw.to_vec(),
+            });
+        }
+
+        Ok(Self { blocks })
+    }
+
+    /// Serializes the srr back into its on-disk representation.
+    pub fn write<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        for block in &self.blocks {
+            writer.write_all(&block.raw)?;
         }
+        Ok(())
+    }
 
-        Ok((&input[offset..], Self { blocks }))
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.raw)
+            .copied()
+            .collect()
+    }
+
+    /// Name of the application that created the srr, if it recorded one.
+    pub fn app_name(&self) -> Option<String> {
+        let block = self
+            .blocks
+            .iter()
+            .find(|block| block.header.ty == BlockType::SrrHeader)?;
+        if block.header.flags & 0x0001 == 0 {
+            return None;
+        }
+        let fields = block.raw.get(7..block.header.size as usize)?;
+        let (_rest, name) =
+            nom::multi::length_data(nom::number::le_u16::<_, nom::error::Error<_>>())
+                .parse(fields)
+                .ok()?;
+        Some(String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// Every RAR volume along with the blocks copied from it.
+    pub fn rar_volumes(&self) -> Vec<(&SrrRarFile, &[Block])> {
+        let mut volumes = vec![];
+        let mut current: Option<(&SrrRarFile, usize)> = None;
+        for (index, block) in self.blocks.iter().enumerate() {
+            if let Some(BlockImpl::SrrRarFile(file)) = &block.inner {
+                if let Some((previous, start)) = current {
+                    volumes.push((previous, &self.blocks[start..index]));
+                }
+                current = Some((file, index + 1));
+            }
+        }
+        if let Some((previous, start)) = current {
+            volumes.push((previous, &self.blocks[start..]));
+        }
+        volumes
     }
 }
 
@@ -265,4 +425,21 @@ mod tests {
     fn thickos() {
         let _srr = load_srr("Thickos.scen0r.zine.Issue.01-THiCK0S.srr");
     }
+
+    #[test]
+    fn malformed_headers() {
+        // a size smaller than the header itself used to underflow, or loop forever at 0
+        for size in [0u8, 3, 6] {
+            let input = [
+                0x6A, 0x6A, 0x6A, 0x00, 0x80, size, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ];
+            assert!(Srr::new(&input).is_err());
+        }
+        // stored file data running past the end of the input
+        let input = [
+            0x6A, 0x6A, 0x6A, 0x00, 0x80, 0x0E, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x01, 0x00, b'a',
+        ];
+        assert!(Srr::new(&input).is_err());
+        assert!(BlockHeader::parse(&[0x69, 0x69]).is_err());
+    }
 }
@@ -0,0 +1,193 @@
+use crate::BlockImpl;
+
+/// Limits on the work done parsing an srr, for input from untrusted sources. The
+/// default has no limits, set the ones that matter:
+///
+/// ```
+/// let options = srr::ParseOptions {
+///     max_stored_file_size: 1024 * 1024,
+///     max_blocks: 10_000,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Largest stored file (.sfv, .nfo, ...) in bytes.
+    pub max_stored_file_size: u64,
+    /// Most blocks in the srr, the headers of RAR5 volumes included.
+    pub max_blocks: usize,
+    /// Longest stored file, RAR volume or archived file name in bytes.
+    pub max_file_name_length: usize,
+    /// Most bytes copied out of the input into the parsed blocks.
+    pub max_memory: usize,
+}
+
+impl ParseOptions {
+    const UNLIMITED: Self = Self {
+        max_stored_file_size: u64::MAX,
+        max_blocks: usize::MAX,
+        max_file_name_length: usize::MAX,
+        max_memory: usize::MAX,
+    };
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    StoredFileSize,
+    Blocks,
+    FileNameLength,
+    Memory,
+    /// How deep srrs are stored in srrs, see [`crate::Srr::nested_srrs`].
+    NestingDepth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ended in the middle of a block.
+    Incomplete,
+    /// The input is not a valid srr, parsing stopped at `offset`.
+    Invalid { offset: usize },
+    /// The srr needs more than the [`ParseOptions`] allow.
+    LimitExceeded { limit: Limit, max: u64, actual: u64 },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Incomplete => write!(f, "the srr is truncated"),
+            ParseError::Invalid { offset } => write!(f, "invalid srr block at offset {offset}"),
+            ParseError::LimitExceeded { limit, max, actual } => {
+                let limit = match limit {
+                    Limit::StoredFileSize => "stored file size",
+                    Limit::Blocks => "number of blocks",
+                    Limit::FileNameLength => "file name length",
+                    Limit::Memory => "memory",
+                    Limit::NestingDepth => "nesting depth",
+                };
+                write!(f, "{limit} of {actual} exceeds the limit of {max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Why parsing stopped: a malformed block, or one the options do not allow.
+pub(crate) enum Failure<'a> {
+    Nom(nom::Err<nom::error::Error<&'a [u8]>>),
+    Limit(ParseError),
+}
+
+impl<'a> Failure<'a> {
+    /// For parsers without options, which can't run out of budget.
+    pub(crate) fn into_nom(self) -> nom::Err<nom::error::Error<&'a [u8]>> {
+        match self {
+            Failure::Nom(err) => err,
+            Failure::Limit(_) => unreachable!("no limits without options"),
+        }
+    }
+}
+
+impl<'a> From<nom::Err<nom::error::Error<&'a [u8]>>> for Failure<'a> {
+    fn from(err: nom::Err<nom::error::Error<&'a [u8]>>) -> Self {
+        Failure::Nom(err)
+    }
+}
+
+/// Keeps count of what the blocks parsed so far use up.
+pub(crate) struct Budget<'o> {
+    options: &'o ParseOptions,
+    blocks: usize,
+    memory: usize,
+}
+
+impl<'o> Budget<'o> {
+    pub(crate) fn new(options: &'o ParseOptions) -> Self {
+        Self {
+            options,
+            blocks: 0,
+            memory: 0,
+        }
+    }
+
+    pub(crate) fn unlimited() -> Budget<'static> {
+        Budget::new(&ParseOptions::UNLIMITED)
+    }
+
+    fn check(limit: Limit, max: u64, actual: u64) -> Result<(), ParseError> {
+        if actual > max {
+            Err(ParseError::LimitExceeded { limit, max, actual })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn stored_file(&self, size: u64) -> Result<(), ParseError> {
+        Self::check(
+            Limit::StoredFileSize,
+            self.options.max_stored_file_size,
+            size,
+        )
+    }
+
+    pub(crate) fn file_name(&self, length: u64) -> Result<(), ParseError> {
+        Self::check(
+            Limit::FileNameLength,
+            self.options.max_file_name_length as u64,
+            length,
+        )
+    }
+
+    /// Accounts for `size` more bytes copied out of the input.
+    pub(crate) fn memory(&mut self, size: usize) -> Result<(), ParseError> {
+        self.memory = self.memory.saturating_add(size);
+        Self::check(
+            Limit::Memory,
+            self.options.max_memory as u64,
+            self.memory as u64,
+        )
+    }
+
+    fn count_blocks(&mut self, blocks: usize) -> Result<(), ParseError> {
+        self.blocks = self.blocks.saturating_add(blocks);
+        Self::check(
+            Limit::Blocks,
+            self.options.max_blocks as u64,
+            self.blocks as u64,
+        )
+    }
+
+    /// Accounts for a RAR5 header of `size` bytes about to be parsed, before the
+    /// rest of its volume.
+    pub(crate) fn rar5_block(&mut self, size: usize) -> Result<(), ParseError> {
+        self.count_blocks(1)?;
+        self.memory(size)
+    }
+
+    /// Accounts for a block about to be copied out of the input. The headers of RAR5
+    /// volumes are charged while they are parsed.
+    pub(crate) fn block(
+        &mut self,
+        raw: &[u8],
+        inner: Option<&BlockImpl>,
+    ) -> Result<(), ParseError> {
+        let name = match inner {
+            Some(BlockImpl::RarPackedFile(file)) => Some(&file.file_name),
+            Some(BlockImpl::RarNewSub(sub)) => Some(&sub.file.file_name),
+            Some(BlockImpl::SrrStoredFile(file)) => Some(&file.file_name),
+            Some(BlockImpl::SrrRarFile(file)) => Some(&file.file_name),
+            _ => None,
+        };
+        if let Some(name) = name {
+            self.file_name(name.len() as u64)?;
+        }
+        self.count_blocks(1)?;
+        self.memory(raw.len())
+    }
+}
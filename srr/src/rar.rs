@@ -0,0 +1,101 @@
+use crate::{
+    parse_old_block, BlockHeader, BlockImpl, BlockType, RarNewSub, RarOldRecovery, RarPackedFile,
+};
+use nom::Parser as _;
+
+/// A block of an actual RAR 1.5-4.x volume, as opposed to the stripped copy kept in an srr.
+#[derive(Debug)]
+pub struct RarVolumeBlock<'a> {
+    pub header: BlockHeader,
+    pub inner: Option<BlockImpl>,
+    /// The block header, `header.size` bytes.
+    pub raw: &'a [u8],
+    /// Packed file data, recovery records, comments, ... following the header.
+    pub data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub struct RarVolume<'a> {
+    pub blocks: Vec<RarVolumeBlock<'a>>,
+}
+
+impl RarVolumeBlock<'_> {
+    /// Parses the header of the block at the start of `input`, returning the size of
+    /// the data following it.
+    pub(crate) fn parse_header(
+        input: &[u8],
+    ) -> nom::IResult<&[u8], (BlockHeader, Option<BlockImpl>, u64)> {
+        let (rest, header) = BlockHeader::parse(input)?;
+        let consumed = input.len() - rest.len();
+        let (rest, raw) = nom::bytes::complete::take(header.size).parse(input)?;
+        let fields = raw.get(consumed..).unwrap_or_default();
+
+        let (inner, data_size) = match header.ty {
+            BlockType::RarPackedFile => {
+                let (_rest, file) = RarPackedFile::parse(fields, &header)?;
+                let packed_size = file.packed_size;
+                (Some(BlockImpl::RarPackedFile(file)), packed_size)
+            }
+            BlockType::RarNewSub => {
+                let (_rest, sub) = RarNewSub::parse(fields, &header)?;
+                let packed_size = sub.file.packed_size;
+                (Some(BlockImpl::RarNewSub(sub)), packed_size)
+            }
+            BlockType::RarOldRecovery => {
+                let (_rest, recovery) = RarOldRecovery::parse(fields)?;
+                (
+                    Some(BlockImpl::RarOldRecovery(recovery)),
+                    header.add_size as u64,
+                )
+            }
+            BlockType::RarVolumeHeader => {
+                (Some(BlockImpl::RarVolumeHeader), header.add_size as u64)
+            }
+            BlockType::OldComment
+            | BlockType::OldAuthenticity1
+            | BlockType::OldSubblock
+            | BlockType::OldAuthenticity2 => {
+                let (_rest, inner) = parse_old_block(header.ty, fields)?;
+                (inner, header.add_size as u64)
+            }
+            _ => (None, header.add_size as u64),
+        };
+        Ok((rest, (header, inner, data_size)))
+    }
+}
+
+impl<'a> RarVolume<'a> {
+    pub fn parse(input: &'a [u8]) -> nom::IResult<&'a [u8], Self> {
+        let mut offset = 0;
+        let mut blocks = vec![];
+        while offset < input.len() {
+            let start = offset;
+            let (rest, (header, inner, data_size)) =
+                RarVolumeBlock::parse_header(&input[offset..])?;
+            offset += input[offset..].len() - rest.len();
+            let raw = &input[start..offset];
+            let (_rest, data) = nom::bytes::complete::take(data_size).parse(&input[offset..])?;
+            offset += data.len();
+
+            let end = header.ty == BlockType::RarMax;
+            blocks.push(RarVolumeBlock {
+                header,
+                inner,
+                raw,
+                data,
+            });
+            if end {
+                break;
+            }
+        }
+        Ok((&input[offset..], Self { blocks }))
+    }
+
+    /// Packed data of every file in the volume, in order.
+    pub fn packed_files(&self) -> impl Iterator<Item = (&RarPackedFile, &'a [u8])> {
+        self.blocks.iter().filter_map(|block| match &block.inner {
+            Some(BlockImpl::RarPackedFile(file)) => Some((file, block.data)),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,917 @@
+use crate::{Budget, Failure};
+use nom::Parser as _;
+
+/// RAR 5.0 archives start with this signature instead of the RAR 1.5-4.x marker block.
+pub const RAR5_SIGNATURE: &[u8; 8] = b"Rar!\x1a\x07\x01\x00";
+
+/// Variable length integer: 7 bits per byte, lowest bits first, the high bit
+/// marks that another byte follows.
+pub fn vint(input: &[u8]) -> nom::IResult<&[u8], u64> {
+    let mut value = 0u64;
+    for (index, byte) in input.iter().enumerate().take(10) {
+        value |= ((byte & 0x7F) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok((&input[index + 1..], value));
+        }
+    }
+    if input.len() < 10 {
+        Err(nom::Err::Incomplete(nom::Needed::Unknown))
+    } else {
+        Err(nom::Err::Error(nom::error::make_error(
+            input,
+            nom::error::ErrorKind::TooLarge,
+        )))
+    }
+}
+
+fn take_vint(input: &[u8]) -> nom::IResult<&[u8], &[u8]> {
+    let (rest, value) = vint(input)?;
+    nom::bytes::complete::take(value).parse(rest)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Rar5BlockType {
+    MainArchive = 1,
+    File = 2,
+    Service = 3,
+    Encryption = 4,
+    EndOfArchive = 5,
+}
+
+impl TryFrom<u64> for Rar5BlockType {
+    type Error = u64;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        let ty = match value {
+            1 => Self::MainArchive,
+            2 => Self::File,
+            3 => Self::Service,
+            4 => Self::Encryption,
+            5 => Self::EndOfArchive,
+            _ => return Err(value),
+        };
+        Ok(ty)
+    }
+}
+
+/// A parsed header along with its type specific fields and its extra area.
+type HeaderParts<'a> = (Rar5BlockHeader, &'a [u8], &'a [u8]);
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5BlockHeader {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+    pub crc: u32,
+    /// Size of the header starting at the type field, extra area included.
+    pub size: u64,
+    pub ty: Rar5BlockType,
+    pub flags: u64,
+    pub extra_size: u64,
+    pub data_size: u64,
+}
+
+impl Rar5BlockHeader {
+    pub const EXTRA_AREA: u64 = 0x0001;
+    pub const DATA_AREA: u64 = 0x0002;
+    pub const SKIP_IF_UNKNOWN: u64 = 0x0004;
+    pub const SPLIT_BEFORE: u64 = 0x0008;
+    pub const SPLIT_AFTER: u64 = 0x0010;
+
+    /// Parses the common header fields, returning the type specific fields and the extra area.
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], HeaderParts<'_>> {
+        let (rest, crc) = nom::number::le_u32().parse(input)?;
+        let (rest, size) = vint(rest)?;
+        let (rest, header) = nom::bytes::take(size).parse(rest)?;
+
+        let (body, ty) = vint(header)?;
+        let ty = Rar5BlockType::try_from(ty).map_err(|_err| {
+            nom::Err::Error(nom::error::make_error(header, nom::error::ErrorKind::Tag))
+        })?;
+        let (body, flags) = vint(body)?;
+        let (body, extra_size) = if flags & Self::EXTRA_AREA != 0 {
+            vint(body)?
+        } else {
+            (body, 0)
+        };
+        let (body, data_size) = if flags & Self::DATA_AREA != 0 {
+            vint(body)?
+        } else {
+            (body, 0)
+        };
+
+        let Some(split) = body.len().checked_sub(extra_size as usize) else {
+            return Err(nom::Err::Error(nom::error::make_error(
+                body,
+                nom::error::ErrorKind::Eof,
+            )));
+        };
+        let (body, extra) = body.split_at(split);
+
+        Ok((
+            rest,
+            (
+                Self {
+                    crc,
+                    size,
+                    ty,
+                    flags,
+                    extra_size,
+                    data_size,
+                },
+                body,
+                extra,
+            ),
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5Block {
+    pub header: Rar5BlockHeader,
+    pub inner: Rar5BlockImpl,
+    /// The block exactly as it appears in the srr, header included.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+    pub raw: Vec<u8>,
+}
+
+impl Rar5Block {
+    /// Parses the header of the block at the start of `input`, leaving `raw` as just
+    /// the header. The block and its file name are charged to `budget` first.
+    pub(crate) fn parse_header<'a>(
+        input: &'a [u8],
+        budget: &mut Budget,
+    ) -> Result<(&'a [u8], Self), Failure<'a>> {
+        let (rest, (header, body, extra)) = Rar5BlockHeader::parse(input)?;
+        budget
+            .rar5_block(input.len() - rest.len())
+            .map_err(Failure::Limit)?;
+        let inner = match header.ty {
+            Rar5BlockType::MainArchive => {
+                Rar5BlockImpl::MainArchive(Rar5MainArchive::parse(body, extra)?.1)
+            }
+            Rar5BlockType::File => {
+                Rar5BlockImpl::File(Rar5File::parse_with_budget(body, extra, budget)?.1)
+            }
+            Rar5BlockType::Service => {
+                Rar5BlockImpl::Service(Rar5File::parse_with_budget(body, extra, budget)?.1)
+            }
+            Rar5BlockType::EndOfArchive => {
+                Rar5BlockImpl::EndOfArchive(Rar5EndOfArchive::parse(body)?.1)
+            }
+            Rar5BlockType::Encryption => {
+                // everything after this header is encrypted
+                return Err(Failure::Nom(nom::Err::Failure(nom::error::make_error(
+                    input,
+                    nom::error::ErrorKind::Verify,
+                ))));
+            }
+        };
+        let raw = input[..input.len() - rest.len()].to_vec();
+        Ok((rest, Self { header, inner, raw }))
+    }
+
+    /// Whether the stored header CRC32 matches the header bytes.
+    pub fn crc_is_valid(&self) -> bool {
+        let header_end = self.raw.len().saturating_sub(self.stored_data_len());
+        self.raw
+            .get(4..header_end)
+            .is_some_and(|header| crc32fast::hash(header) == self.header.crc)
+    }
+
+    pub(crate) fn stored_data_len(&self) -> usize {
+        if self.stores_data() {
+            self.header.data_size as usize
+        } else {
+            0
+        }
+    }
+
+    /// The data area of file blocks and recovery records is not kept in the srr,
+    /// everything else (comments, quick open data, ...) is.
+    pub(crate) fn stores_data(&self) -> bool {
+        match &self.inner {
+            Rar5BlockImpl::File(_) => false,
+            Rar5BlockImpl::Service(service) => service.file_name != "RR",
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Rar5BlockImpl {
+    MainArchive(Rar5MainArchive),
+    File(Rar5File),
+    Service(Rar5File),
+    EndOfArchive(Rar5EndOfArchive),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5MainArchive {
+    pub archive_flags: u64,
+    pub volume_number: Option<u64>,
+    pub quick_open_offset: Option<u64>,
+    pub recovery_record_offset: Option<u64>,
+}
+
+impl Rar5MainArchive {
+    pub const VOLUME: u64 = 0x0001;
+    pub const VOLUME_NUMBER: u64 = 0x0002;
+    pub const SOLID: u64 = 0x0004;
+    pub const RECOVERY_RECORD: u64 = 0x0008;
+    pub const LOCKED: u64 = 0x0010;
+
+    pub fn parse<'a>(input: &'a [u8], extra: &'a [u8]) -> nom::IResult<&'a [u8], Self> {
+        let (rest, archive_flags) = vint(input)?;
+        let (rest, volume_number) =
+            nom::combinator::cond(archive_flags & Self::VOLUME_NUMBER != 0, vint).parse(rest)?;
+
+        let mut quick_open_offset = None;
+        let mut recovery_record_offset = None;
+        for (ty, data) in ExtraRecords(extra) {
+            let ty = ty?;
+            // locator record
+            if ty == 1 {
+                let (data, flags) = vint(data)?;
+                let (data, quick_open) = nom::combinator::cond(flags & 1 != 0, vint).parse(data)?;
+                let (_data, recovery_record) =
+                    nom::combinator::cond(flags & 2 != 0, vint).parse(data)?;
+                quick_open_offset = quick_open;
+                recovery_record_offset = recovery_record;
+            }
+        }
+
+        Ok((
+            rest,
+            Self {
+                archive_flags,
+                volume_number,
+                quick_open_offset,
+                recovery_record_offset,
+            },
+        ))
+    }
+
+    pub fn is_volume(&self) -> bool {
+        self.archive_flags & Self::VOLUME != 0
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.archive_flags & Self::SOLID != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Rar5HostOS {
+    Windows = 0,
+    Unix = 1,
+}
+
+impl TryFrom<u64> for Rar5HostOS {
+    type Error = u64;
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Windows),
+            1 => Ok(Self::Unix),
+            _ => Err(value),
+        }
+    }
+}
+
+/// File and service headers share the same layout.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5File {
+    pub file_flags: u64,
+    pub unpacked_size: u64,
+    pub attributes: u64,
+    /// Unix time in seconds.
+    pub mtime: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc_option"))]
+    pub data_crc: Option<u32>,
+    pub compression_info: u64,
+    pub os: Rar5HostOS,
+    pub file_name: String,
+    pub extra: Vec<Rar5FileExtra>,
+}
+
+impl Rar5File {
+    pub const DIRECTORY: u64 = 0x0001;
+    pub const MTIME: u64 = 0x0002;
+    pub const CRC32: u64 = 0x0004;
+    pub const UNKNOWN_SIZE: u64 = 0x0008;
+
+    pub fn parse<'a>(input: &'a [u8], extra: &'a [u8]) -> nom::IResult<&'a [u8], Self> {
+        Self::parse_with_budget(input, extra, &mut Budget::unlimited()).map_err(Failure::into_nom)
+    }
+
+    /// Like [`Rar5File::parse`], charging the file name to `budget` before copying it.
+    pub(crate) fn parse_with_budget<'a>(
+        input: &'a [u8],
+        extra: &'a [u8],
+        budget: &mut Budget,
+    ) -> Result<(&'a [u8], Self), Failure<'a>> {
+        let (rest, file_flags) = vint(input)?;
+        let (rest, unpacked_size) = vint(rest)?;
+        let (rest, attributes) = vint(rest)?;
+        let (rest, mtime) =
+            nom::combinator::cond(file_flags & Self::MTIME != 0, nom::number::le_u32())
+                .parse(rest)?;
+        let (rest, data_crc) =
+            nom::combinator::cond(file_flags & Self::CRC32 != 0, nom::number::le_u32())
+                .parse(rest)?;
+        let (rest, compression_info) = vint(rest)?;
+        let (rest, os) = vint(rest).and_then(|(rest, v)| {
+            let os = Rar5HostOS::try_from(v).map_err(|_err| {
+                nom::Err::Error(nom::error::make_error(rest, nom::error::ErrorKind::Tag))
+            })?;
+            Ok((rest, os))
+        })?;
+        let (rest, name_length) = vint(rest)?;
+        budget.file_name(name_length).map_err(Failure::Limit)?;
+        let (rest, file_name) = nom::bytes::complete::take(name_length)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .parse(rest)?;
+
+        let extra = ExtraRecords(extra)
+            .map(|(ty, data)| Rar5FileExtra::parse(ty?, data))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((
+            rest,
+            Self {
+                file_flags,
+                unpacked_size,
+                attributes,
+                mtime,
+                data_crc,
+                compression_info,
+                os,
+                file_name,
+                extra,
+            },
+        ))
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.file_flags & Self::DIRECTORY != 0
+    }
+
+    /// Version of the compression algorithm: 0 for RAR 5.0, 1 for RAR 7.0.
+    pub fn algorithm_version(&self) -> u8 {
+        (self.compression_info & 0x3F) as u8
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.compression_info & 0x40 != 0
+    }
+
+    /// 0 is store, 1 to 5 are fastest to best.
+    pub fn compression_method(&self) -> u8 {
+        ((self.compression_info >> 7) & 0x07) as u8
+    }
+
+    pub fn dictionary_size(&self) -> u64 {
+        0x20000 << ((self.compression_info >> 10) & 0x0F)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Rar5FileExtra {
+    Hash(Rar5FileHash),
+    Time(Rar5FileTime),
+    Version(u64),
+    Redirection {
+        ty: u64,
+        flags: u64,
+        target: String,
+    },
+    UnixOwner {
+        user: Option<String>,
+        group: Option<String>,
+        user_id: Option<u64>,
+        group_id: Option<u64>,
+    },
+    /// Encryption, service data and record types added after RAR 5.0.
+    Other {
+        ty: u64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Rar5FileHash {
+    Blake2sp(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))] [u8; 32]),
+    Unknown {
+        ty: u64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+        data: Vec<u8>,
+    },
+}
+
+/// Times are either Unix time (in seconds or nanoseconds) or Windows FILETIME.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5FileTime {
+    pub flags: u64,
+    pub mtime: Option<u64>,
+    pub ctime: Option<u64>,
+    pub atime: Option<u64>,
+}
+
+impl Rar5FileTime {
+    pub const UNIX: u64 = 0x0001;
+    pub const MTIME: u64 = 0x0002;
+    pub const CTIME: u64 = 0x0004;
+    pub const ATIME: u64 = 0x0008;
+    pub const UNIX_NS: u64 = 0x0010;
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, flags) = vint(input)?;
+        let unix = flags & Self::UNIX != 0;
+        let time = |i| -> nom::IResult<&[u8], u64> {
+            if unix {
+                nom::number::le_u32().map(u64::from).parse(i)
+            } else {
+                nom::number::le_u64().parse(i)
+            }
+        };
+        let (rest, mtime) = nom::combinator::cond(flags & Self::MTIME != 0, time).parse(rest)?;
+        let (rest, ctime) = nom::combinator::cond(flags & Self::CTIME != 0, time).parse(rest)?;
+        let (rest, atime) = nom::combinator::cond(flags & Self::ATIME != 0, time).parse(rest)?;
+
+        let mut time = Self {
+            flags,
+            mtime,
+            ctime,
+            atime,
+        };
+        let rest = if unix && flags & Self::UNIX_NS != 0 {
+            let nanos = |i| nom::number::le_u32().map(u64::from).parse(i);
+            let mut rest = rest;
+            for value in [&mut time.mtime, &mut time.ctime, &mut time.atime]
+                .into_iter()
+                .flatten()
+            {
+                let (r, ns) = nanos(rest)?;
+                *value = value
+                    .saturating_mul(1_000_000_000)
+                    .saturating_add(ns & 0x3FFFFFFF);
+                rest = r;
+            }
+            rest
+        } else {
+            rest
+        };
+
+        Ok((rest, time))
+    }
+}
+
+impl Rar5FileExtra {
+    fn parse(ty: u64, data: &[u8]) -> Result<Self, nom::Err<nom::error::Error<&[u8]>>> {
+        let extra = match ty {
+            0x02 => {
+                let (data, hash_type) = vint(data)?;
+                let hash = match hash_type {
+                    0 => {
+                        let (_data, hash) = nom::bytes::complete::take(32usize).parse(data)?;
+                        Rar5FileHash::Blake2sp(hash.try_into().unwrap())
+                    }
+                    ty => Rar5FileHash::Unknown {
+                        ty,
+                        data: data.to_vec(),
+                    },
+                };
+                Self::Hash(hash)
+            }
+            0x03 => Self::Time(Rar5FileTime::parse(data)?.1),
+            0x04 => {
+                let (data, _flags) = vint(data)?;
+                Self::Version(vint(data)?.1)
+            }
+            0x05 => {
+                let (data, ty) = vint(data)?;
+                let (data, flags) = vint(data)?;
+                let (_data, target) = take_vint(data)?;
+                Self::Redirection {
+                    ty,
+                    flags,
+                    target: String::from_utf8_lossy(target).into_owned(),
+                }
+            }
+            0x06 => {
+                let name = |i| {
+                    take_vint
+                        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                        .parse(i)
+                };
+                let (data, flags) = vint(data)?;
+                let (data, user) = nom::combinator::cond(flags & 1 != 0, name).parse(data)?;
+                let (data, group) = nom::combinator::cond(flags & 2 != 0, name).parse(data)?;
+                let (data, user_id) = nom::combinator::cond(flags & 4 != 0, vint).parse(data)?;
+                let (_data, group_id) = nom::combinator::cond(flags & 8 != 0, vint).parse(data)?;
+                Self::UnixOwner {
+                    user,
+                    group,
+                    user_id,
+                    group_id,
+                }
+            }
+            ty => Self::Other {
+                ty,
+                data: data.to_vec(),
+            },
+        };
+        Ok(extra)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5EndOfArchive {
+    pub flags: u64,
+}
+
+impl Rar5EndOfArchive {
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (rest, flags) = vint(input)?;
+        Ok((rest, Self { flags }))
+    }
+
+    pub fn is_last_volume(&self) -> bool {
+        self.flags & 0x0001 == 0
+    }
+}
+
+/// Iterates over the records of an extra area as (type, data) pairs.
+struct ExtraRecords<'a>(&'a [u8]);
+
+impl<'a> Iterator for ExtraRecords<'a> {
+    type Item = (Result<u64, nom::Err<nom::error::Error<&'a [u8]>>>, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let record = take_vint(self.0).and_then(|(rest, record)| {
+            let (data, ty) = vint(record)?;
+            Ok((rest, (ty, data)))
+        });
+        match record {
+            Ok((rest, (ty, data))) => {
+                self.0 = rest;
+                Some((Ok(ty), data))
+            }
+            Err(err) => {
+                self.0 = &[];
+                Some((Err(err), &[]))
+            }
+        }
+    }
+}
+
+/// The headers of a single RAR5 volume as stored in an srr, from the
+/// main archive header up to and including the end of archive header.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rar5Volume {
+    pub blocks: Vec<Rar5Block>,
+}
+
+impl Rar5Volume {
+    /// Parses the headers following the RAR5 signature. Stops after the end
+    /// of archive header, or at the next srr block for volumes written without one.
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        Self::parse_with_budget(input, &mut Budget::unlimited()).map_err(Failure::into_nom)
+    }
+
+    /// Like [`Rar5Volume::parse`], charging every block to `budget` as it is parsed.
+    pub(crate) fn parse_with_budget<'a>(
+        input: &'a [u8],
+        budget: &mut Budget,
+    ) -> Result<(&'a [u8], Self), Failure<'a>> {
+        Self::parse_blocks(input, true, budget)
+    }
+
+    /// Parses the headers of an actual RAR5 volume following the signature, skipping
+    /// the data left out of srrs. The blocks are left as an srr stores them.
+    pub fn parse_archive(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        Self::parse_blocks(input, false, &mut Budget::unlimited()).map_err(Failure::into_nom)
+    }
+
+    fn parse_blocks<'a>(
+        input: &'a [u8],
+        stripped: bool,
+        budget: &mut Budget,
+    ) -> Result<(&'a [u8], Self), Failure<'a>> {
+        fn is_srr_block(input: &[u8]) -> bool {
+            matches!(input, [b @ (0x69 | 0x6A | 0x6B | 0x6C | 0x71), c, d, ..] if b == c && c == d)
+        }
+
+        let mut offset = 0;
+        let mut blocks = vec![];
+        while offset < input.len() && !(stripped && is_srr_block(&input[offset..])) {
+            let (rest, mut block) = Rar5Block::parse_header(&input[offset..], budget)?;
+            offset += input[offset..].len() - rest.len();
+            let data_size = block.stored_data_len();
+            let (_rest, data) = nom::bytes::take(data_size).parse(&input[offset..])?;
+            budget.memory(data.len()).map_err(Failure::Limit)?;
+            block.raw.extend_from_slice(data);
+            if stripped {
+                offset += data_size;
+            } else {
+                let (_rest, _data) =
+                    nom::bytes::take(block.header.data_size).parse(&input[offset..])?;
+                offset += block.header.data_size as usize;
+            }
+
+            let end = matches!(block.inner, Rar5BlockImpl::EndOfArchive(_));
+            blocks.push(block);
+            if end {
+                break;
+            }
+        }
+
+        Ok((&input[offset..], Self { blocks }))
+    }
+
+    pub fn main_archive(&self) -> Option<&Rar5MainArchive> {
+        self.blocks.iter().find_map(|block| match &block.inner {
+            Rar5BlockImpl::MainArchive(main) => Some(main),
+            _ => None,
+        })
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Rar5File> {
+        self.blocks.iter().filter_map(|block| match &block.inner {
+            Rar5BlockImpl::File(file) => Some(file),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockImpl, Srr};
+
+    fn encode_vint(mut value: u64) -> Vec<u8> {
+        let mut out = vec![];
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return out;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn block(ty: u64, flags: u64, body: &[u8], extra: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut header = encode_vint(ty);
+        let mut flags = flags;
+        if !extra.is_empty() {
+            flags |= Rar5BlockHeader::EXTRA_AREA;
+        }
+        header.extend(encode_vint(flags));
+        if !extra.is_empty() {
+            header.extend(encode_vint(extra.len() as u64));
+        }
+        if flags & Rar5BlockHeader::DATA_AREA != 0 {
+            header.extend(encode_vint(data.len() as u64));
+        }
+        header.extend_from_slice(body);
+        header.extend_from_slice(extra);
+
+        let mut crc_data = encode_vint(header.len() as u64);
+        crc_data.extend(header);
+        let mut out = crc32fast::hash(&crc_data).to_le_bytes().to_vec();
+        out.extend(crc_data);
+        out
+    }
+
+    fn record(ty: u64, data: &[u8]) -> Vec<u8> {
+        let mut record = encode_vint(ty);
+        record.extend_from_slice(data);
+        let mut out = encode_vint(record.len() as u64);
+        out.extend(record);
+        out
+    }
+
+    fn file_body(name: &str, unpacked_size: u64, compression_info: u64) -> Vec<u8> {
+        let mut body = encode_vint(Rar5File::MTIME | Rar5File::CRC32);
+        body.extend(encode_vint(unpacked_size));
+        body.extend(encode_vint(0x20));
+        body.extend(0x5F3E_6614u32.to_le_bytes());
+        body.extend(0xDEAD_BEEFu32.to_le_bytes());
+        body.extend(encode_vint(compression_info));
+        body.extend(encode_vint(Rar5HostOS::Windows as u64));
+        body.extend(encode_vint(name.len() as u64));
+        body.extend(name.as_bytes());
+        body
+    }
+
+    fn srr_rar_file(name: &str) -> Vec<u8> {
+        let mut out = vec![0x71, 0x71, 0x71, 0x01, 0x00];
+        out.extend(((7 + 2 + name.len()) as u16).to_le_bytes());
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(name.as_bytes());
+        out
+    }
+
+    fn rar5_srr() -> Vec<u8> {
+        let mut input = vec![0x69, 0x69, 0x69, 0x00, 0x00, 0x07, 0x00];
+
+        input.extend(srr_rar_file("release.part1.rar"));
+        input.extend(RAR5_SIGNATURE);
+        let mut main = encode_vint(Rar5MainArchive::VOLUME);
+        main.extend(encode_vint(0));
+        let locator = [encode_vint(2), encode_vint(0x1234)].concat();
+        input.extend(block(1, 0, &main, &record(1, &locator), &[]));
+        let mut extra = record(
+            0x03,
+            &[
+                &encode_vint(0x02)[..],
+                &0x01D5_F0A0_0000_0000u64.to_le_bytes(),
+            ]
+            .concat(),
+        );
+        extra.extend(record(0x02, &[&[0x00][..], &[0xAB; 32]].concat()));
+        let file = file_body("release.mkv", 1_000_000, 3 << 10);
+        input.extend(block(
+            2,
+            Rar5BlockHeader::DATA_AREA | Rar5BlockHeader::SPLIT_AFTER,
+            &file,
+            &extra,
+            &[0; 100],
+        ));
+        let cmt = file_body("CMT", 5, 0);
+        let mut comment = block(3, Rar5BlockHeader::DATA_AREA, &cmt, &[], b"hello");
+        comment.extend(b"hello");
+        input.extend(comment);
+        input.extend(block(5, 0, &encode_vint(1), &[], &[]));
+
+        // volume written without an end of archive header
+        input.extend(srr_rar_file("release.part2.rar"));
+        input.extend(RAR5_SIGNATURE);
+        let mut main = encode_vint(Rar5MainArchive::VOLUME | Rar5MainArchive::VOLUME_NUMBER);
+        main.extend(encode_vint(1));
+        input.extend(block(1, 0, &main, &[], &[]));
+        input.extend(block(
+            2,
+            Rar5BlockHeader::DATA_AREA | Rar5BlockHeader::SPLIT_BEFORE,
+            &file,
+            &[],
+            &[0; 100],
+        ));
+        input
+    }
+
+    #[test]
+    fn vints() {
+        for value in [0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, u32::MAX as u64, u64::MAX] {
+            let encoded = encode_vint(value);
+            assert_eq!(vint(&encoded), Ok((&[][..], value)));
+        }
+        assert!(vint(&[0x80, 0x80]).is_err());
+    }
+
+    #[test]
+    fn parse_limits() {
+        use crate::{Limit, ParseError, ParseOptions};
+
+        let mut input = vec![0x69, 0x69, 0x69, 0x00, 0x00, 0x07, 0x00];
+        input.extend(srr_rar_file("release.rar"));
+        input.extend(RAR5_SIGNATURE);
+        let tiny = block(1, 0, &encode_vint(0), &[], &[]);
+        for _ in 0..100_000 {
+            input.extend_from_slice(&tiny);
+        }
+        let options = ParseOptions {
+            max_blocks: 100,
+            ..Default::default()
+        };
+        // stops at the first header over the limit, not after the whole volume
+        assert_eq!(
+            Srr::parse_with_options(&input, &options).unwrap_err(),
+            ParseError::LimitExceeded {
+                limit: Limit::Blocks,
+                max: 100,
+                actual: 101,
+            }
+        );
+
+        // the name length is checked before the name is read
+        let mut body = file_body("", 0, 0);
+        body.pop();
+        body.extend(encode_vint(1 << 40));
+        let mut input = vec![0x69, 0x69, 0x69, 0x00, 0x00, 0x07, 0x00];
+        input.extend(srr_rar_file("release.rar"));
+        input.extend(RAR5_SIGNATURE);
+        input.extend(block(2, 0, &body, &[], &[]));
+        let options = ParseOptions {
+            max_file_name_length: 255,
+            ..Default::default()
+        };
+        assert_eq!(
+            Srr::parse_with_options(&input, &options).unwrap_err(),
+            ParseError::LimitExceeded {
+                limit: Limit::FileNameLength,
+                max: 255,
+                actual: 1 << 40,
+            }
+        );
+    }
+
+    #[test]
+    fn srr_with_rar5_volumes() {
+        let input = rar5_srr();
+        let (rest, srr) = Srr::new(&input).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(srr.to_bytes(), input);
+
+        let volumes = srr
+            .blocks
+            .iter()
+            .filter_map(|block| match &block.inner {
+                Some(BlockImpl::Rar5(volume)) => Some(volume),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(volumes.len(), 2);
+        assert!(volumes
+            .iter()
+            .flat_map(|volume| &volume.blocks)
+            .all(Rar5Block::crc_is_valid));
+
+        let first = volumes[0];
+        assert_eq!(first.blocks.len(), 4);
+        let main = first.main_archive().unwrap();
+        assert!(main.is_volume());
+        assert_eq!(main.volume_number, None);
+        assert_eq!(main.recovery_record_offset, Some(0x1234));
+
+        let file = first.files().next().unwrap();
+        assert_eq!(file.file_name, "release.mkv");
+        assert_eq!(file.unpacked_size, 1_000_000);
+        assert_eq!(file.data_crc, Some(0xDEAD_BEEF));
+        assert_eq!(file.compression_method(), 0);
+        assert_eq!(file.dictionary_size(), 1024 * 1024);
+        assert!(matches!(
+            file.extra[..],
+            [
+                Rar5FileExtra::Time(Rar5FileTime {
+                    mtime: Some(0x01D5_F0A0_0000_0000),
+                    ctime: None,
+                    atime: None,
+                    ..
+                }),
+                Rar5FileExtra::Hash(Rar5FileHash::Blake2sp([0xAB, ..])),
+            ]
+        ));
+
+        let Rar5BlockImpl::Service(comment) = &first.blocks[2].inner else {
+            panic!("expected a service header");
+        };
+        assert_eq!(comment.file_name, "CMT");
+        assert!(first.blocks[2].raw.ends_with(b"hello"));
+        let Rar5BlockImpl::EndOfArchive(end) = &first.blocks[3].inner else {
+            panic!("expected an end of archive header");
+        };
+        assert!(!end.is_last_volume());
+
+        assert_eq!(volumes[1].blocks.len(), 2);
+        assert_eq!(volumes[1].main_archive().unwrap().volume_number, Some(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rar5_volumes() {
+        let input = rar5_srr();
+        let (_rest, srr) = Srr::new(&input).unwrap();
+        let json = serde_json::to_value(&srr).unwrap();
+        let file = &json["blocks"][2]["inner"]["rar5"]["blocks"][1];
+        assert_eq!(file["inner"]["file"]["data_crc"], "DEADBEEF");
+        assert_eq!(
+            file["inner"]["file"]["extra"][1]["hash"]["blake2sp"],
+            "ab".repeat(32)
+        );
+
+        let srr: Srr = serde_json::from_value(json).unwrap();
+        assert_eq!(srr.to_bytes(), input);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::{Block, BlockImpl, Rar5BlockImpl, RarPackedFile, RarVolume, Srr};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ReconstructError {
+    Io(std::io::Error),
+    /// An archived file could not be opened in the input directory.
+    MissingFile {
+        file_name: String,
+        error: std::io::Error,
+    },
+    /// An archived file ended before all of its packed data was written.
+    FileTooShort {
+        file_name: String,
+    },
+    /// The compressor backend failed to pack the compressed files.
+    Compression(std::io::Error),
+    /// The compressor produced packed data of a different size than the headers expect,
+    /// usually because a different RAR version or different switches were used.
+    PackedSizeMismatch {
+        file_name: String,
+        expected: u64,
+        actual: u64,
+    },
+    Unsupported(&'static str),
+    /// A volume or archived file name that would leave its directory, like `../x`.
+    UnsafePath(String),
+    /// The sector counts of a recovery record don't fit the volume or its data size.
+    InvalidRecoveryRecord,
+}
+
+impl From<std::io::Error> for ReconstructError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl std::fmt::Display for ReconstructError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReconstructError::Io(error) => error.fmt(f),
+            ReconstructError::MissingFile { file_name, error } => {
+                write!(f, "could not open {file_name:?}: {error}")
+            }
+            ReconstructError::FileTooShort { file_name } => {
+                write!(f, "{file_name:?} is shorter than the archived file")
+            }
+            ReconstructError::Compression(error) => write!(f, "compression failed: {error}"),
+            ReconstructError::PackedSizeMismatch {
+                file_name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{file_name:?} packed to {actual} bytes, the archive has {expected}"
+            ),
+            ReconstructError::Unsupported(what) => write!(f, "{what} can't be reconstructed"),
+            ReconstructError::UnsafePath(name) => {
+                write!(f, "{name:?} is not a relative path inside the directory")
+            }
+            ReconstructError::InvalidRecoveryRecord => {
+                f.write_str("the recovery record doesn't fit the volume")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReconstructError {}
+
+/// A name from the srr as a path relative to a directory. Names come from untrusted
+/// srrs, so only plain components are allowed.
+pub(crate) fn relative_path(name: &str) -> Result<PathBuf, ReconstructError> {
+    let path = PathBuf::from(name.replace('\\', "/"));
+    let is_relative = !name.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)));
+    if is_relative {
+        Ok(path)
+    } else {
+        Err(ReconstructError::UnsafePath(name.to_owned()))
+    }
+}
+
+/// A compressed file that has to be packed again to rebuild its volumes.
+#[derive(Debug)]
+pub struct CompressedFile<'a> {
+    /// Where the archived file can be read from.
+    pub path: PathBuf,
+    /// Flags of the file's first block header.
+    pub flags: u16,
+    pub file: &'a RarPackedFile,
+}
+
+impl CompressedFile<'_> {
+    /// 0 (64 KiB) to 6 (4096 KiB).
+    pub fn dictionary_bits(&self) -> u8 {
+        ((self.flags & RarPackedFile::DICTIONARY_MASK) >> 5) as u8
+    }
+
+    /// Dictionary size in KiB.
+    pub fn dictionary_size(&self) -> u32 {
+        64 << self.dictionary_bits()
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.flags & RarPackedFile::SOLID != 0
+    }
+}
+
+/// Produces the packed data of compressed files, which can only be reproduced by
+/// running the exact RAR version that created the archive.
+pub trait Compressor {
+    /// Packs `files` in the given order and returns the packed data of each of them.
+    /// All compressed files of a release are passed at once, so solid archives can be rebuilt.
+    fn compress(&mut self, files: &[CompressedFile<'_>]) -> std::io::Result<Vec<Vec<u8>>>;
+}
+
+impl<C: Compressor + ?Sized> Compressor for &mut C {
+    fn compress(&mut self, files: &[CompressedFile<'_>]) -> std::io::Result<Vec<Vec<u8>>> {
+        (**self).compress(files)
+    }
+}
+
+/// Only reconstructs releases that were stored, fails on compressed files.
+#[derive(Debug, Default)]
+pub struct StoredOnly;
+
+impl Compressor for StoredOnly {
+    fn compress(&mut self, _files: &[CompressedFile<'_>]) -> std::io::Result<Vec<Vec<u8>>> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "compressed files need a compressor backend",
+        ))
+    }
+}
+
+/// A RAR command line executable (`rar`, `rar.exe`, `rar32`, ...).
+#[derive(Debug, Clone)]
+pub struct RarExecutable {
+    pub path: PathBuf,
+    /// 10 * major version + minor version, e.g. 39 for RAR 3.90.
+    pub version: u8,
+}
+
+impl RarExecutable {
+    /// The unpack version written into the headers of files packed by this version.
+    pub fn unpack_version(&self) -> u8 {
+        match self.version {
+            0..20 => 15,
+            20..29 => 20,
+            _ => 29,
+        }
+    }
+
+    /// The largest dictionary this version can pack with, see `CompressedFile::dictionary_bits`.
+    pub fn max_dictionary_bits(&self) -> u8 {
+        if self.version >= 29 {
+            6
+        } else {
+            4
+        }
+    }
+
+    fn supports(&self, file: &CompressedFile<'_>) -> bool {
+        self.unpack_version() == file.file.unpack_version
+            && self.max_dictionary_bits() >= file.dictionary_bits()
+    }
+}
+
+/// Delegates compression to external RAR executables, picking the first one that
+/// matches the unpack version and dictionary size of the files.
+#[derive(Debug, Default)]
+pub struct RarCompressor {
+    pub executables: Vec<RarExecutable>,
+}
+
+impl RarCompressor {
+    pub fn new(executables: Vec<RarExecutable>) -> Self {
+        Self { executables }
+    }
+}
+
+impl Compressor for RarCompressor {
+    fn compress(&mut self, files: &[CompressedFile<'_>]) -> std::io::Result<Vec<Vec<u8>>> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let Some(first) = files.first() else {
+            return Ok(vec![]);
+        };
+        let executable = self
+            .executables
+            .iter()
+            .find(|executable| files.iter().all(|file| executable.supports(file)))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!(
+                        "no rar executable for unpack version {} with a {} KiB dictionary",
+                        first.file.unpack_version,
+                        first.dictionary_size()
+                    ),
+                )
+            })?;
+
+        // run from the directory the archived paths are relative to
+        let depth = first.file.file_name.split(['\\', '/']).count();
+        let root = first.path.ancestors().nth(depth).unwrap_or(Path::new("."));
+        let archive = std::env::temp_dir().join(format!(
+            "srr-{}-{}.rar",
+            std::process::id(),
+            COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+
+        let mut command = std::process::Command::new(&executable.path);
+        command
+            .current_dir(root)
+            .arg("a")
+            .arg("-y")
+            .arg(format!(
+                "-m{}",
+                first
+                    .file
+                    .compression_method
+                    .saturating_sub(RarPackedFile::METHOD_STORE)
+            ))
+            // RAR5 defaults to megabytes
+            .arg(format!(
+                "-md{}{}",
+                first.dictionary_size(),
+                if executable.version >= 50 { "k" } else { "" }
+            ));
+        if files.iter().any(CompressedFile::is_solid) {
+            command.arg("-s");
+        }
+        if executable.version >= 50 {
+            command.arg("-ma4");
+        }
+        command.arg(&archive);
+        for file in files {
+            command.arg(file.file.file_name.replace('\\', "/"));
+        }
+
+        let output = command.output()?;
+        let packed = std::fs::read(&archive);
+        let _ = std::fs::remove_file(&archive);
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} exited with {}: {}",
+                executable.path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let packed = packed?;
+        let (_rest, volume) = RarVolume::parse(&packed)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
+        let mut data = volume
+            .packed_files()
+            .map(|(file, data)| (file.file_name.clone(), data))
+            .collect::<HashMap<_, _>>();
+        files
+            .iter()
+            .map(|file| {
+                data.remove(&file.file.file_name)
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("{} is missing from the packed archive", file.file.file_name),
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Rebuilds the RAR volumes of a release from its srr and the archived files.
+pub struct Reconstructor<C = StoredOnly> {
+    input_dir: PathBuf,
+    compressor: C,
+}
+
+impl Reconstructor {
+    /// `input_dir` holds the archived files, laid out with the paths stored in the archive.
+    pub fn new<P: Into<PathBuf>>(input_dir: P) -> Self {
+        Self {
+            input_dir: input_dir.into(),
+            compressor: StoredOnly,
+        }
+    }
+}
+
+impl<C: Compressor> Reconstructor<C> {
+    pub fn with_compressor<D: Compressor>(self, compressor: D) -> Reconstructor<D> {
+        Reconstructor {
+            input_dir: self.input_dir,
+            compressor,
+        }
+    }
+
+    fn path(&self, file_name: &str) -> Result<PathBuf, ReconstructError> {
+        Ok(self.input_dir.join(relative_path(file_name)?))
+    }
+
+    /// Writes every volume into the writer `create` returns for its name, in volume
+    /// order. The writers are read back to compute recovery records.
+    pub fn reconstruct<F, W>(&mut self, srr: &Srr, mut create: F) -> Result<(), ReconstructError>
+    where
+        F: FnMut(&str) -> std::io::Result<W>,
+        W: Read + Write + Seek,
+    {
+        let mut sources = self.pack(srr)?;
+        // split files are copied from their source sequentially
+        let mut volumes = srr.rar_volumes();
+        crate::sort_volumes_by_key(&mut volumes, |(rar_file, _blocks)| &rar_file.file_name);
+        for (rar_file, blocks) in volumes {
+            let mut writer = create(&rar_file.file_name)?;
+            for block in blocks {
+                self.write_block(block, &mut writer, &mut sources)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes every volume into `output_dir`.
+    pub fn reconstruct_to_dir<P: AsRef<Path>>(
+        &mut self,
+        srr: &Srr,
+        output_dir: P,
+    ) -> Result<(), ReconstructError> {
+        let output_dir = output_dir.as_ref();
+        for (rar_file, _blocks) in srr.rar_volumes() {
+            relative_path(&rar_file.file_name)?;
+        }
+        self.reconstruct(srr, |file_name| {
+            let path = output_dir.join(relative_path(file_name).map_err(std::io::Error::other)?);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .map(std::io::BufWriter::new)
+                .map(ReadWriteSeek)
+        })
+    }
+
+    /// Runs the compressor over all compressed files, returning their packed data.
+    fn pack(&mut self, srr: &Srr) -> Result<HashMap<String, Source>, ReconstructError> {
+        let mut files = vec![];
+        let mut expected = HashMap::<&str, u64>::new();
+        for block in &srr.blocks {
+            if let Some(BlockImpl::RarPackedFile(file)) = &block.inner {
+                if file.is_stored() {
+                    continue;
+                }
+                *expected.entry(&file.file_name).or_default() += file.packed_size;
+                if block.header.flags & RarPackedFile::SPLIT_BEFORE == 0 {
+                    files.push(CompressedFile {
+                        path: self.path(&file.file_name)?,
+                        flags: block.header.flags,
+                        file,
+                    });
+                }
+            }
+        }
+        if files.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let packed = self
+            .compressor
+            .compress(&files)
+            .map_err(ReconstructError::Compression)?;
+        files
+            .iter()
+            .zip(packed)
+            .map(|(file, packed)| {
+                let file_name = &file.file.file_name;
+                let expected = expected[file_name.as_str()];
+                if expected != packed.len() as u64 {
+                    return Err(ReconstructError::PackedSizeMismatch {
+                        file_name: file_name.clone(),
+                        expected,
+                        actual: packed.len() as u64,
+                    });
+                }
+                Ok((
+                    file_name.clone(),
+                    Box::new(std::io::Cursor::new(packed)) as Source,
+                ))
+            })
+            .collect()
+    }
+
+    fn write_block<W: Read + Write + Seek>(
+        &self,
+        block: &Block,
+        writer: &mut W,
+        sources: &mut HashMap<String, Source>,
+    ) -> Result<(), ReconstructError> {
+        match &block.inner {
+            Some(BlockImpl::RarPackedFile(file)) => {
+                writer.write_all(&block.raw)?;
+                if file.is_stored() && block.header.flags & RarPackedFile::SPLIT_BEFORE == 0 {
+                    sources.remove(&file.file_name);
+                }
+                self.copy_file_data(&file.file_name, file.packed_size, writer, sources)?;
+            }
+            Some(BlockImpl::RarNewSub(sub)) => {
+                let start = writer.stream_position()?;
+                writer.write_all(&block.raw)?;
+                if let Some(recovery) = &sub.recovery {
+                    write_recovery_record(
+                        writer,
+                        start,
+                        recovery.recovery_sectors as u64,
+                        recovery.data_sectors,
+                        sub.file.packed_size,
+                    )?;
+                }
+            }
+            Some(BlockImpl::RarOldRecovery(recovery)) => {
+                let start = writer.stream_position()?;
+                writer.write_all(&block.raw)?;
+                write_recovery_record(
+                    writer,
+                    start,
+                    recovery.recovery_sector as u64,
+                    recovery.data_sectors as u64,
+                    block.header.add_size as u64,
+                )?;
+            }
+            Some(BlockImpl::Rar5(volume)) => {
+                writer.write_all(&block.raw[..block.header.size as usize])?;
+                for block in &volume.blocks {
+                    writer.write_all(&block.raw)?;
+                    match &block.inner {
+                        Rar5BlockImpl::File(file) => {
+                            if file.compression_method() != 0 {
+                                return Err(ReconstructError::Unsupported("compressed RAR5 files"));
+                            }
+                            if block.header.flags & crate::Rar5BlockHeader::SPLIT_BEFORE == 0 {
+                                sources.remove(&file.file_name);
+                            }
+                            self.copy_file_data(
+                                &file.file_name,
+                                block.header.data_size,
+                                writer,
+                                sources,
+                            )?;
+                        }
+                        Rar5BlockImpl::Service(service)
+                            if service.file_name == "RR" && block.header.data_size > 0 =>
+                        {
+                            return Err(ReconstructError::Unsupported("RAR5 recovery records"));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Some(BlockImpl::SrrRarPadding) => writer.write_all(block.data())?,
+            Some(
+                BlockImpl::SrrStoredFile(_) | BlockImpl::SrrRarFile(_) | BlockImpl::SrrOsoHash,
+            ) => {}
+            _ => writer.write_all(&block.raw)?,
+        }
+        Ok(())
+    }
+
+    fn copy_file_data<W: Write>(
+        &self,
+        file_name: &str,
+        size: u64,
+        writer: &mut W,
+        sources: &mut HashMap<String, Source>,
+    ) -> Result<(), ReconstructError> {
+        if size == 0 {
+            return Ok(());
+        }
+        let source = match sources.entry(file_name.to_owned()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let file = std::fs::File::open(self.path(file_name)?).map_err(|error| {
+                    ReconstructError::MissingFile {
+                        file_name: file_name.to_owned(),
+                        error,
+                    }
+                })?;
+                entry.insert(Box::new(std::io::BufReader::new(file)))
+            }
+        };
+        let copied = std::io::copy(&mut source.take(size), writer)?;
+        if copied != size {
+            return Err(ReconstructError::FileTooShort {
+                file_name: file_name.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+type Source = Box<dyn Read>;
+
+/// Computes the recovery record over the first `data_sectors` 512 byte sectors of the
+/// volume and appends it: a 16 bit CRC per sector followed by the XOR parity sectors.
+/// The counts come from the srr, so they must cover no more than the `end` bytes
+/// written so far and add up to the `size` of the record.
+fn write_recovery_record<W: Read + Write + Seek>(
+    writer: &mut W,
+    end: u64,
+    recovery_sectors: u64,
+    data_sectors: u64,
+    size: u64,
+) -> Result<(), ReconstructError> {
+    let record_size = data_sectors
+        .checked_mul(2)
+        .zip(recovery_sectors.checked_mul(512))
+        .and_then(|(crcs, parity)| crcs.checked_add(parity));
+    if data_sectors > end.div_ceil(512)
+        || recovery_sectors > data_sectors
+        || record_size != Some(size)
+    {
+        return Err(ReconstructError::InvalidRecoveryRecord);
+    }
+    let position = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(0))?;
+    let mut reader = (&mut *writer).take(end);
+
+    let mut crcs = Vec::with_capacity(data_sectors as usize * 2);
+    let mut parity = vec![0u8; recovery_sectors as usize * 512];
+    let mut sector = [0u8; 512];
+    for index in 0..data_sectors {
+        sector.fill(0);
+        let mut filled = 0;
+        while filled < sector.len() {
+            match reader.read(&mut sector[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        crcs.extend_from_slice(&(!crc32fast::hash(&sector) as u16).to_le_bytes());
+        if recovery_sectors > 0 {
+            let offset = (index % recovery_sectors) as usize * 512;
+            for (parity, byte) in parity[offset..offset + 512].iter_mut().zip(sector) {
+                *parity ^= byte;
+            }
+        }
+    }
+
+    writer.seek(SeekFrom::Start(position))?;
+    writer.write_all(&crcs)?;
+    writer.write_all(&parity)?;
+    Ok(())
+}
+
+/// Lets a buffered file be read back for recovery records.
+struct ReadWriteSeek(std::io::BufWriter<std::fs::File>);
+
+impl Read for ReadWriteSeek {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.flush()?;
+        self.0.get_mut().read(buf)
+    }
+}
+
+impl Write for ReadWriteSeek {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for ReadWriteSeek {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
@@ -0,0 +1,1045 @@
+//! Rebuilding a sample from its SRS and the media file it was cut from. The SRS keeps
+//! the container structure of the sample, the media data of every track is read from
+//! the media file, starting where the track's signature is found.
+
+use crate::srs::{
+    ebml_vint, skip_id3, FLAC_SRSF, FLAC_SRST, MKV_CLUSTER, MKV_RESAMPLE, MKV_SEGMENT,
+};
+use crate::{Srs, SrsContainer, SrsError, SrsFile, SrsTrack};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+const MKV_BLOCK_GROUP: u32 = 0xA0;
+const MKV_BLOCK: u32 = 0xA1;
+const MKV_SIMPLE_BLOCK: u32 = 0xA3;
+const MKV_ATTACHMENTS: u32 = 0x1941A469;
+/// Enough for the lacing of any block.
+const MAX_BLOCK_HEADER: u64 = 0x20000;
+
+#[derive(Debug)]
+pub enum SampleError {
+    Io(std::io::Error),
+    Srs(SrsError),
+    /// The media file is not the container the SRS describes.
+    InvalidMedia {
+        offset: u64,
+    },
+    /// The signature of the track is not in the media file.
+    TrackNotFound {
+        track_number: u32,
+    },
+    /// The media file ends before all data of the track was read.
+    TrackTooShort {
+        track_number: u32,
+    },
+    /// The rebuilt sample differs from the original.
+    Mismatch {
+        expected_size: u64,
+        actual_size: u64,
+        expected_crc: u32,
+        actual_crc: u32,
+    },
+    Unsupported(&'static str),
+}
+
+impl From<std::io::Error> for SampleError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<SrsError> for SampleError {
+    fn from(value: SrsError) -> Self {
+        Self::Srs(value)
+    }
+}
+
+impl std::fmt::Display for SampleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleError::Io(error) => error.fmt(f),
+            SampleError::Srs(error) => error.fmt(f),
+            SampleError::InvalidMedia { offset } => {
+                write!(f, "invalid media file structure at offset {offset}")
+            }
+            SampleError::TrackNotFound { track_number } => {
+                write!(f, "track {track_number} is not in the media file")
+            }
+            SampleError::TrackTooShort { track_number } => {
+                write!(
+                    f,
+                    "the media file has too little data for track {track_number}"
+                )
+            }
+            SampleError::Mismatch {
+                expected_size,
+                actual_size,
+                expected_crc,
+                actual_crc,
+            } => write!(
+                f,
+                "rebuilt {actual_size} bytes with CRC32 {actual_crc:08X}, \
+                 the sample has {expected_size} bytes with CRC32 {expected_crc:08X}"
+            ),
+            SampleError::Unsupported(what) => write!(f, "{what} can't be rebuilt"),
+        }
+    }
+}
+
+impl std::error::Error for SampleError {}
+
+/// A part of the sample: bytes from the SRS or data of a track.
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Srs(Range<usize>),
+    Bytes(Vec<u8>),
+    Track { number: u32, len: u64 },
+}
+
+fn track_pieces(tracks: &[SrsTrack]) -> impl Iterator<Item = Piece> + '_ {
+    tracks.iter().map(|track| Piece::Track {
+        number: track.track_number,
+        len: track.data_length,
+    })
+}
+
+fn range(input: &[u8], start: usize, len: u64) -> Result<Range<usize>, SrsError> {
+    usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .filter(|end| *end <= input.len())
+        .map(|end| start..end)
+        .ok_or(SrsError::Incomplete)
+}
+
+/// MP3 and stream SRS files: the SRS blocks take the place of the track data.
+fn blocks_layout(srs: &Srs, input: &[u8]) -> Result<Vec<Piece>, SrsError> {
+    let start = match srs.container {
+        SrsContainer::Stream => 8,
+        _ => skip_id3(input),
+    };
+    let mut offset = start;
+    while let Some(header) = input.get(offset..offset + 8) {
+        if !matches!(&header[..4], b"SRSF" | b"SRST" | b"SRSP") {
+            break;
+        }
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        offset = range(input, offset, size as u64)?.end;
+    }
+    let mut pieces = vec![];
+    if srs.container == SrsContainer::Mp3 {
+        pieces.push(Piece::Srs(0..start));
+    }
+    pieces.extend(track_pieces(&srs.tracks));
+    pieces.push(Piece::Srs(offset..input.len()));
+    Ok(pieces)
+}
+
+/// The SRS metadata blocks follow those of the sample and have the last block flag.
+fn flac_layout(srs: &Srs, input: &[u8]) -> Result<Vec<Piece>, SrsError> {
+    let start = skip_id3(input);
+    let mut offset = range(input, start, 4)?.end;
+    let mut pieces = vec![Piece::Srs(0..offset)];
+    let mut last_kept = None;
+    loop {
+        let header = input.get(offset..offset + 4).ok_or(SrsError::Incomplete)?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+        let block = range(input, offset, 4 + len as u64)?;
+        let last = header[0] & 0x80 != 0;
+        if !matches!(header[0] & 0x7F, FLAC_SRSF | FLAC_SRST) {
+            last_kept = Some(pieces.len());
+            pieces.push(Piece::Srs(block.clone()));
+        }
+        offset = block.end;
+        if last {
+            break;
+        }
+    }
+    if let Some(index) = last_kept {
+        if let Piece::Srs(block) = &pieces[index] {
+            let mut bytes = input[block.clone()].to_vec();
+            bytes[0] |= 0x80;
+            pieces[index] = Piece::Bytes(bytes);
+        }
+    }
+    pieces.extend(track_pieces(&srs.tracks));
+    pieces.push(Piece::Srs(offset..input.len()));
+    Ok(pieces)
+}
+
+/// AVI stream chunks are named after their stream number, e.g. `01wb`.
+fn avi_stream(fourcc: &[u8]) -> Option<u32> {
+    match fourcc {
+        [tens, ones, ..] if tens.is_ascii_digit() && ones.is_ascii_digit() => {
+            Some(((tens - b'0') * 10 + (ones - b'0')) as u32)
+        }
+        _ => None,
+    }
+}
+
+/// Only the headers of the stream chunks are kept, padding included.
+fn avi_layout(input: &[u8]) -> Result<Vec<Piece>, SrsError> {
+    let mut pieces = vec![];
+    let mut offset = 0;
+    while offset < input.len() {
+        let header = input.get(offset..offset + 8).ok_or(SrsError::Incomplete)?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        let padded = size + (size & 1);
+        match &header[..4] {
+            b"RIFF" | b"LIST" => {
+                pieces.push(Piece::Srs(range(input, offset, 12)?));
+                offset += 12;
+            }
+            b"SRSF" | b"SRST" => offset = range(input, offset, 8 + padded)?.end,
+            fourcc => match avi_stream(fourcc) {
+                Some(number) => {
+                    let header = range(input, offset, 8 + (size & 1))?;
+                    pieces.push(Piece::Srs(offset..offset + 8));
+                    pieces.push(Piece::Track { number, len: size });
+                    pieces.push(Piece::Srs(offset + 8..header.end));
+                    offset = header.end;
+                }
+                None => {
+                    let chunk = range(input, offset, 8 + padded)?;
+                    offset = chunk.end;
+                    pieces.push(Piece::Srs(chunk));
+                }
+            },
+        }
+    }
+    Ok(pieces)
+}
+
+/// Track number and header length of a (Simple)Block, lacing included.
+fn mkv_block_header(data: &[u8]) -> Result<(u32, usize), SrsError> {
+    let (number, mut len) = ebml_vint(data, 0, false)?;
+    let flags = *data.get(len + 2).ok_or(SrsError::Incomplete)?;
+    len += 3;
+    let lacing = flags >> 1 & 3;
+    if lacing != 0 {
+        let frames = *data.get(len).ok_or(SrsError::Incomplete)? as usize + 1;
+        len += 1;
+        for _ in 1..frames {
+            match lacing {
+                // Xiph
+                1 => loop {
+                    let byte = *data.get(len).ok_or(SrsError::Incomplete)?;
+                    len += 1;
+                    if byte != 0xFF {
+                        break;
+                    }
+                },
+                // EBML, a size and then differences
+                3 => len += ebml_vint(data, len, false)?.1,
+                // fixed
+                _ => break,
+            }
+        }
+    }
+    Ok((number as u32, len))
+}
+
+/// Only the headers of the blocks are kept, the ReSample element is not in the sample.
+fn mkv_layout(srs: &Srs, input: &[u8]) -> Result<Vec<Piece>, SampleError> {
+    let mut pieces = vec![];
+    let mut offset = 0;
+    while offset < input.len() {
+        let (id, id_len) = ebml_vint(input, offset, true)?;
+        let (size, size_len) = ebml_vint(input, offset + id_len, false)?;
+        let data = offset + id_len + size_len;
+        match id as u32 {
+            MKV_RESAMPLE => offset = range(input, data, size)?.end,
+            MKV_SEGMENT | MKV_CLUSTER | MKV_BLOCK_GROUP => {
+                pieces.push(Piece::Srs(range(input, offset, (data - offset) as u64)?));
+                offset = data;
+            }
+            MKV_BLOCK | MKV_SIMPLE_BLOCK => {
+                let (number, header_len) = mkv_block_header(&input[data.min(input.len())..])?;
+                let len = size
+                    .checked_sub(header_len as u64)
+                    .ok_or(SrsError::Invalid { offset })?;
+                pieces.push(Piece::Srs(offset..data + header_len));
+                pieces.push(Piece::Track { number, len });
+                offset = data + header_len;
+            }
+            MKV_ATTACHMENTS if srs.file.flags & SrsFile::ATTACHMENTS_REMOVED != 0 => {
+                return Err(SampleError::Unsupported("MKV samples with attachments"));
+            }
+            _ => {
+                let element = range(input, offset, (data - offset) as u64 + size)?;
+                offset = element.end;
+                pieces.push(Piece::Srs(element));
+            }
+        }
+    }
+    Ok(pieces)
+}
+
+/// An MP4 atom at the start of `input`: its type, header length and size. `remaining`
+/// is what is left of the file, for atoms that extend to its end.
+fn mp4_atom(input: &[u8], remaining: u64) -> Result<([u8; 4], usize, u64), SrsError> {
+    let header = input.get(..8).ok_or(SrsError::Incomplete)?;
+    let ty = [header[4], header[5], header[6], header[7]];
+    let (header_len, size) = match u32::from_be_bytes([header[0], header[1], header[2], header[3]])
+    {
+        0 => (8, remaining),
+        1 => {
+            let large = input.get(8..16).ok_or(SrsError::Incomplete)?;
+            let mut size = [0; 8];
+            size.copy_from_slice(large);
+            (16, u64::from_be_bytes(size))
+        }
+        size => (8, size as u64),
+    };
+    if size < header_len as u64 {
+        return Err(SrsError::Invalid { offset: 0 });
+    }
+    Ok((ty, header_len, size))
+}
+
+/// An atom type and its content.
+type Atom<'a> = ([u8; 4], &'a [u8]);
+
+/// The child atoms in the content of an atom.
+fn mp4_children(input: &[u8]) -> Result<Vec<Atom<'_>>, SrsError> {
+    let mut children = vec![];
+    let mut offset = 0;
+    while offset < input.len() {
+        let (ty, header_len, size) = mp4_atom(&input[offset..], (input.len() - offset) as u64)
+            .map_err(|_| SrsError::Invalid { offset })?;
+        let atom = range(input, offset, size)?;
+        children.push((ty, &input[atom.start + header_len..atom.end]));
+        offset = atom.end;
+    }
+    Ok(children)
+}
+
+fn mp4_child<'a>(input: &'a [u8], ty: &[u8; 4]) -> Result<&'a [u8], SrsError> {
+    mp4_children(input)?
+        .into_iter()
+        .find_map(|(child, content)| (&child == ty).then_some(content))
+        .ok_or(SrsError::Invalid { offset: 0 })
+}
+
+fn be_u32(input: &[u8], offset: usize) -> Result<u32, SrsError> {
+    let bytes = input.get(offset..offset + 4).ok_or(SrsError::Incomplete)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Offset, size and track ID of every chunk in the sample tables of a moov atom, in
+/// file order.
+fn mp4_chunks(moov: &[u8]) -> Result<Vec<(u64, u64, u32)>, SrsError> {
+    let mut chunks = vec![];
+    for (ty, trak) in mp4_children(moov)? {
+        if &ty != b"trak" {
+            continue;
+        }
+        let tkhd = mp4_child(trak, b"tkhd")?;
+        let track_id = be_u32(tkhd, if tkhd.first() == Some(&1) { 20 } else { 12 })?;
+        let stbl = mp4_child(mp4_child(mp4_child(trak, b"mdia")?, b"minf")?, b"stbl")?;
+
+        let stsz = mp4_child(stbl, b"stsz")?;
+        let sample_size = be_u32(stsz, 4)?;
+        let sample_count = be_u32(stsz, 8)? as usize;
+        let size = |sample: usize| -> Result<u64, SrsError> {
+            match sample_size {
+                _ if sample >= sample_count => Err(SrsError::Invalid { offset: 0 }),
+                0 => Ok(be_u32(stsz, 12 + 4 * sample)? as u64),
+                size => Ok(size as u64),
+            }
+        };
+        let stsc = mp4_child(stbl, b"stsc")?;
+        let samples_per_chunk = (0..be_u32(stsc, 4)? as usize)
+            .map(|entry| {
+                Ok((
+                    be_u32(stsc, 8 + 12 * entry)?,
+                    be_u32(stsc, 12 + 12 * entry)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, SrsError>>()?;
+        let offsets = match mp4_child(stbl, b"stco") {
+            Ok(stco) => (0..be_u32(stco, 4)? as usize)
+                .map(|chunk| Ok(be_u32(stco, 8 + 4 * chunk)? as u64))
+                .collect::<Result<Vec<_>, SrsError>>()?,
+            Err(_) => {
+                let co64 = mp4_child(stbl, b"co64")?;
+                (0..be_u32(co64, 4)? as usize)
+                    .map(|chunk| {
+                        let high = be_u32(co64, 8 + 8 * chunk)? as u64;
+                        Ok(high << 32 | be_u32(co64, 12 + 8 * chunk)? as u64)
+                    })
+                    .collect::<Result<Vec<_>, SrsError>>()?
+            }
+        };
+
+        let mut sample = 0;
+        for (index, offset) in offsets.into_iter().enumerate() {
+            let samples = samples_per_chunk
+                .iter()
+                .rev()
+                .find(|(first_chunk, _samples)| *first_chunk as usize <= index + 1)
+                .map_or(0, |(_first_chunk, samples)| *samples as usize);
+            let chunk_size = (sample..sample + samples).try_fold(0u64, |total, sample| {
+                total
+                    .checked_add(size(sample)?)
+                    .ok_or(SrsError::Invalid { offset: 0 })
+            })?;
+            sample += samples;
+            chunks.push((offset, chunk_size, track_id));
+        }
+    }
+    chunks.sort_unstable();
+    Ok(chunks)
+}
+
+/// Only the header of the mdat atom is kept, its chunks are in the sample tables of the
+/// moov atom. The SRS atoms follow those of the sample.
+fn mp4_layout(input: &[u8]) -> Result<Vec<Piece>, SrsError> {
+    // only the header of mdat is kept, so its size goes past the end of the SRS
+    let mut atoms = vec![];
+    let mut offset = 0;
+    while offset < input.len() {
+        let (ty, header_len, size) = mp4_atom(&input[offset..], (input.len() - offset) as u64)
+            .map_err(|_| SrsError::Invalid { offset })?;
+        let atom = match &ty {
+            b"mdat" => range(input, offset, header_len as u64)?,
+            _ => range(input, offset, size)?,
+        };
+        offset = atom.end;
+        atoms.push((ty, atom, header_len, size));
+    }
+    let moov = atoms
+        .iter()
+        .find(|(ty, ..)| ty == b"moov")
+        .map(|(_ty, atom, header_len, _size)| &input[atom.start + header_len..atom.end])
+        .ok_or(SrsError::Invalid { offset: 0 })?;
+    let mut chunks = mp4_chunks(moov)?.into_iter().peekable();
+    let mut pieces = vec![];
+    // where the next atom starts in the sample, the sizes come from the SRS
+    let mut sample_offset = 0u64;
+    for (ty, atom, header_len, size) in atoms {
+        match &ty {
+            b"SRSF" | b"SRST" => {}
+            b"mdat" => {
+                let offset = atom.start;
+                pieces.push(Piece::Srs(atom));
+                let start = sample_offset + header_len as u64;
+                let end = sample_offset
+                    .checked_add(size)
+                    .ok_or(SrsError::Invalid { offset })?;
+                let mut position = start;
+                while let Some(&(chunk, len, track_id)) = chunks.peek() {
+                    if chunk >= end {
+                        break;
+                    }
+                    if chunk != position {
+                        return Err(SrsError::Invalid { offset });
+                    }
+                    pieces.push(Piece::Track {
+                        number: track_id,
+                        len,
+                    });
+                    position = position
+                        .checked_add(len)
+                        .ok_or(SrsError::Invalid { offset })?;
+                    chunks.next();
+                }
+                if position != end {
+                    return Err(SrsError::Invalid { offset });
+                }
+                sample_offset = end;
+            }
+            _ => {
+                sample_offset += (atom.end - atom.start) as u64;
+                pieces.push(Piece::Srs(atom));
+            }
+        }
+    }
+    Ok(pieces)
+}
+
+/// The sample as the SRS describes it.
+fn layout(srs: &Srs, input: &[u8]) -> Result<Vec<Piece>, SampleError> {
+    match srs.container {
+        SrsContainer::Mp3 | SrsContainer::Stream => Ok(blocks_layout(srs, input)?),
+        SrsContainer::Flac => Ok(flac_layout(srs, input)?),
+        SrsContainer::Avi => Ok(avi_layout(input)?),
+        SrsContainer::Mkv => mkv_layout(srs, input),
+        SrsContainer::Mp4 => Ok(mp4_layout(input)?),
+        SrsContainer::Wmv => Err(SampleError::Unsupported("WMV samples")),
+    }
+}
+
+/// Reads as much of `buf` as the media file has left.
+fn read_at<M: Read + Seek>(media: &mut M, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+    media.seek(SeekFrom::Start(offset))?;
+    let mut read = 0;
+    while read < buf.len() {
+        match media.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(read)
+}
+
+type Spans = Vec<(u64, u64)>;
+
+/// Offsets and lengths of the data of every stream of an AVI.
+fn avi_spans<M: Read + Seek>(media: &mut M, len: u64) -> Result<HashMap<u32, Spans>, SampleError> {
+    let mut spans = HashMap::<u32, Spans>::new();
+    let mut offset = 0;
+    let mut header = [0; 8];
+    while offset + 8 <= len {
+        read_at(media, offset, &mut header)?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        match &header[..4] {
+            b"RIFF" | b"LIST" => offset += 12,
+            fourcc => {
+                if let Some(number) = avi_stream(fourcc) {
+                    spans.entry(number).or_default().push((offset + 8, size));
+                }
+                offset += 8 + size + (size & 1);
+            }
+        }
+    }
+    Ok(spans)
+}
+
+/// Offsets and lengths of the frames of every track of a Matroska file.
+fn mkv_spans<M: Read + Seek>(media: &mut M, len: u64) -> Result<HashMap<u32, Spans>, SampleError> {
+    let mut spans = HashMap::<u32, Spans>::new();
+    let mut offset = 0;
+    let mut header = [0; 16];
+    let invalid = |offset| move |_| SampleError::InvalidMedia { offset };
+    while offset < len {
+        let read = read_at(media, offset, &mut header)?;
+        let header = &header[..read];
+        let (id, id_len) = ebml_vint(header, 0, true).map_err(invalid(offset))?;
+        let (size, size_len) = ebml_vint(header, id_len, false).map_err(invalid(offset))?;
+        let data = offset + (id_len + size_len) as u64;
+        match id as u32 {
+            MKV_SEGMENT | MKV_CLUSTER | MKV_BLOCK_GROUP => offset = data,
+            MKV_BLOCK | MKV_SIMPLE_BLOCK => {
+                let mut block = vec![0; size.min(MAX_BLOCK_HEADER) as usize];
+                let read = read_at(media, data, &mut block)?;
+                let (number, header_len) =
+                    mkv_block_header(&block[..read]).map_err(invalid(data))?;
+                let frames = size
+                    .checked_sub(header_len as u64)
+                    .ok_or(SampleError::InvalidMedia { offset })?;
+                spans
+                    .entry(number)
+                    .or_default()
+                    .push((data + header_len as u64, frames));
+                offset = data.saturating_add(size);
+            }
+            _ => offset = data.saturating_add(size),
+        }
+    }
+    Ok(spans)
+}
+
+/// Offsets and lengths of the chunks of every track of an MP4.
+fn mp4_spans<M: Read + Seek>(media: &mut M, len: u64) -> Result<HashMap<u32, Spans>, SampleError> {
+    let mut spans = HashMap::<u32, Spans>::new();
+    let mut offset = 0;
+    let mut header = [0; 16];
+    while offset < len {
+        let read = read_at(media, offset, &mut header)?;
+        let (ty, header_len, size) = mp4_atom(&header[..read], len - offset)
+            .map_err(|_| SampleError::InvalidMedia { offset })?;
+        if &ty == b"moov" {
+            if offset + size > len {
+                return Err(SampleError::InvalidMedia { offset });
+            }
+            let mut moov = vec![0; (size - header_len as u64) as usize];
+            read_at(media, offset + header_len as u64, &mut moov)?;
+            let chunks = mp4_chunks(&moov).map_err(|_| SampleError::InvalidMedia { offset })?;
+            for (chunk, size, track_id) in chunks {
+                spans.entry(track_id).or_default().push((chunk, size));
+            }
+        }
+        offset = offset.saturating_add(size);
+    }
+    Ok(spans)
+}
+
+/// Track data spread over parts of the media file.
+struct TrackData {
+    spans: Spans,
+    span: usize,
+    offset: u64,
+}
+
+impl TrackData {
+    /// Copies up to `len` bytes, returns how many there were.
+    fn copy<M: Read + Seek, W: Write>(
+        &mut self,
+        media: &mut M,
+        mut len: u64,
+        out: &mut W,
+    ) -> std::io::Result<u64> {
+        let mut copied = 0;
+        while len > 0 {
+            let Some(&(start, size)) = self.spans.get(self.span) else {
+                break;
+            };
+            let count = (size - self.offset).min(len);
+            media.seek(SeekFrom::Start(start + self.offset))?;
+            let written = std::io::copy(&mut media.by_ref().take(count), out)?;
+            copied += written;
+            if written < count {
+                break;
+            }
+            len -= count;
+            self.offset += count;
+            if self.offset == size {
+                self.span += 1;
+                self.offset = 0;
+            }
+        }
+        Ok(copied)
+    }
+
+    fn starts_with<M: Read + Seek>(
+        spans: &[(u64, u64)],
+        media: &mut M,
+        signature: &[u8],
+    ) -> std::io::Result<bool> {
+        let mut data = TrackData {
+            spans: spans.to_vec(),
+            span: 0,
+            offset: 0,
+        };
+        let mut start = Vec::with_capacity(signature.len());
+        data.copy(media, signature.len() as u64, &mut start)?;
+        Ok(start == signature)
+    }
+
+    /// Starts at the span where the signature is, trying the one at the match offset first.
+    fn locate<M: Read + Seek>(
+        spans: Spans,
+        media: &mut M,
+        track: &SrsTrack,
+    ) -> Result<Self, SampleError> {
+        let matched = spans
+            .iter()
+            .position(|(start, _size)| track.match_offset != 0 && *start == track.match_offset);
+        for span in matched.into_iter().chain(0..spans.len()) {
+            if Self::starts_with(&spans[span..], media, &track.signature)? {
+                return Ok(Self {
+                    spans,
+                    span,
+                    offset: 0,
+                });
+            }
+        }
+        Err(SampleError::TrackNotFound {
+            track_number: track.track_number,
+        })
+    }
+
+    /// The track is in one piece, starting with the signature.
+    fn find<M: Read + Seek>(
+        media: &mut M,
+        len: u64,
+        track: &SrsTrack,
+    ) -> Result<Self, SampleError> {
+        let signature = &track.signature;
+        let contiguous = |start| Self {
+            spans: vec![(start, track.data_length)],
+            span: 0,
+            offset: 0,
+        };
+        if track.match_offset != 0
+            && Self::starts_with(&[(track.match_offset, len)], media, signature)?
+        {
+            return Ok(contiguous(track.match_offset));
+        }
+        if signature.is_empty() {
+            return Ok(contiguous(0));
+        }
+
+        let mut buf = vec![0; (1 << 20) + signature.len()];
+        let mut offset = 0;
+        while offset < len {
+            let read = read_at(media, offset, &mut buf)?;
+            if let Some(found) = buf[..read]
+                .windows(signature.len())
+                .position(|window| window == signature.as_slice())
+            {
+                return Ok(contiguous(offset + found as u64));
+            }
+            if read < buf.len() {
+                break;
+            }
+            offset += (read - signature.len() + 1) as u64;
+        }
+        Err(SampleError::TrackNotFound {
+            track_number: track.track_number,
+        })
+    }
+}
+
+struct CrcWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    written: u64,
+}
+
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Rebuilds the sample described by the SRS file `srs` from the `media` file it was cut
+/// from and writes it to `out`. The sample is checked against the size and CRC32 in
+/// the SRS, on a mismatch `out` holds the wrongly rebuilt sample.
+pub fn rebuild_sample<M: Read + Seek, W: Write>(
+    srs: &[u8],
+    mut media: M,
+    out: W,
+) -> Result<Srs, SampleError> {
+    let parsed = Srs::parse(srs)?;
+    let pieces = layout(&parsed, srs)?;
+    let len = media.seek(SeekFrom::End(0))?;
+
+    let mut spans = match parsed.container {
+        SrsContainer::Avi => Some(avi_spans(&mut media, len)?),
+        SrsContainer::Mkv => Some(mkv_spans(&mut media, len)?),
+        SrsContainer::Mp4 => Some(mp4_spans(&mut media, len)?),
+        _ => None,
+    };
+    let mut tracks = HashMap::new();
+    for track in &parsed.tracks {
+        let data = match &mut spans {
+            Some(spans) => {
+                let spans = spans.remove(&track.track_number).unwrap_or_default();
+                TrackData::locate(spans, &mut media, track)?
+            }
+            None => TrackData::find(&mut media, len, track)?,
+        };
+        tracks.insert(track.track_number, data);
+    }
+
+    let mut out = CrcWriter {
+        inner: out,
+        hasher: crc32fast::Hasher::new(),
+        written: 0,
+    };
+    for piece in pieces {
+        match piece {
+            Piece::Srs(range) => out.write_all(&srs[range])?,
+            Piece::Bytes(bytes) => out.write_all(&bytes)?,
+            Piece::Track { number, len } => {
+                let too_short = SampleError::TrackTooShort {
+                    track_number: number,
+                };
+                let data = tracks.get_mut(&number).ok_or(too_short)?;
+                if data.copy(&mut media, len, &mut out)? < len {
+                    return Err(SampleError::TrackTooShort {
+                        track_number: number,
+                    });
+                }
+            }
+        }
+    }
+    out.flush()?;
+
+    let actual_crc = out.hasher.finalize();
+    if out.written != parsed.file.size || actual_crc != parsed.file.crc {
+        return Err(SampleError::Mismatch {
+            expected_size: parsed.file.size,
+            actual_size: out.written,
+            expected_crc: parsed.file.crc,
+            actual_crc,
+        });
+    }
+    Ok(parsed)
+}
+
+/// Where the SRS records go: after the header of the movi list, at the start of the
+/// Segment or after the last atom.
+fn records_offset(container: SrsContainer, sample: &[u8]) -> Result<usize, SampleError> {
+    let mut offset = 0;
+    match container {
+        SrsContainer::Avi => {
+            while let Some(header) = sample.get(offset..offset + 12) {
+                let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                match (&header[..4], &header[8..]) {
+                    (b"LIST", b"movi") => return Ok(offset + 12),
+                    (b"RIFF" | b"LIST", _) => offset += 12,
+                    _ => offset = offset.saturating_add(8 + size as usize + (size & 1) as usize),
+                }
+            }
+        }
+        SrsContainer::Mkv => {
+            while offset < sample.len() {
+                let invalid = |_| SampleError::InvalidMedia {
+                    offset: offset as u64,
+                };
+                let (id, id_len) = ebml_vint(sample, offset, true).map_err(invalid)?;
+                let (size, size_len) =
+                    ebml_vint(sample, offset + id_len, false).map_err(invalid)?;
+                if id as u32 == MKV_SEGMENT {
+                    return Ok(offset + id_len + size_len);
+                }
+                offset = usize::try_from(size)
+                    .ok()
+                    .and_then(|size| (offset + id_len + size_len).checked_add(size))
+                    .unwrap_or(usize::MAX);
+            }
+        }
+        _ => return Ok(sample.len()),
+    }
+    Err(SampleError::InvalidMedia {
+        offset: offset.min(sample.len()) as u64,
+    })
+}
+
+/// A size with as few bytes as EBML allows.
+fn ebml_size(size: usize) -> Vec<u8> {
+    let len = (1..8)
+        .find(|len| (size as u64) < (1 << (7 * len)) - 1)
+        .unwrap_or(8);
+    let marked = (size as u64) | 1 << (7 * len);
+    marked.to_be_bytes()[8 - len..].to_vec()
+}
+
+/// The SRS records in the form of the container.
+fn records(container: SrsContainer, file: &SrsFile, tracks: &[SrsTrack]) -> Vec<u8> {
+    let mut contents = vec![];
+    let mut content = vec![];
+    file.write(&mut content);
+    contents.push(content);
+    for track in tracks {
+        let mut content = vec![];
+        track.write(&mut content);
+        contents.push(content);
+    }
+
+    let mut records = vec![];
+    for (index, content) in contents.iter().enumerate() {
+        let record = if index == 0 { "SRSF" } else { "SRST" };
+        match container {
+            SrsContainer::Avi => {
+                records.extend_from_slice(record.as_bytes());
+                records.extend_from_slice(&(content.len() as u32).to_le_bytes());
+                records.extend_from_slice(content);
+                if content.len() % 2 == 1 {
+                    records.push(0);
+                }
+            }
+            SrsContainer::Mkv => {
+                let id: &[u8] = if index == 0 {
+                    &[0x6A, 0x75]
+                } else {
+                    &[0x6B, 0x75]
+                };
+                records.extend_from_slice(id);
+                records.extend(ebml_size(content.len()));
+                records.extend_from_slice(content);
+            }
+            _ => {
+                records.extend_from_slice(&(content.len() as u32 + 8).to_be_bytes());
+                records.extend_from_slice(record.as_bytes());
+                records.extend_from_slice(content);
+            }
+        }
+    }
+    if container == SrsContainer::Mkv {
+        let mut resample = MKV_RESAMPLE.to_be_bytes().to_vec();
+        resample.extend(ebml_size(records.len()));
+        resample.extend(records);
+        return resample;
+    }
+    records
+}
+
+impl Srs {
+    /// Creates the SRS of an AVI, MKV or MP4 sample: the sample without the media data of
+    /// its tracks, and the records to find that data again in the full media file.
+    pub fn create(app_name: &str, file_name: &str, sample: &[u8]) -> Result<Vec<u8>, SampleError> {
+        let container = SrsContainer::detect(sample).ok_or(SrsError::UnknownContainer)?;
+        let mut cursor = std::io::Cursor::new(sample);
+        let len = sample.len() as u64;
+        let spans = match container {
+            SrsContainer::Avi => avi_spans(&mut cursor, len)?,
+            SrsContainer::Mkv => mkv_spans(&mut cursor, len)?,
+            SrsContainer::Mp4 => mp4_spans(&mut cursor, len)?,
+            _ => return Err(SrsError::UnknownContainer.into()),
+        };
+        let invalid = |offset| SampleError::InvalidMedia { offset };
+        let data = |(start, size): (u64, u64)| {
+            usize::try_from(start)
+                .ok()
+                .zip(usize::try_from(size).ok())
+                .and_then(|(start, size)| sample.get(start..start.checked_add(size)?))
+                .ok_or(invalid(start))
+        };
+
+        let mut numbers = spans.keys().copied().collect::<Vec<_>>();
+        numbers.sort_unstable();
+        let mut tracks = vec![];
+        for number in numbers {
+            let mut signature = vec![];
+            for &span in &spans[&number] {
+                let data = data(span)?;
+                let missing = 256 - signature.len();
+                signature.extend_from_slice(&data[..data.len().min(missing)]);
+            }
+            let data_length = spans[&number].iter().map(|(_start, size)| size).sum();
+            tracks.push(SrsTrack::new(number, data_length, 0, signature));
+        }
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(sample);
+        let file = SrsFile {
+            flags: SrsFile::SIMPLE_BLOCK_FIX,
+            app_name: app_name.to_owned(),
+            file_name: file_name.to_owned(),
+            size: len,
+            crc: hasher.finalize(),
+        };
+
+        let mut records = Some(records(container, &file, &tracks));
+        let records_offset = records_offset(container, sample)?;
+        let mut spans = spans.into_values().flatten().collect::<Vec<_>>();
+        spans.sort_unstable();
+        let mut srs = vec![];
+        let mut offset = 0;
+        for span in spans {
+            let data = data(span)?;
+            let start = span.0 as usize;
+            if start < offset {
+                return Err(invalid(span.0));
+            }
+            if let Some(records) = records.take_if(|_| records_offset <= start) {
+                let before = sample.get(offset..records_offset).ok_or(invalid(span.0))?;
+                srs.extend_from_slice(before);
+                srs.extend(records);
+                offset = records_offset;
+            }
+            srs.extend_from_slice(&sample[offset..start]);
+            offset = start + data.len();
+        }
+        if let Some(records) = records {
+            let before = sample.get(offset..records_offset).ok_or(invalid(len))?;
+            srs.extend_from_slice(before);
+            srs.extend(records);
+            offset = records_offset;
+        }
+        srs.extend_from_slice(&sample[offset..]);
+
+        // data between the tracks, like gaps in an mdat atom, can't be described
+        let size = layout(&Srs::parse(&srs)?, &srs)?
+            .iter()
+            .map(|piece| match piece {
+                Piece::Srs(range) => range.len() as u64,
+                Piece::Bytes(bytes) => bytes.len() as u64,
+                Piece::Track { len, .. } => *len,
+            })
+            .sum::<u64>();
+        if size != len {
+            return Err(SampleError::Unsupported(
+                "samples with data outside of the tracks",
+            ));
+        }
+        Ok(srs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Without the media files, the layout of the fixture samples must at least add up
+    /// to their size and the data length of their tracks.
+    #[test]
+    fn fixture_layouts() {
+        let mut srrs = vec![];
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let mut dirs = vec![root];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|extension| extension == "srr") {
+                    srrs.push(path);
+                }
+            }
+        }
+
+        let mut samples = 0;
+        for path in srrs {
+            let input = std::fs::read(&path).unwrap();
+            let Ok((_rest, srr)) = crate::Srr::new(&input) else {
+                continue;
+            };
+            for (file, data) in srr.stored_files() {
+                let Ok(srs) = Srs::parse(data) else {
+                    continue;
+                };
+                let pieces = layout(&srs, data).unwrap();
+                let mut size = 0;
+                let mut tracks = HashMap::new();
+                for piece in &pieces {
+                    match piece {
+                        Piece::Srs(range) => size += range.len() as u64,
+                        Piece::Bytes(bytes) => size += bytes.len() as u64,
+                        Piece::Track { number, len } => {
+                            size += len;
+                            *tracks.entry(*number).or_insert(0) += len;
+                        }
+                    }
+                }
+                assert_eq!(size, srs.file.size, "{}", file.file_name);
+                for track in &srs.tracks {
+                    assert_eq!(
+                        tracks.get(&track.track_number),
+                        Some(&track.data_length),
+                        "{} track {}",
+                        file.file_name,
+                        track.track_number
+                    );
+                }
+                samples += 1;
+            }
+        }
+        assert!(samples > 20);
+    }
+
+    #[test]
+    fn mkv_lacing() {
+        // track 1, timecode, no lacing
+        assert_eq!(mkv_block_header(&[0x81, 0, 0, 0x80]), Ok((1, 4)));
+        // fixed lacing of 8 frames
+        assert_eq!(mkv_block_header(&[0x82, 0, 0x10, 0x84, 7]), Ok((2, 5)));
+        // Xiph lacing of 3 frames, 300 and 20 bytes
+        assert_eq!(
+            mkv_block_header(&[0x81, 0, 0, 0x02, 2, 0xFF, 45, 20]),
+            Ok((1, 8))
+        );
+        // EBML lacing of 3 frames, 500 and then 10 bytes less
+        assert_eq!(
+            mkv_block_header(&[0x81, 0, 0, 0x06, 2, 0x41, 0xF4, 0xBF - 10]),
+            Ok((1, 8))
+        );
+        assert_eq!(
+            mkv_block_header(&[0x81, 0, 0, 0x02, 2, 0xFF]),
+            Err(SrsError::Incomplete)
+        );
+    }
+}
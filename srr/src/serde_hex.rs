@@ -0,0 +1,106 @@
+//! Field formats for the `serde` feature: CRCs as fixed width upper case hex strings
+//! and raw bytes as lower case hex strings.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Integers that are formatted as hex with one digit per nibble, e.g. `6A6A` for a `u16`.
+pub(crate) trait Crc: Copy {
+    fn to_hex(self) -> String;
+    fn from_hex(s: &str) -> Result<Self, std::num::ParseIntError>;
+}
+
+impl Crc for u16 {
+    fn to_hex(self) -> String {
+        format!("{self:04X}")
+    }
+
+    fn from_hex(s: &str) -> Result<Self, std::num::ParseIntError> {
+        u16::from_str_radix(s, 16)
+    }
+}
+
+impl Crc for u32 {
+    fn to_hex(self) -> String {
+        format!("{self:08X}")
+    }
+
+    fn from_hex(s: &str) -> Result<Self, std::num::ParseIntError> {
+        u32::from_str_radix(s, 16)
+    }
+}
+
+pub(crate) mod crc {
+    use super::*;
+
+    pub(crate) fn serialize<T: Crc, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_hex())
+    }
+
+    pub(crate) fn deserialize<'de, T: Crc, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        T::from_hex(&s).map_err(D::Error::custom)
+    }
+}
+
+pub(crate) mod crc_option {
+    use super::*;
+
+    pub(crate) fn serialize<T: Crc, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&value.to_hex()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, T: Crc, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| T::from_hex(&s).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+pub(crate) mod bytes {
+    use super::*;
+
+    pub(crate) fn serialize<T: AsRef<[u8]>, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let hex = value
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        serializer.serialize_str(&hex)
+    }
+
+    pub(crate) fn deserialize<'de, T: TryFrom<Vec<u8>>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        if s.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| {
+                s.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| D::Error::custom(format!("invalid hex digits at {i}")))
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        let len = bytes.len();
+        T::try_from(bytes).map_err(|_| D::Error::invalid_length(len, &"a matching number of bytes"))
+    }
+}
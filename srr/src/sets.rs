@@ -0,0 +1,174 @@
+use crate::{
+    Block, BlockImpl, BlockType, Budget, Limit, ParseError, ParseOptions, Srr, VolumeName,
+    VolumeScheme,
+};
+
+/// How deep [`Srr::nested_srrs`] looks for srrs stored in srrs.
+const MAX_NESTING_DEPTH: usize = 16;
+
+/// The blocks of one RAR set, by index into the srr.
+struct RarSet {
+    key: (String, Option<std::mem::Discriminant<VolumeScheme>>),
+    /// Directory of the volumes with a trailing `/`, empty in the root.
+    dir: String,
+    blocks: Vec<usize>,
+    /// Names of the archived files, without their directories.
+    archived: Vec<String>,
+}
+
+/// The directory of `name` with a trailing `/`, empty for files in the root.
+fn directory(name: &str) -> &str {
+    name.rfind(['/', '\\']).map_or("", |end| &name[..=end])
+}
+
+/// The name at the end of an OSO hash block, after the file size and the hash.
+fn oso_hash_file_name(block: &Block) -> Option<String> {
+    let length = block.raw.get(23..25)?;
+    let length = u16::from_le_bytes([length[0], length[1]]) as usize;
+    let name = block.raw.get(25..25 + length)?;
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+impl Srr {
+    /// Splits the srr into one srr per RAR set, e.g. `CD1/` and `CD2/` of a 2CD
+    /// release, so every set can be verified and reconstructed on its own. Volumes
+    /// are grouped by their name, which includes the directory, and the sets keep the
+    /// order in which they first appear. A set keeps the stored files in the directory
+    /// of its volumes, like its .sfv, and the OSO hashes of its archived files.
+    pub fn rar_sets(&self) -> Vec<Srr> {
+        let mut sets: Vec<RarSet> = vec![];
+        let mut current = None;
+        for (index, block) in self.blocks.iter().enumerate() {
+            match &block.inner {
+                Some(BlockImpl::SrrRarFile(file)) => {
+                    let key = match VolumeName::parse(&file.file_name) {
+                        Some(volume) => {
+                            let (stem, scheme) = volume.set_key();
+                            (stem, Some(scheme))
+                        }
+                        None => (file.file_name.to_lowercase(), None),
+                    };
+                    current = Some(match sets.iter().position(|set| set.key == key) {
+                        Some(position) => position,
+                        None => {
+                            sets.push(RarSet {
+                                key,
+                                dir: directory(&file.file_name).to_owned(),
+                                blocks: vec![],
+                                archived: vec![],
+                            });
+                            sets.len() - 1
+                        }
+                    });
+                }
+                _ if matches!(
+                    block.header.ty,
+                    BlockType::SrrHeader | BlockType::SrrStoredFile | BlockType::SrrOsoHash
+                ) =>
+                {
+                    current = None;
+                    continue;
+                }
+                _ => {}
+            }
+            let Some(set) = current.map(|position| &mut sets[position]) else {
+                continue;
+            };
+            set.blocks.push(index);
+            match &block.inner {
+                Some(BlockImpl::RarPackedFile(file)) => {
+                    let name = &file.file_name[directory(&file.file_name).len()..];
+                    set.archived.push(name.to_owned());
+                }
+                Some(BlockImpl::Rar5(volume)) => {
+                    for file in volume.files() {
+                        let name = &file.file_name[directory(&file.file_name).len()..];
+                        set.archived.push(name.to_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            if let Some(BlockImpl::SrrStoredFile(file)) = &block.inner {
+                let dir = directory(&file.file_name);
+                for set in sets
+                    .iter_mut()
+                    .filter(|set| set.dir.eq_ignore_ascii_case(dir))
+                {
+                    set.blocks.push(index);
+                }
+            } else if block.header.ty == BlockType::SrrOsoHash {
+                let Some(name) = oso_hash_file_name(block) else {
+                    continue;
+                };
+                if let Some(set) = sets.iter_mut().find(|set| set.archived.contains(&name)) {
+                    set.blocks.push(index);
+                }
+            }
+        }
+
+        let header = self
+            .blocks
+            .iter()
+            .take_while(|block| block.header.ty == BlockType::SrrHeader);
+        sets.into_iter()
+            .map(|mut set| {
+                set.blocks.sort_unstable();
+                let blocks = set.blocks.iter().map(|&index| &self.blocks[index]);
+                Srr {
+                    blocks: header.clone().chain(blocks).cloned().collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// The srrs stored in this one and, recursively, in those, e.g. for subpacks.
+    /// Stored files are relative to the directory of the srr storing them, so the
+    /// names are too: `inner.srr` stored in `Subs/subs.srr` is `Subs/inner.srr`.
+    ///
+    /// The `options` limit all of the nested srrs together, not each of them, and srrs
+    /// nested more than 16 deep are not parsed.
+    pub fn nested_srrs(&self, options: &ParseOptions) -> Vec<(String, Result<Srr, ParseError>)> {
+        let mut nested = vec![];
+        self.collect_nested("", 1, &mut Budget::new(options), &mut nested);
+        nested
+    }
+
+    fn collect_nested(
+        &self,
+        dir: &str,
+        depth: usize,
+        budget: &mut Budget,
+        nested: &mut Vec<(String, Result<Srr, ParseError>)>,
+    ) {
+        for (file, data) in self.stored_files() {
+            let is_srr = file
+                .file_name
+                .rsplit_once('.')
+                .is_some_and(|(_stem, extension)| extension.eq_ignore_ascii_case("srr"));
+            if !is_srr {
+                continue;
+            }
+            let name = format!("{dir}{}", file.file_name);
+            if depth > MAX_NESTING_DEPTH {
+                let err = ParseError::LimitExceeded {
+                    limit: Limit::NestingDepth,
+                    max: MAX_NESTING_DEPTH as u64,
+                    actual: depth as u64,
+                };
+                nested.push((name, Err(err)));
+                continue;
+            }
+            let srr = Srr::parse_with_budget(data, budget);
+            let mut inner = vec![];
+            if let Ok(srr) = &srr {
+                let dir = name.rfind('/').map_or("", |end| &name[..=end]);
+                srr.collect_nested(dir, depth + 1, budget, &mut inner);
+            }
+            nested.push((name, srr));
+            nested.extend(inner);
+        }
+    }
+}
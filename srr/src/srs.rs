@@ -0,0 +1,523 @@
+//! SRS files, the samples stripped of their media data by ReSample. Every container
+//! type embeds the same two records: the original sample file (SRSF) and one entry
+//! per track (SRST) holding the first bytes of the track data, used to find it in
+//! the full media file.
+
+use crate::{Srr, SrrStoredFile};
+use nom::number::complete::{le_u16, le_u32, le_u64};
+use nom::Parser as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SrsContainer {
+    Avi,
+    Mkv,
+    Mp4,
+    Wmv,
+    Flac,
+    Mp3,
+    /// MPEG and VOB streams.
+    Stream,
+}
+
+impl SrsContainer {
+    /// Detects the container from the first bytes of an SRS file.
+    pub fn detect(input: &[u8]) -> Option<Self> {
+        let magic = input.get(..4)?;
+        match magic {
+            b"RIFF" => Some(SrsContainer::Avi),
+            [0x1A, 0x45, 0xDF, 0xA3] => Some(SrsContainer::Mkv),
+            b"STRM" => Some(SrsContainer::Stream),
+            b"SRSF" => Some(SrsContainer::Mp3),
+            b"fLaC" => Some(SrsContainer::Flac),
+            _ if input.starts_with(&ASF_HEADER) => Some(SrsContainer::Wmv),
+            _ if magic.starts_with(b"ID3") => match input.get(skip_id3(input)..)? {
+                rest if rest.starts_with(b"fLaC") => Some(SrsContainer::Flac),
+                _ => Some(SrsContainer::Mp3),
+            },
+            _ if input.get(4..8).is_some_and(|ty| MP4_ATOMS.contains(&ty)) => {
+                Some(SrsContainer::Mp4)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The sample the SRS was made from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SrsFile {
+    pub flags: u16,
+    /// The program that created the SRS, e.g. "pyReSample 0.7".
+    pub app_name: String,
+    pub file_name: String,
+    pub size: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+    pub crc: u32,
+}
+
+impl SrsFile {
+    /// MKV lacing was fixed up while the SRS was created.
+    pub const SIMPLE_BLOCK_FIX: u16 = 0x1;
+    /// MKV attachments are left out of the SRS.
+    pub const ATTACHMENTS_REMOVED: u16 = 0x2;
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        for name in [&self.app_name, &self.file_name] {
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+        }
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&self.crc.to_le_bytes());
+    }
+
+    fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (input, flags) = le_u16(input)?;
+        let (input, app_name) = string(input)?;
+        let (input, file_name) = string(input)?;
+        let (input, (size, crc)) = (le_u64, le_u32).parse(input)?;
+        Ok((
+            input,
+            Self {
+                flags,
+                app_name,
+                file_name,
+                size,
+                crc,
+            },
+        ))
+    }
+}
+
+/// A track of the sample and where its data starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SrsTrack {
+    pub flags: u16,
+    pub track_number: u32,
+    /// Bytes of track data in the sample.
+    pub data_length: u64,
+    /// Offset of the signature in the full media file, 0 if not yet known.
+    pub match_offset: u64,
+    /// The first bytes of the track data.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+    pub signature: Vec<u8>,
+}
+
+impl SrsTrack {
+    /// `data_length` is stored as 64 bits.
+    pub const BIG_FILE: u16 = 0x4;
+    /// `track_number` is stored as 32 bits.
+    pub const BIG_TRACK_NUMBER: u16 = 0x8;
+
+    /// A track with the flags its sizes need.
+    pub fn new(track_number: u32, data_length: u64, match_offset: u64, signature: Vec<u8>) -> Self {
+        let mut flags = 0;
+        if data_length > u32::MAX as u64 {
+            flags |= Self::BIG_FILE;
+        }
+        if track_number > u16::MAX as u32 {
+            flags |= Self::BIG_TRACK_NUMBER;
+        }
+        Self {
+            flags,
+            track_number,
+            data_length,
+            match_offset,
+            signature,
+        }
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        if self.flags & Self::BIG_TRACK_NUMBER != 0 {
+            out.extend_from_slice(&self.track_number.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(self.track_number as u16).to_le_bytes());
+        }
+        if self.flags & Self::BIG_FILE != 0 {
+            out.extend_from_slice(&self.data_length.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(self.data_length as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&self.match_offset.to_le_bytes());
+        out.extend_from_slice(&(self.signature.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.signature);
+    }
+
+    fn parse(input: &[u8]) -> nom::IResult<&[u8], Self> {
+        let (input, flags) = le_u16(input)?;
+        let (input, track_number) = if flags & Self::BIG_TRACK_NUMBER != 0 {
+            le_u32(input)?
+        } else {
+            le_u16.map(u32::from).parse(input)?
+        };
+        let (input, data_length) = if flags & Self::BIG_FILE != 0 {
+            le_u64(input)?
+        } else {
+            le_u32.map(u64::from).parse(input)?
+        };
+        let (input, match_offset) = le_u64(input)?;
+        let (input, signature) = nom::multi::length_data(le_u16).parse(input)?;
+        Ok((
+            input,
+            Self {
+                flags,
+                track_number,
+                data_length,
+                match_offset,
+                signature: signature.to_vec(),
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Srs {
+    pub container: SrsContainer,
+    pub file: SrsFile,
+    pub tracks: Vec<SrsTrack>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SrsError {
+    /// Not one of the containers ReSample supports.
+    UnknownContainer,
+    /// The input ended in the middle of a record.
+    Incomplete,
+    /// A malformed container structure or SRS record at `offset`.
+    Invalid { offset: usize },
+    /// There is no SRSF record.
+    MissingFile,
+}
+
+impl std::fmt::Display for SrsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SrsError::UnknownContainer => write!(f, "unknown srs container"),
+            SrsError::Incomplete => write!(f, "the srs is truncated"),
+            SrsError::Invalid { offset } => write!(f, "invalid srs data at offset {offset}"),
+            SrsError::MissingFile => write!(f, "the srs has no sample file record"),
+        }
+    }
+}
+
+impl std::error::Error for SrsError {}
+
+/// 75B22630-668E-11CF-A6D9-00AA0062CE6C
+const ASF_HEADER: [u8; 16] = [
+    0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C,
+];
+const MP4_ATOMS: &[&[u8]] = &[
+    b"ftyp", b"moov", b"mdat", b"free", b"skip", b"wide", b"SRSF",
+];
+pub(crate) const MKV_SEGMENT: u32 = 0x18538067;
+pub(crate) const MKV_CLUSTER: u32 = 0x1F43B675;
+pub(crate) const MKV_RESAMPLE: u32 = 0x1F697576;
+const MKV_RESAMPLE_FILE: u32 = 0x6A75;
+const MKV_RESAMPLE_TRACK: u32 = 0x6B75;
+pub(crate) const FLAC_SRSF: u8 = 0x73;
+pub(crate) const FLAC_SRST: u8 = 0x74;
+
+fn string(input: &[u8]) -> nom::IResult<&[u8], String> {
+    nom::multi::length_data(le_u16)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .parse(input)
+}
+
+/// Length of the ID3v2 tag at the start of `input`, 0 without one.
+pub(crate) fn skip_id3(input: &[u8]) -> usize {
+    match input.get(..10) {
+        Some([b'I', b'D', b'3', _, _, flags, size @ ..]) => {
+            let size = size
+                .iter()
+                .fold(0usize, |size, byte| size << 7 | (byte & 0x7F) as usize);
+            let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+            10 + size + footer
+        }
+        _ => 0,
+    }
+}
+
+/// EBML variable length integer at `offset`: the value and its length. IDs keep the
+/// length marker, sizes drop it.
+pub(crate) fn ebml_vint(
+    input: &[u8],
+    offset: usize,
+    keep_marker: bool,
+) -> Result<(u64, usize), SrsError> {
+    let first = *input.get(offset).ok_or(SrsError::Incomplete)?;
+    if first == 0 {
+        return Err(SrsError::Invalid { offset });
+    }
+    let len = first.leading_zeros() as usize + 1;
+    let bytes = input
+        .get(offset..offset + len)
+        .ok_or(SrsError::Incomplete)?;
+    let mask = if keep_marker {
+        0xFF
+    } else {
+        (0xFF_u16 >> len) as u8
+    };
+    let value = bytes[1..]
+        .iter()
+        .fold((first & mask) as u64, |value, &byte| {
+            value << 8 | byte as u64
+        });
+    Ok((value, len))
+}
+
+/// Collects the records while walking a container.
+struct Records<'a> {
+    input: &'a [u8],
+    file: Option<SrsFile>,
+    tracks: Vec<SrsTrack>,
+}
+
+impl<'a> Records<'a> {
+    fn get(&self, offset: usize, len: usize) -> Result<&'a [u8], SrsError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.input.get(offset..end))
+            .ok_or(SrsError::Incomplete)
+    }
+
+    fn file(&mut self, offset: usize, len: usize) -> Result<(), SrsError> {
+        let (_rest, file) =
+            SrsFile::parse(self.get(offset, len)?).map_err(|_| SrsError::Invalid { offset })?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn track(&mut self, offset: usize, len: usize) -> Result<(), SrsError> {
+        let (_rest, track) =
+            SrsTrack::parse(self.get(offset, len)?).map_err(|_| SrsError::Invalid { offset })?;
+        self.tracks.push(track);
+        Ok(())
+    }
+
+    fn u32_le(&self, offset: usize) -> Result<u32, SrsError> {
+        let bytes = self.get(offset, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u32_be(&self, offset: usize) -> Result<u32, SrsError> {
+        let bytes = self.get(offset, 4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// RIFF chunks, descending into lists. The media chunks in movi have no data in an
+    /// SRS, so the walk ends at the first of them. Lists can nest as deep as the input
+    /// allows, so the lists the walk is in are kept on a stack instead of recursing.
+    fn avi(&mut self) -> Result<(), SrsError> {
+        let (mut offset, mut end, mut in_movi) = (0usize, self.input.len(), false);
+        // where each enclosing list continues, its end and whether it is in movi
+        let mut parents = vec![];
+        loop {
+            if offset.saturating_add(8) > end {
+                match parents.pop() {
+                    Some(parent) => (offset, end, in_movi) = parent,
+                    None => return Ok(()),
+                }
+                continue;
+            }
+            let fourcc = self.get(offset, 4)?;
+            let size = self.u32_le(offset + 4)? as usize;
+            let data = offset + 8;
+            let next = data.saturating_add(size).saturating_add(size & 1);
+            match fourcc {
+                b"RIFF" | b"LIST" => {
+                    let list_type = self.get(data, 4)?;
+                    parents.push((next, end, in_movi));
+                    in_movi = in_movi || list_type == b"movi";
+                    end = data.saturating_add(size).min(self.input.len());
+                    offset = data + 4;
+                    continue;
+                }
+                b"SRSF" => self.file(data, size)?,
+                b"SRST" => self.track(data, size)?,
+                _ if in_movi => return Ok(()),
+                _ => {}
+            }
+            offset = next;
+        }
+    }
+
+    /// EBML elements. The records are in the ReSample element at the start of the
+    /// Segment, whose size is that of the original sample.
+    fn mkv(&mut self) -> Result<(), SrsError> {
+        let mut offset = 0;
+        while offset < self.input.len() {
+            let (id, id_len) = ebml_vint(self.input, offset, true)?;
+            let (size, size_len) = ebml_vint(self.input, offset + id_len, false)?;
+            let data = offset + id_len + size_len;
+            let size = usize::try_from(size).unwrap_or(usize::MAX);
+            match id as u32 {
+                MKV_SEGMENT => offset = data,
+                MKV_RESAMPLE => {
+                    self.get(data, size)?;
+                    offset = data;
+                }
+                MKV_RESAMPLE_FILE => {
+                    self.file(data, size)?;
+                    offset = data + size;
+                }
+                MKV_RESAMPLE_TRACK => {
+                    self.track(data, size)?;
+                    offset = data + size;
+                }
+                MKV_CLUSTER => break,
+                _ => offset = data.saturating_add(size),
+            }
+        }
+        Ok(())
+    }
+
+    /// Atoms, the mdat atom is only a header in an SRS.
+    fn mp4(&mut self) -> Result<(), SrsError> {
+        let mut offset = 0;
+        while offset < self.input.len() {
+            let size = self.u32_be(offset)? as u64;
+            let ty = self.get(offset + 4, 4)?;
+            let (header, size) = match size {
+                0 => (8, (self.input.len() - offset) as u64),
+                1 => {
+                    let high = self.u32_be(offset + 8)? as u64;
+                    let low = self.u32_be(offset + 12)? as u64;
+                    (16, high << 32 | low)
+                }
+                size => (8, size),
+            };
+            let len = usize::try_from(size)
+                .ok()
+                .and_then(|size| size.checked_sub(header))
+                .ok_or(SrsError::Invalid { offset })?;
+            let data = offset + header;
+            match ty {
+                b"SRSF" => self.file(data, len)?,
+                b"SRST" => self.track(data, len)?,
+                b"mdat" => {
+                    offset = data;
+                    continue;
+                }
+                _ => {}
+            }
+            offset = data.saturating_add(len);
+        }
+        Ok(())
+    }
+
+    /// ASF objects are found by their GUIDs, ReSample places them in the header object.
+    fn wmv(&mut self) -> Result<(), SrsError> {
+        let mut offset = 0;
+        while let Some(found) = self.input.get(offset..).and_then(|rest| {
+            rest.windows(16)
+                .position(|guid| guid == b"SRSFSRSFSRSFSRSF" || guid == b"SRSTSRSTSRSTSRST")
+        }) {
+            let start = offset + found;
+            let size = self.get(start + 16, 8)?;
+            let size = u64::from_le_bytes(size.try_into().unwrap_or_default());
+            let len = usize::try_from(size)
+                .ok()
+                .and_then(|size| size.checked_sub(24))
+                .ok_or(SrsError::Invalid { offset: start })?;
+            if self.input[start + 3] == b'F' {
+                self.file(start + 24, len)?;
+            } else {
+                self.track(start + 24, len)?;
+            }
+            offset = start + 24 + len;
+        }
+        Ok(())
+    }
+
+    /// Metadata blocks after the optional ID3v2 tag.
+    fn flac(&mut self) -> Result<(), SrsError> {
+        let mut offset = skip_id3(self.input);
+        if self.get(offset, 4)? != b"fLaC" {
+            return Err(SrsError::Invalid { offset });
+        }
+        offset += 4;
+        loop {
+            let header = self.u32_be(offset)?;
+            let (last, ty, len) = (
+                header >> 31 != 0,
+                (header >> 24) as u8 & 0x7F,
+                header & 0xFF_FFFF,
+            );
+            let data = offset + 4;
+            match ty {
+                FLAC_SRSF => self.file(data, len as usize)?,
+                FLAC_SRST => self.track(data, len as usize)?,
+                _ => {}
+            }
+            offset = data + len as usize;
+            if last {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Blocks with a marker and a size that includes the 8 byte header. The ID3 tags
+    /// of an MP3 follow them.
+    fn blocks(&mut self, mut offset: usize) -> Result<(), SrsError> {
+        offset += skip_id3(self.input.get(offset..).unwrap_or_default());
+        while offset.saturating_add(8) <= self.input.len() {
+            let marker = self.get(offset, 4)?;
+            let size = self.u32_le(offset + 4)? as usize;
+            let len = size.checked_sub(8).ok_or(SrsError::Invalid { offset })?;
+            match marker {
+                b"SRSF" => self.file(offset + 8, len)?,
+                b"SRST" => self.track(offset + 8, len)?,
+                b"SRSP" => {}
+                _ => break,
+            }
+            offset += size;
+        }
+        Ok(())
+    }
+}
+
+impl Srs {
+    pub fn parse(input: &[u8]) -> Result<Self, SrsError> {
+        let container = SrsContainer::detect(input).ok_or(SrsError::UnknownContainer)?;
+        let mut records = Records {
+            input,
+            file: None,
+            tracks: vec![],
+        };
+        match container {
+            SrsContainer::Avi => records.avi()?,
+            SrsContainer::Mkv => records.mkv()?,
+            SrsContainer::Mp4 => records.mp4()?,
+            SrsContainer::Wmv => records.wmv()?,
+            SrsContainer::Flac => records.flac()?,
+            SrsContainer::Mp3 => records.blocks(0)?,
+            SrsContainer::Stream => records.blocks(8)?,
+        }
+        Ok(Self {
+            container,
+            file: records.file.ok_or(SrsError::MissingFile)?,
+            tracks: records.tracks,
+        })
+    }
+
+    /// The track with the given number.
+    pub fn track(&self, track_number: u32) -> Option<&SrsTrack> {
+        self.tracks
+            .iter()
+            .find(|track| track.track_number == track_number)
+    }
+}
+
+impl Srr {
+    /// The stored SRS files, parsed.
+    pub fn samples(&self) -> impl Iterator<Item = (&SrrStoredFile, Result<Srs, SrsError>)> {
+        self.stored_files()
+            .filter(|(file, _data)| file.file_name.to_ascii_lowercase().ends_with(".srs"))
+            .map(|(file, data)| (file, Srs::parse(data)))
+    }
+}
@@ -0,0 +1,469 @@
+use crate::reconstruct::relative_path;
+use crate::{Block, BlockImpl, Rar5BlockHeader, Rar5BlockImpl, Rar5FileExtra, RarPackedFile, Srr};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// A file archived in the RAR volumes, as opposed to one stored in the srr.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArchivedFile {
+    pub file_name: String,
+    pub size: u64,
+    /// CRC32 of the whole file, RAR5 archives may leave it out.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc_option"))]
+    pub crc: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Mismatch {
+    Missing(String),
+    Size {
+        file_name: String,
+        expected: u64,
+        actual: u64,
+    },
+    Crc {
+        file_name: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+        expected: u32,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::crc"))]
+        actual: u32,
+    },
+    /// The headers of a volume on disk differ from the ones in the srr.
+    Headers(String),
+    /// A name in the srr that would leave the directory, like `../x`, so it is not opened.
+    UnsafePath(String),
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Missing(file_name) => write!(f, "{file_name:?} is missing"),
+            Mismatch::Size {
+                file_name,
+                expected,
+                actual,
+            } => write!(f, "{file_name:?} is {actual} bytes, expected {expected}"),
+            Mismatch::Crc {
+                file_name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{file_name:?} has CRC32 {actual:08X}, expected {expected:08X}"
+            ),
+            Mismatch::Headers(file_name) => {
+                write!(f, "{file_name:?} does not match the srr")
+            }
+            Mismatch::UnsafePath(file_name) => {
+                write!(
+                    f,
+                    "{file_name:?} is not a relative path inside the directory"
+                )
+            }
+        }
+    }
+}
+
+impl Srr {
+    /// Every file in the RAR volumes, directories left out.
+    pub fn archived_files(&self) -> Vec<ArchivedFile> {
+        let mut files = vec![];
+        for block in &self.blocks {
+            match &block.inner {
+                // the last part of a split file holds the CRC of the whole file
+                Some(BlockImpl::RarPackedFile(file))
+                    if block.header.flags & RarPackedFile::SPLIT_AFTER == 0
+                        && block.header.flags & RarPackedFile::DIRECTORY
+                            != RarPackedFile::DIRECTORY =>
+                {
+                    files.push(ArchivedFile {
+                        file_name: file.file_name.replace('\\', "/"),
+                        size: file.unpacked_size,
+                        crc: Some(file.file_crc),
+                    });
+                }
+                Some(BlockImpl::Rar5(volume)) => {
+                    for block in &volume.blocks {
+                        match &block.inner {
+                            Rar5BlockImpl::File(file)
+                                if block.header.flags & Rar5BlockHeader::SPLIT_AFTER == 0
+                                    && !file.is_directory() =>
+                            {
+                                files.push(ArchivedFile {
+                                    file_name: file.file_name.clone(),
+                                    size: file.unpacked_size,
+                                    crc: file.data_crc,
+                                });
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        files
+    }
+
+    /// Compares the sizes and CRCs of the archived files against the extracted files in `dir`.
+    pub fn verify_files<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<Vec<Mismatch>> {
+        let dir = dir.as_ref();
+        let mut mismatches = vec![];
+        for file in self.archived_files() {
+            let Ok(path) = relative_path(&file.file_name) else {
+                mismatches.push(Mismatch::UnsafePath(file.file_name));
+                continue;
+            };
+            let mut reader = match std::fs::File::open(dir.join(path)) {
+                Ok(reader) => reader,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                    mismatches.push(Mismatch::Missing(file.file_name));
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            let mut hasher = crc32fast::Hasher::new();
+            let mut size = 0;
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                size += read as u64;
+            }
+
+            if size != file.size {
+                mismatches.push(Mismatch::Size {
+                    file_name: file.file_name,
+                    expected: file.size,
+                    actual: size,
+                });
+            } else if let Some(expected) = file.crc {
+                let actual = hasher.finalize();
+                if actual != expected {
+                    mismatches.push(Mismatch::Crc {
+                        file_name: file.file_name,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Compares the RAR volumes in `dir` against the srr. Besides the headers, the
+    /// CRC32 of each volume is checked against the stored .sfv files when they list
+    /// it, and otherwise the packed data of stored files against their CRCs.
+    pub fn verify_volumes<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<Vec<Mismatch>> {
+        let dir = dir.as_ref();
+        let missing = self.missing_volumes(dir);
+        let sfv = self.sfv_crcs();
+        let mut mismatches = vec![];
+        let mut volumes = self.rar_volumes();
+        crate::sort_volumes_by_key(&mut volumes, |(rar_file, _blocks)| &rar_file.file_name);
+        // CRC32s of the parts of split stored files seen so far, by file name
+        let mut running = HashMap::new();
+        for (rar_file, blocks) in volumes {
+            let file_name = &rar_file.file_name;
+            let Ok(path) = relative_path(file_name) else {
+                mismatches.push(Mismatch::UnsafePath(file_name.clone()));
+                running.clear();
+                continue;
+            };
+            if missing.contains(file_name) {
+                running.clear();
+                continue;
+            }
+            let reader = BufReader::new(File::open(dir.join(path))?);
+            let expected = sfv.get(&file_name.to_lowercase()).copied();
+            let mut volume = VolumeReader::new(reader, expected.is_some())?;
+            match verify_volume(file_name, blocks, &mut volume, &mut running)? {
+                Some(mismatch) => {
+                    running.clear();
+                    mismatches.push(mismatch);
+                }
+                None => {
+                    if let (Some(expected), Some(actual)) = (expected, volume.crc()) {
+                        if actual != expected {
+                            mismatches.push(Mismatch::Crc {
+                                file_name: file_name.clone(),
+                                expected,
+                                actual,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        mismatches.extend(
+            missing
+                .into_iter()
+                .filter(|name| relative_path(name).is_ok())
+                .map(Mismatch::Missing),
+        );
+        Ok(mismatches)
+    }
+
+    /// CRC32s listed in the stored .sfv files, by lowercase file name.
+    fn sfv_crcs(&self) -> HashMap<String, u32> {
+        let mut crcs = HashMap::new();
+        for (file, data) in self.stored_files() {
+            if !file.file_name.to_lowercase().ends_with(".sfv") {
+                continue;
+            }
+            for line in String::from_utf8_lossy(data).lines() {
+                if line.starts_with(';') {
+                    continue;
+                }
+                let Some((name, crc)) = line.trim().rsplit_once(char::is_whitespace) else {
+                    continue;
+                };
+                if let Ok(crc) = u32::from_str_radix(crc, 16) {
+                    crcs.insert(name.trim().to_lowercase(), crc);
+                }
+            }
+        }
+        crcs
+    }
+}
+
+/// Packed data of an archived file, which the srr leaves out of a volume.
+struct Packed<'a> {
+    file_name: &'a str,
+    size: u64,
+    /// CRC32 of this part for split files, otherwise of the whole file.
+    crc: Option<u32>,
+    stored: bool,
+    encrypted: bool,
+    split_before: bool,
+    split_after: bool,
+}
+
+/// What a volume holds for a block of the srr, in order.
+enum Part<'a> {
+    /// Bytes kept in the srr.
+    Kept(&'a [u8]),
+    Packed(Packed<'a>),
+    /// Recovery records and other data that can't be checked on its own.
+    Skipped(u64),
+}
+
+/// The parts of a volume, following how [`crate::Reconstructor`] writes it.
+fn volume_parts(blocks: &[Block]) -> Vec<Part<'_>> {
+    let mut parts = vec![];
+    for block in blocks {
+        match &block.inner {
+            Some(BlockImpl::RarPackedFile(file)) => {
+                let flags = block.header.flags;
+                parts.push(Part::Kept(&block.raw));
+                parts.push(Part::Packed(Packed {
+                    file_name: &file.file_name,
+                    size: file.packed_size,
+                    crc: Some(file.file_crc),
+                    stored: file.is_stored(),
+                    encrypted: flags & RarPackedFile::PASSWORD != 0,
+                    split_before: flags & RarPackedFile::SPLIT_BEFORE != 0,
+                    split_after: flags & RarPackedFile::SPLIT_AFTER != 0,
+                }));
+            }
+            Some(BlockImpl::RarNewSub(sub)) => {
+                parts.push(Part::Kept(&block.raw));
+                if sub.file.file_name != "CMT" {
+                    parts.push(Part::Skipped(sub.file.packed_size));
+                }
+            }
+            Some(BlockImpl::RarOldRecovery(_)) => {
+                parts.push(Part::Kept(&block.raw));
+                parts.push(Part::Skipped(block.header.add_size as u64));
+            }
+            Some(BlockImpl::Rar5(volume)) => {
+                parts.push(Part::Kept(&block.raw[..block.header.size as usize]));
+                for block in &volume.blocks {
+                    parts.push(Part::Kept(&block.raw));
+                    let omitted = block.header.data_size - block.stored_data_len() as u64;
+                    match &block.inner {
+                        Rar5BlockImpl::File(file) => parts.push(Part::Packed(Packed {
+                            file_name: &file.file_name,
+                            size: omitted,
+                            crc: file.data_crc,
+                            stored: file.compression_method() == 0,
+                            encrypted: file
+                                .extra
+                                .iter()
+                                .any(|extra| matches!(extra, Rar5FileExtra::Other { ty: 1, .. })),
+                            split_before: block.header.flags & Rar5BlockHeader::SPLIT_BEFORE != 0,
+                            split_after: block.header.flags & Rar5BlockHeader::SPLIT_AFTER != 0,
+                        })),
+                        _ => parts.push(Part::Skipped(omitted)),
+                    }
+                }
+            }
+            Some(BlockImpl::SrrRarPadding) => parts.push(Part::Kept(block.data())),
+            Some(
+                BlockImpl::SrrStoredFile(_) | BlockImpl::SrrRarFile(_) | BlockImpl::SrrOsoHash,
+            ) => {}
+            _ => parts.push(Part::Kept(&block.raw)),
+        }
+    }
+    parts
+}
+
+/// Compares a volume against its blocks in the srr. Without a volume CRC to check,
+/// the packed data of stored files is checked instead.
+fn verify_volume<R: Read + Seek>(
+    file_name: &str,
+    blocks: &[Block],
+    volume: &mut VolumeReader<R>,
+    running: &mut HashMap<String, crc32fast::Hasher>,
+) -> std::io::Result<Option<Mismatch>> {
+    let headers = || Ok(Some(Mismatch::Headers(file_name.to_owned())));
+    for part in volume_parts(blocks) {
+        let packed = match part {
+            Part::Kept(expected) if volume.matches(expected)? => continue,
+            Part::Skipped(size) if volume.skip(size)? => continue,
+            Part::Packed(packed) => packed,
+            _ => return headers(),
+        };
+        if volume.hasher.is_some() || packed.encrypted {
+            running.remove(packed.file_name);
+            if !volume.skip(packed.size)? {
+                return headers();
+            }
+            continue;
+        }
+
+        // split parts of a stored file add up to the CRC of the whole file
+        let split = packed.split_before || packed.split_after;
+        if packed.stored && split && !packed.split_before {
+            running.insert(packed.file_name.to_owned(), crc32fast::Hasher::new());
+        }
+        let Some(actual) = volume.data(packed.size, running.get_mut(packed.file_name))? else {
+            return headers();
+        };
+        let (expected, actual) = if packed.split_after {
+            // every part but the last has the CRC of its own packed data
+            (packed.crc, Some(actual))
+        } else if !packed.stored {
+            (None, None)
+        } else if packed.split_before {
+            let whole = running.remove(packed.file_name);
+            (packed.crc, whole.map(crc32fast::Hasher::finalize))
+        } else {
+            (packed.crc, Some(actual))
+        };
+        if let (Some(expected), Some(actual)) = (expected, actual) {
+            if actual != expected {
+                return Ok(Some(Mismatch::Crc {
+                    file_name: file_name.to_owned(),
+                    expected,
+                    actual,
+                }));
+            }
+        }
+    }
+    if volume.remaining > 0 {
+        // data after what the srr describes
+        return headers();
+    }
+    Ok(None)
+}
+
+/// Reads a volume in chunks, hashing all of it when its CRC32 is to be checked and
+/// seeking past data that isn't otherwise.
+struct VolumeReader<R> {
+    reader: R,
+    hasher: Option<crc32fast::Hasher>,
+    buffer: Vec<u8>,
+    remaining: u64,
+}
+
+impl<R: Read + Seek> VolumeReader<R> {
+    fn new(mut reader: R, hash: bool) -> std::io::Result<Self> {
+        let start = reader.stream_position()?;
+        let remaining = reader.seek(SeekFrom::End(0))?.saturating_sub(start);
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            reader,
+            hasher: hash.then(crc32fast::Hasher::new),
+            buffer: vec![0; 64 * 1024],
+            remaining,
+        })
+    }
+
+    /// CRC32 of the whole volume once it has been read, if it was hashed.
+    fn crc(&self) -> Option<u32> {
+        self.hasher.clone().map(crc32fast::Hasher::finalize)
+    }
+
+    /// Reads the next `size` bytes a chunk at a time. False if the volume ends first.
+    fn chunks<F>(&mut self, size: u64, mut f: F) -> std::io::Result<bool>
+    where
+        F: FnMut(&[u8], u64) -> bool,
+    {
+        if size > self.remaining {
+            return Ok(false);
+        }
+        let mut offset = 0;
+        while offset < size {
+            let len = (size - offset).min(self.buffer.len() as u64) as usize;
+            self.reader.read_exact(&mut self.buffer[..len])?;
+            if let Some(hasher) = &mut self.hasher {
+                hasher.update(&self.buffer[..len]);
+            }
+            self.remaining -= len as u64;
+            if !f(&self.buffer[..len], offset) {
+                return Ok(false);
+            }
+            offset += len as u64;
+        }
+        Ok(true)
+    }
+
+    /// Whether the next bytes are `expected`.
+    fn matches(&mut self, expected: &[u8]) -> std::io::Result<bool> {
+        self.chunks(expected.len() as u64, |chunk, offset| {
+            let offset = offset as usize;
+            chunk == &expected[offset..offset + chunk.len()]
+        })
+    }
+
+    /// CRC32 of the next `size` bytes, which are also added to `running`. `None` if the
+    /// volume ends first.
+    fn data(
+        &mut self,
+        size: u64,
+        mut running: Option<&mut crc32fast::Hasher>,
+    ) -> std::io::Result<Option<u32>> {
+        let mut hasher = crc32fast::Hasher::new();
+        let complete = self.chunks(size, |chunk, _offset| {
+            hasher.update(chunk);
+            if let Some(running) = &mut running {
+                running.update(chunk);
+            }
+            true
+        })?;
+        Ok(complete.then(|| hasher.finalize()))
+    }
+
+    /// Moves past the next `size` bytes. False if the volume ends first.
+    fn skip(&mut self, size: u64) -> std::io::Result<bool> {
+        if self.hasher.is_some() {
+            return self.chunks(size, |_chunk, _offset| true);
+        }
+        if size > self.remaining {
+            return Ok(false);
+        }
+        self.reader.seek(SeekFrom::Current(size as i64))?;
+        self.remaining -= size;
+        Ok(true)
+    }
+}
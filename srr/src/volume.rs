@@ -0,0 +1,339 @@
+use crate::reconstruct::relative_path;
+use crate::Srr;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// How the volumes of a RAR set are named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VolumeScheme {
+    /// `name.rar`, `name.r00` ... `name.r99`, `name.s00` ... `name.z99`.
+    Old,
+    /// `name.part1.rar` ... with the volume number padded to `digits`.
+    Part { digits: usize },
+    /// `name.001`, `name.002` ... as written by file splitters.
+    Numbered { digits: usize },
+}
+
+/// A volume file name split into the set it belongs to and its position in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeName {
+    /// Everything before the volume number, e.g. `CD1/name` for `CD1/name.r00`.
+    pub stem: String,
+    pub scheme: VolumeScheme,
+    /// Only set by the constructors, which keep old style names within `.z99`.
+    index: u32,
+    /// Whether the extension was written in upper case, e.g. `.R00`.
+    pub uppercase: bool,
+}
+
+impl VolumeName {
+    pub fn parse(name: &str) -> Option<Self> {
+        let (stem, extension) = name.rsplit_once('.')?;
+        let uppercase = extension.starts_with(|c: char| c.is_ascii_uppercase());
+        let lower = extension.to_ascii_lowercase();
+
+        if lower == "rar" {
+            if let Some((stem, part)) = stem.rsplit_once('.') {
+                let number = part
+                    .get(..4)
+                    .filter(|prefix| prefix.eq_ignore_ascii_case("part"))
+                    .map(|_| &part[4..])
+                    .filter(|digits| is_number(digits));
+                if let Some(number) = number {
+                    return Some(Self {
+                        stem: stem.to_owned(),
+                        scheme: VolumeScheme::Part {
+                            digits: number.len(),
+                        },
+                        index: number.parse::<u32>().ok()?.checked_sub(1)?,
+                        uppercase,
+                    });
+                }
+            }
+            return Some(Self {
+                stem: stem.to_owned(),
+                scheme: VolumeScheme::Old,
+                index: 0,
+                uppercase,
+            });
+        }
+
+        let bytes = lower.as_bytes();
+        if bytes.len() == 3 && (b'r'..=b'z').contains(&bytes[0]) && is_number(&lower[1..]) {
+            let number = lower[1..].parse::<u32>().ok()?;
+            return Some(Self {
+                stem: stem.to_owned(),
+                scheme: VolumeScheme::Old,
+                index: (bytes[0] - b'r') as u32 * 100 + number + 1,
+                uppercase,
+            });
+        }
+        if bytes.len() >= 3 && is_number(&lower) {
+            return Some(Self {
+                stem: stem.to_owned(),
+                scheme: VolumeScheme::Numbered {
+                    digits: lower.len(),
+                },
+                index: lower.parse::<u32>().ok()?.checked_sub(1)?,
+                uppercase: false,
+            });
+        }
+        None
+    }
+
+    /// The name of the volume at `index` in the same set, `None` past `.z99` for old style names.
+    pub fn with_index(&self, index: u32) -> Option<Self> {
+        if self.scheme == VolumeScheme::Old && index > 9 * 100 {
+            return None;
+        }
+        Some(Self {
+            index,
+            ..self.clone()
+        })
+    }
+
+    /// 0 for the first volume.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn next(&self) -> Option<Self> {
+        self.with_index(self.index.checked_add(1)?)
+    }
+
+    pub fn previous(&self) -> Option<Self> {
+        self.with_index(self.index.checked_sub(1)?)
+    }
+
+    pub fn is_first(&self) -> bool {
+        self.index == 0
+    }
+
+    /// Volumes of the same set have the same key, stems are compared case insensitively.
+    pub(crate) fn set_key(&self) -> (String, std::mem::Discriminant<VolumeScheme>) {
+        (
+            self.stem.to_lowercase(),
+            std::mem::discriminant(&self.scheme),
+        )
+    }
+}
+
+impl std::fmt::Display for VolumeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let extension = match self.scheme {
+            VolumeScheme::Old if self.index == 0 => "rar".to_owned(),
+            VolumeScheme::Old => {
+                let letter = (b'r' + ((self.index - 1) / 100) as u8) as char;
+                format!("{letter}{:02}", (self.index - 1) % 100)
+            }
+            VolumeScheme::Part { digits } => {
+                format!("part{:0digits$}.rar", u64::from(self.index) + 1)
+            }
+            VolumeScheme::Numbered { digits } => format!("{:0digits$}", u64::from(self.index) + 1),
+        };
+        if self.uppercase {
+            write!(f, "{}.{}", self.stem, extension.to_ascii_uppercase())
+        } else {
+            write!(f, "{}.{}", self.stem, extension)
+        }
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Orders volumes by their position in the set. Sets keep the order in which they
+/// first appear, names that are not RAR volumes sort after them.
+pub fn sort_volumes_by_key<T, F>(items: &mut [T], name: F)
+where
+    F: Fn(&T) -> &str,
+{
+    let mut sets = HashMap::new();
+    for item in items.iter() {
+        if let Some(volume) = VolumeName::parse(name(item)) {
+            let position = sets.len();
+            sets.entry(volume.set_key()).or_insert(position);
+        }
+    }
+    items.sort_by_cached_key(|item| match VolumeName::parse(name(item)) {
+        Some(volume) => (sets[&volume.set_key()], volume.index),
+        None => (usize::MAX, 0),
+    });
+}
+
+pub fn sort_volumes<S: AsRef<str>>(names: &mut [S]) {
+    sort_volumes_by_key(names, |name| name.as_ref())
+}
+
+/// Volume numbers from untrusted names above this are not looked for: no RAR set
+/// is that large.
+const MAX_VOLUME_INDEX: u32 = 99_999;
+
+/// At most this many missing volumes are reported.
+const MAX_MISSING_VOLUMES: usize = 1_000;
+
+/// Volumes missing between the first volume and the last one present of every set,
+/// the first 1000 of them. Volumes numbered above 100000 are ignored.
+pub fn missing_volumes<S: AsRef<str>>(names: &[S]) -> Vec<String> {
+    let mut sets = Vec::<(VolumeName, BTreeSet<u32>)>::new();
+    let mut positions = HashMap::new();
+    for volume in names
+        .iter()
+        .filter_map(|name| VolumeName::parse(name.as_ref()))
+        .filter(|volume| volume.index <= MAX_VOLUME_INDEX)
+    {
+        let position = *positions.entry(volume.set_key()).or_insert(sets.len());
+        if position == sets.len() {
+            sets.push((volume.clone(), BTreeSet::new()));
+        }
+        sets[position].1.insert(volume.index);
+    }
+
+    let mut missing = vec![];
+    for (volume, indices) in sets {
+        let mut next = 0;
+        for index in indices {
+            missing.extend(
+                (next..index)
+                    .take(MAX_MISSING_VOLUMES - missing.len())
+                    .filter_map(|index| volume.with_index(index))
+                    .map(|volume| volume.to_string()),
+            );
+            next = index + 1;
+        }
+    }
+    missing
+}
+
+impl Srr {
+    /// Volumes of the srr that are not in `dir`, in volume order. Gaps in the srr's own
+    /// list, like the first volume of an incomplete srr, count as missing, and so do
+    /// names that would leave `dir`, which are not looked up.
+    pub fn missing_volumes<P: AsRef<Path>>(&self, dir: P) -> Vec<String> {
+        let dir = dir.as_ref();
+        let mut names = self
+            .rar_volumes()
+            .into_iter()
+            .map(|(file, _blocks)| file.file_name.clone())
+            .collect::<Vec<_>>();
+        names.extend(missing_volumes(&names));
+        sort_volumes(&mut names);
+        names.retain(|name| !relative_path(name).is_ok_and(|path| dir.join(path).is_file()));
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(volume: &VolumeName, count: usize) -> Vec<String> {
+        std::iter::successors(Some(volume.clone()), VolumeName::next)
+            .take(count)
+            .map(|volume| volume.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn old_style() {
+        let volume = VolumeName::parse("winrar2.80.rar").unwrap();
+        assert_eq!(volume.stem, "winrar2.80");
+        assert_eq!(volume.scheme, VolumeScheme::Old);
+        assert_eq!(
+            names(&volume, 3),
+            ["winrar2.80.rar", "winrar2.80.r00", "winrar2.80.r01"]
+        );
+
+        let volume = VolumeName::parse("name.r99").unwrap();
+        assert_eq!(volume.index, 100);
+        assert_eq!(volume.next().unwrap().to_string(), "name.s00");
+        assert_eq!(
+            VolumeName::parse("name.s00").unwrap().previous(),
+            Some(volume)
+        );
+        assert_eq!(VolumeName::parse("name.z99").unwrap().next(), None);
+        assert_eq!(VolumeName::parse("name.rar").unwrap().with_index(901), None);
+        assert_eq!(
+            VolumeName::parse("NAME.R05")
+                .unwrap()
+                .with_index(0)
+                .unwrap()
+                .to_string(),
+            "NAME.RAR"
+        );
+    }
+
+    #[test]
+    fn part_style() {
+        let volume = VolumeName::parse("CD1/sdx-antz-int.part09.rar").unwrap();
+        assert_eq!(volume.stem, "CD1/sdx-antz-int");
+        assert_eq!(volume.scheme, VolumeScheme::Part { digits: 2 });
+        assert_eq!(volume.index, 8);
+        assert_eq!(
+            names(&volume, 2),
+            ["CD1/sdx-antz-int.part09.rar", "CD1/sdx-antz-int.part10.rar"]
+        );
+        assert_eq!(VolumeName::parse("a.part1.rar").unwrap().previous(), None);
+        let last = VolumeName::parse("a.part4294967295.rar").unwrap().next();
+        assert_eq!(last.unwrap().to_string(), "a.part4294967296.rar");
+        assert_eq!(
+            VolumeName::parse("a.part0.rar"),
+            None,
+            "volumes are numbered from 1"
+        );
+    }
+
+    #[test]
+    fn numbered_style() {
+        let volume = VolumeName::parse("movie.avi.001").unwrap();
+        assert_eq!(volume.stem, "movie.avi");
+        assert!(volume.is_first());
+        assert_eq!(names(&volume, 2), ["movie.avi.001", "movie.avi.002"]);
+        assert_eq!(VolumeName::parse("readme.txt"), None);
+        assert_eq!(VolumeName::parse("file.01"), None);
+    }
+
+    #[test]
+    fn ordering() {
+        let mut names = [
+            "b.r00",
+            "a.part10.rar",
+            "b.s00",
+            "a.part2.rar",
+            "b.rar",
+            "a.sfv",
+            "b.r99",
+            "a.part1.rar",
+        ];
+        sort_volumes(&mut names);
+        assert_eq!(
+            names,
+            [
+                "b.rar",
+                "b.r00",
+                "b.r99",
+                "b.s00",
+                "a.part1.rar",
+                "a.part2.rar",
+                "a.part10.rar",
+                "a.sfv"
+            ]
+        );
+    }
+
+    #[test]
+    fn gaps() {
+        assert_eq!(
+            missing_volumes(&["x.r01", "x.r03", "Y.part2.rar", "y.part3.rar"]),
+            ["x.rar", "x.r00", "x.r02", "Y.part1.rar"]
+        );
+        assert!(missing_volumes(&["a.rar", "a.r00", "a.r01"]).is_empty());
+
+        // names can claim any volume number
+        assert!(missing_volumes(&["a.part1.rar", "a.part300000.rar"]).is_empty());
+        let missing = missing_volumes(&["a.part1.rar", "a.part99999.rar", "b.part9.rar"]);
+        assert_eq!(missing.len(), MAX_MISSING_VOLUMES);
+        assert_eq!(missing[0], "a.part2.rar");
+    }
+}
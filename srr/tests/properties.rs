@@ -0,0 +1,161 @@
+//! Parsing untrusted input must never panic. The inputs are fixtures with random damage.
+
+use proptest::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+fn fixtures(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            fixtures(&path, out);
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension == "srr" || extension.starts_with('r'))
+        {
+            out.push(path);
+        }
+    }
+}
+
+/// Every srr and RAR volume in the test directory.
+fn corpus() -> &'static [Vec<u8>] {
+    static CORPUS: OnceLock<Vec<Vec<u8>>> = OnceLock::new();
+    CORPUS.get_or_init(|| {
+        let mut paths = vec![];
+        fixtures(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"),
+            &mut paths,
+        );
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| std::fs::read(path).unwrap())
+            .collect()
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Damage {
+    Truncate(prop::sample::Index),
+    Overwrite(prop::sample::Index, u8),
+    Insert(prop::sample::Index, Vec<u8>),
+    Remove(prop::sample::Index, prop::sample::Index),
+}
+
+impl Damage {
+    fn apply(&self, input: &mut Vec<u8>) {
+        if input.is_empty() {
+            return;
+        }
+        match self {
+            Damage::Truncate(at) => input.truncate(at.index(input.len())),
+            Damage::Overwrite(at, byte) => {
+                let at = at.index(input.len());
+                input[at] = *byte;
+            }
+            Damage::Insert(at, bytes) => {
+                let at = at.index(input.len());
+                input.splice(at..at, bytes.iter().copied());
+            }
+            Damage::Remove(at, len) => {
+                let at = at.index(input.len());
+                let end = at + len.index(input.len() - at).min(16);
+                input.drain(at..end);
+            }
+        }
+    }
+}
+
+fn damage() -> impl Strategy<Value = Damage> {
+    prop_oneof![
+        any::<prop::sample::Index>().prop_map(Damage::Truncate),
+        (any::<prop::sample::Index>(), any::<u8>())
+            .prop_map(|(at, byte)| Damage::Overwrite(at, byte)),
+        // header sizes are small numbers, 0xFF bytes make them huge
+        (any::<prop::sample::Index>(), Just(0xFF))
+            .prop_map(|(at, byte)| Damage::Overwrite(at, byte)),
+        (any::<prop::sample::Index>(), Just(0x00))
+            .prop_map(|(at, byte)| Damage::Overwrite(at, byte)),
+        (
+            any::<prop::sample::Index>(),
+            prop::collection::vec(any::<u8>(), 1..16)
+        )
+            .prop_map(|(at, bytes)| Damage::Insert(at, bytes)),
+        (any::<prop::sample::Index>(), any::<prop::sample::Index>())
+            .prop_map(|(at, len)| Damage::Remove(at, len)),
+    ]
+}
+
+fn damaged_fixture() -> impl Strategy<Value = Vec<u8>> {
+    (
+        any::<prop::sample::Index>(),
+        prop::collection::vec(damage(), 1..8),
+    )
+        .prop_map(|(fixture, damage)| {
+            let corpus = corpus();
+            let mut input = corpus[fixture.index(corpus.len())].clone();
+            for damage in &damage {
+                damage.apply(&mut input);
+            }
+            input
+        })
+}
+
+/// Runs everything that reads from a parsed srr.
+fn inspect(srr: &srr::Srr) {
+    let _ = srr.app_name();
+    let _ = srr.archived_files();
+    let _ = srr.rar_settings();
+    let _ = srr.stored_files().count();
+    let _ = srr.samples().count();
+    let _ = srr.rar_sets();
+    let _ = srr.nested_srrs(&srr::ParseOptions::default());
+    for (_file, blocks) in srr.rar_volumes() {
+        let _ = srr::volume_size(blocks);
+    }
+    for block in &srr.blocks {
+        let _ = block.data();
+        if let Some(srr::BlockImpl::Rar5(volume)) = &block.inner {
+            for block in &volume.blocks {
+                let _ = block.crc_is_valid();
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn damaged_srrs(input in damaged_fixture()) {
+        let options = srr::ParseOptions::default();
+        let parsed = srr::Srr::parse_with_options(&input, &options);
+        if let Ok((rest, srr)) = srr::Srr::new(&input) {
+            prop_assert!(rest.is_empty());
+            prop_assert_eq!(&srr.to_bytes(), &input);
+            prop_assert!(parsed.is_ok());
+            inspect(&srr);
+        } else {
+            prop_assert!(parsed.is_err());
+        }
+    }
+
+    #[test]
+    fn damaged_volumes(input in damaged_fixture()) {
+        let _ = srr::RarVolume::parse(&input);
+        if let Ok(srr) = srr::Srr::create("", [("damaged.rar", input.as_slice())]) {
+            inspect(&srr);
+        }
+    }
+
+    #[test]
+    fn random_bytes(input in prop::collection::vec(any::<u8>(), 0..256)) {
+        if let Ok((_rest, srr)) = srr::Srr::new(&input) {
+            inspect(&srr);
+        }
+        let _ = srr::RarVolume::parse(&input);
+        let _ = srr::Rar5Volume::parse(&input);
+        let _ = srr::Rar5Volume::parse_archive(&input);
+        let _ = srr::Srs::parse(&input);
+    }
+}
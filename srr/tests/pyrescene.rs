@@ -1,6 +1,4 @@
-#[test]
-fn parse_pyrescenes() {
-    let files = vec![
+const FILES: &[&str] = &[
         "pyrescene_test_files/best_little/added_empty_file.srr",
         "pyrescene_test_files/bug_detected_as_being_different/The.First.Great.Train.Robbery.1978.iNTERNAL.DVDRip.XviD-EXViDiNT_nzbsauto.srr",
         "pyrescene_test_files/bug_detected_as_being_different/The.First.Great.Train.Robbery.1978.iNTERNAL.DVDRip.XviD-EXViDiNT_yopom.srr",
@@ -53,12 +51,312 @@ fn parse_pyrescenes() {
         "pyrescene_test_files/store_split_folder_old_srrsfv_windows/winrar2.80.srr",
         "pyrescene_test_files/store_utf8_comment/store_utf8_comment.srr",
         "pyrescene_test_files/store_utf8_comment/utf8_filename_added.srr",
-    ];
+];
+
+fn read(file: &str) -> Vec<u8> {
     let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
-    for file in files {
+    std::fs::read(root.join(file)).unwrap()
+}
+
+fn load(file: &str) -> srr::Srr {
+    let input = read(file);
+    let (rest, srr) = srr::Srr::new(&input).expect(file);
+    assert!(rest.is_empty());
+    srr
+}
+
+#[test]
+fn parse_pyrescenes() {
+    for file in FILES {
         // println!("{}", file);
-        let input = std::fs::read(root.join(file)).unwrap();
-        let (rest, _srr) = srr::Srr::new(&input).expect(file);
-        assert!(rest.is_empty());
+        let _srr = load(file);
+    }
+}
+
+#[test]
+fn roundtrip_pyrescenes() {
+    for file in FILES {
+        assert_eq!(load(file).to_bytes(), read(file), "{file}");
+    }
+}
+
+#[test]
+fn add_empty_file() {
+    let mut srr = load("pyrescene_test_files/store_empty/store_empty.srr");
+    srr.add_stored_file("empty_file.txt", &[]).unwrap();
+    assert_eq!(
+        srr.to_bytes(),
+        read("pyrescene_test_files/store_empty/added_empty_file.srr")
+    );
+}
+
+#[test]
+fn add_utf8_file_name() {
+    let expected = load("pyrescene_test_files/store_utf8_comment/utf8_filename_added.srr");
+    let (stored, data) = expected.stored_files().next().unwrap();
+
+    let mut srr = load("pyrescene_test_files/store_utf8_comment/store_utf8_comment.srr");
+    srr.add_stored_file(&stored.file_name, data).unwrap();
+    assert_eq!(srr.to_bytes(), expected.to_bytes());
+    assert!(matches!(
+        srr.add_stored_file(&stored.file_name, data),
+        Err(srr::EditError::AlreadyExists(_))
+    ));
+}
+
+#[test]
+fn remove_stored_file() {
+    let mut srr = load("pyrescene_test_files/store_empty/added_empty_file.srr");
+    assert_eq!(srr.remove_stored_file("empty_file.txt").unwrap(), b"");
+    assert_eq!(
+        srr.to_bytes(),
+        read("pyrescene_test_files/store_empty/store_empty.srr")
+    );
+    assert!(matches!(
+        srr.remove_stored_file("empty_file.txt"),
+        Err(srr::EditError::NotFound(_))
+    ));
+}
+
+#[test]
+fn rename_stored_file() {
+    let mut srr = load("pyrescene_test_files/store_utf8_comment/utf8_filename_added.srr");
+    let (stored, data) = srr.stored_files().next().unwrap();
+    let (name, data) = (stored.file_name.clone(), data.to_vec());
+
+    srr.rename_stored_file(&name, "Sample\\greek.txt").unwrap();
+    let (_rest, srr) = srr::Srr::new(&srr.to_bytes()).unwrap();
+    assert_eq!(srr.stored_file("Sample/greek.txt"), Some(&data[..]));
+    assert_eq!(srr.stored_file(&name), None);
+    // the rar blocks are untouched
+    let expected = read("pyrescene_test_files/store_utf8_comment/store_utf8_comment.srr");
+    assert!(srr.to_bytes().ends_with(&expected[0x19..]));
+}
+
+#[test]
+fn backslash_stored_file_names() {
+    let mut srr = load("pyrescene_test_files/store_empty/store_empty.srr");
+    srr.add_stored_file("Sample\\x.srs", b"srs").unwrap();
+    srr.rename_stored_file("Sample\\x.srs", "Sample\\y.srs")
+        .unwrap();
+    assert_eq!(srr.stored_file("Sample/y.srs"), Some(&b"srs"[..]));
+    assert_eq!(srr.remove_stored_file("Sample\\y.srs").unwrap(), b"srs");
+    assert_eq!(
+        srr.to_bytes(),
+        read("pyrescene_test_files/store_empty/store_empty.srr")
+    );
+}
+
+#[test]
+fn rar_settings_new_volumes() {
+    let srr =
+        load("pyrescene_test_files/store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr");
+    let settings = srr.rar_settings();
+    assert_eq!(*settings.versions.start(), 29);
+    assert_eq!(settings.method, Some(0));
+    assert_eq!(settings.volume_size, Some(33000));
+    assert_eq!(settings.command_line(), "rar a -m0 -v33 -ep -k -rr2");
+}
+
+#[test]
+fn rar_settings_old_volume_names() {
+    let srr =
+        load("pyrescene_test_files/store_split_folder_old_srrsfv_windows/store_split_folder.srr");
+    let settings = srr.rar_settings();
+    assert_eq!(settings.volume_size, Some(32768));
+    assert!(settings.switches.iter().any(|switch| switch == "-vn"));
+    assert!(!settings.switches.iter().any(|switch| switch == "-ep"));
+}
+
+#[test]
+fn rar_settings_old_recovery_record() {
+    let srr = load(
+        "pyrescene_test_files/other/Farscape.S01E01.AC3.DivX.DVDRip.iNTERNAL-AMC_old_style_rr.srr",
+    );
+    let settings = srr.rar_settings();
+    assert_eq!(settings.versions, 15..=28);
+    assert!(settings.switches.iter().any(|switch| switch == "-rr8"));
+    assert!(!settings.supports(&srr::RarExecutable {
+        path: "rar".into(),
+        version: 29,
+    }));
+}
+
+#[test]
+fn volumes_on_disk() {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let srr =
+        load("pyrescene_test_files/store_split_folder_old_srrsfv_windows/store_split_folder.srr");
+    let dir = root.join("pyrescene_test_files/store_split_folder_old_srrsfv_windows");
+    assert!(srr.missing_volumes(dir).is_empty());
+    assert_eq!(
+        srr.missing_volumes(&root),
+        [
+            "store_split_folder.rar",
+            "store_split_folder.r00",
+            "store_split_folder.r01"
+        ]
+    );
+}
+
+#[test]
+fn incomplete_volume_list() {
+    let srr = load(
+        "pyrescene_test_files/incomplete_srr/Shark.Week.2012.Shark.Fight.HDTV.x264-KILLERS.srr",
+    );
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    assert_eq!(
+        srr.missing_volumes(root)[0],
+        "shark.week.2012.shark.fight-killers.rar"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_roundtrip_pyrescenes() {
+    for file in FILES {
+        let json = serde_json::to_string(&load(file)).unwrap();
+        let srr: srr::Srr = serde_json::from_str(&json).expect(file);
+        assert_eq!(srr.to_bytes(), read(file), "{file}");
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_field_formats() {
+    let srr = load("pyrescene_test_files/store_little/store_little_srrfile_with_path.srr");
+    let json = serde_json::to_value(&srr).unwrap();
+    let blocks = json["blocks"].as_array().unwrap();
+    assert_eq!(blocks[0]["header"]["crc"], "6969");
+    assert_eq!(blocks[0]["header"]["ty"], "srr_header");
+    assert_eq!(blocks[0]["raw"].as_str().unwrap()[..6], *"696969");
+
+    let file = blocks
+        .iter()
+        .find_map(|block| block["inner"].get("rar_packed_file"))
+        .unwrap();
+    assert_eq!(file["file_name"], "little_file.txt");
+    assert_eq!(file["file_crc"], "876DBBA3");
+}
+
+#[test]
+fn dos_timestamps_roundtrip() {
+    for file in FILES {
+        for block in load(file).blocks {
+            if let Some(srr::BlockImpl::RarPackedFile(packed)) = &block.inner {
+                // header, unpacked size, host OS and CRC come before the timestamp
+                let stamp = u32::from_le_bytes(block.raw[20..24].try_into().unwrap());
+                assert_eq!(packed.datetime.to_dos(), stamp, "{file}");
+            }
+        }
+    }
+}
+
+#[test]
+fn parse_limits() {
+    use srr::{Limit, ParseError, ParseOptions};
+
+    let file = "pyrescene_test_files/store_split_folder_old_srrsfv_windows/store_split_folder.srr";
+    let input = read(file);
+    let srr = srr::Srr::parse_with_options(&input, &ParseOptions::default()).unwrap();
+    assert_eq!(srr.to_bytes(), input);
+
+    let limit = |options: ParseOptions| match srr::Srr::parse_with_options(&input, &options) {
+        Err(ParseError::LimitExceeded { limit, .. }) => Some(limit),
+        _ => None,
+    };
+    let stored_size = srr.stored_files().map(|(_file, data)| data.len()).max();
+    assert_eq!(
+        limit(ParseOptions {
+            max_stored_file_size: stored_size.unwrap() as u64 - 1,
+            ..Default::default()
+        }),
+        Some(Limit::StoredFileSize)
+    );
+    assert_eq!(
+        limit(ParseOptions {
+            max_blocks: srr.blocks.len() - 1,
+            ..Default::default()
+        }),
+        Some(Limit::Blocks)
+    );
+    assert_eq!(
+        limit(ParseOptions {
+            max_file_name_length: 10,
+            ..Default::default()
+        }),
+        Some(Limit::FileNameLength)
+    );
+    assert_eq!(
+        limit(ParseOptions {
+            max_memory: input.len() - 1,
+            ..Default::default()
+        }),
+        Some(Limit::Memory)
+    );
+    assert_eq!(
+        limit(ParseOptions {
+            max_stored_file_size: stored_size.unwrap() as u64,
+            max_blocks: srr.blocks.len(),
+            max_file_name_length: 64,
+            max_memory: input.len(),
+        }),
+        None
+    );
+
+    assert_eq!(
+        srr::Srr::parse_with_options(&input[..input.len() - 1], &ParseOptions::default())
+            .unwrap_err(),
+        ParseError::Incomplete
+    );
+}
+
+/// A RAR 2.x archive with a comment, an authenticity verification, a Unix owner and
+/// an NTFS stream subblock and a signature, written after the RAR 2.x technote
+/// around the file of store_little.rar, and its srr made by `srr create`.
+#[test]
+fn old_style_blocks() {
+    let volume = read("old_blocks/old_blocks.rar");
+    let input = read("old_blocks/old_blocks.srr");
+    let srr = load("old_blocks/old_blocks.srr");
+    assert_eq!(srr.to_bytes(), input);
+
+    let inner = srr
+        .blocks
+        .iter()
+        .filter_map(|block| block.inner.as_ref())
+        .collect::<Vec<_>>();
+    let [srr::BlockImpl::SrrRarFile(_), srr::BlockImpl::RarVolumeHeader, srr::BlockImpl::OldComment(comment), srr::BlockImpl::OldAuthenticity1(authenticity), srr::BlockImpl::RarPackedFile(_), srr::BlockImpl::OldSubblock(owner), srr::BlockImpl::OldSubblock(stream), srr::BlockImpl::OldAuthenticity2(signature)] =
+        inner[..]
+    else {
+        panic!("unexpected blocks: {inner:?}");
+    };
+    assert_eq!(comment.unpacked_size, 5);
+    assert_eq!(comment.comment_crc, crc32fast::hash(b"hello") as u16);
+    assert_eq!(authenticity.av_info_crc, 0x1234_5678);
+    assert!(matches!(
+        &owner.data,
+        srr::RarOldSubblockData::UnixOwner { owner, group } if owner == "root" && group == "staff"
+    ));
+    assert!(matches!(
+        stream.data,
+        srr::RarOldSubblockData::NtfsStream { .. }
+    ));
+    assert_eq!(signature.creation_time.to_string(), "2012-08-01T21:06:36");
+    assert_eq!(signature.user_name, "nick");
+
+    // the comment and the stream data are kept, unlike file data
+    let created = srr::Srr::create("srr 0.1.0", [("old_blocks.rar", volume.as_slice())]).unwrap();
+    assert_eq!(created.to_bytes(), input);
+
+    let (_rest, parsed) = srr::RarVolume::parse(&volume).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    for (file, data) in parsed.packed_files() {
+        std::fs::write(dir.path().join(&file.file_name), data).unwrap();
     }
+    let output = tempfile::tempdir().unwrap();
+    srr::Reconstructor::new(dir.path())
+        .reconstruct_to_dir(&srr, output.path())
+        .unwrap();
+    assert!(std::fs::read(output.path().join("old_blocks.rar")).unwrap() == volume);
 }
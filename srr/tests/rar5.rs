@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+
+const VOLUMES: [&str; 3] = [
+    "rar5_store_split.part1.rar",
+    "rar5_store_split.part2.rar",
+    "rar5_store_split.part3.rar",
+];
+
+/// A stored RAR5 set split over three volumes, written after the RAR 5.0 archive
+/// format description and checked with libarchive, with an srr made by
+/// `srr create --store rar5_store_split.sfv`.
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/rar5")
+}
+
+fn load() -> (Vec<u8>, srr::Srr) {
+    let input = std::fs::read(root().join("rar5_store_split.srr")).unwrap();
+    let (rest, srr) = srr::Srr::new(&input).unwrap();
+    assert!(rest.is_empty());
+    (input, srr)
+}
+
+fn rar5_volumes(srr: &srr::Srr) -> Vec<&srr::Rar5Volume> {
+    srr.blocks
+        .iter()
+        .filter_map(|block| match &block.inner {
+            Some(srr::BlockImpl::Rar5(volume)) => Some(volume),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn parse() {
+    let (input, srr) = load();
+    assert_eq!(srr.to_bytes(), input);
+    assert_eq!(srr.app_name().as_deref(), Some("srr 0.1.0"));
+    assert!(srr.stored_file("rar5_store_split.sfv").is_some());
+
+    let names = srr
+        .rar_volumes()
+        .iter()
+        .map(|(file, _blocks)| file.file_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, VOLUMES);
+
+    let volumes = rar5_volumes(&srr);
+    assert_eq!(volumes.len(), 3);
+    for (number, volume) in volumes.iter().enumerate() {
+        assert!(volume.blocks.iter().all(srr::Rar5Block::crc_is_valid));
+        let main = volume.main_archive().unwrap();
+        assert!(main.is_volume());
+        assert_eq!(main.volume_number, (number > 0).then_some(number as u64));
+        let [file] = volume.files().collect::<Vec<_>>()[..] else {
+            panic!("expected one file in volume {number}");
+        };
+        assert_eq!(file.file_name, "rar5.store.split.bin");
+        assert_eq!(file.compression_method(), 0);
+        let Some(srr::Rar5Block {
+            inner: srr::Rar5BlockImpl::EndOfArchive(end),
+            ..
+        }) = volume.blocks.last()
+        else {
+            panic!("expected an end of archive header");
+        };
+        assert_eq!(end.is_last_volume(), number == 2);
+    }
+
+    assert_eq!(
+        srr.archived_files(),
+        [srr::ArchivedFile {
+            file_name: "rar5.store.split.bin".to_owned(),
+            size: 50_000,
+            crc: Some(0xC60E3693),
+        }]
+    );
+}
+
+#[test]
+fn create_and_reconstruct() {
+    let (_input, srr) = load();
+    let volumes = VOLUMES.map(|name| std::fs::read(root().join(name)).unwrap());
+    let created = srr::Srr::create(
+        "srr 0.1.0",
+        VOLUMES
+            .iter()
+            .copied()
+            .zip(volumes.iter().map(Vec::as_slice)),
+    )
+    .unwrap();
+    assert_eq!(
+        created
+            .rar_volumes()
+            .into_iter()
+            .map(|(_file, blocks)| blocks.iter().map(|block| &block.raw).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        srr.rar_volumes()
+            .into_iter()
+            .map(|(_file, blocks)| blocks.iter().map(|block| &block.raw).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    );
+
+    // the volumes only hold the stored file, after the main and file headers
+    let mut data = vec![];
+    for volume in &volumes {
+        let input = volume.strip_prefix(srr::RAR5_SIGNATURE).unwrap();
+        let (_rest, parsed) = srr::Rar5Volume::parse_archive(input).unwrap();
+        let [main, file, _end] = &parsed.blocks[..] else {
+            panic!("unexpected blocks");
+        };
+        let start = srr::RAR5_SIGNATURE.len() + main.raw.len() + file.raw.len();
+        data.extend_from_slice(&volume[start..start + file.header.data_size as usize]);
+    }
+    assert_eq!(crc32fast::hash(&data), 0xC60E3693);
+    let input = tempfile::tempdir().unwrap();
+    std::fs::write(input.path().join("rar5.store.split.bin"), data).unwrap();
+    let output = tempfile::tempdir().unwrap();
+    srr::Reconstructor::new(input.path())
+        .reconstruct_to_dir(&srr, output.path())
+        .unwrap();
+    for (name, expected) in VOLUMES.iter().zip(volumes) {
+        assert!(
+            std::fs::read(output.path().join(name)).unwrap() == expected,
+            "{name} differs"
+        );
+    }
+}
+
+#[test]
+fn verify_volumes() {
+    let (_input, srr) = load();
+    assert!(srr.verify_volumes(root()).unwrap().is_empty());
+
+    let copy = tempfile::tempdir().unwrap();
+    for name in VOLUMES {
+        let mut volume = std::fs::read(root().join(name)).unwrap();
+        if name == VOLUMES[1] {
+            // the last byte of the file data, before the end of archive header
+            let end = volume.len() - 8;
+            volume[end - 1] ^= 0xFF;
+        }
+        std::fs::write(copy.path().join(name), volume).unwrap();
+    }
+    assert!(matches!(
+        &srr.verify_volumes(copy.path()).unwrap()[..],
+        [srr::Mismatch::Crc { file_name, .. }] if file_name == VOLUMES[1]
+    ));
+}
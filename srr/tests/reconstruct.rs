@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pyrescene_test_files")
+}
+
+fn load(file: &str) -> srr::Srr {
+    let input = std::fs::read(root().join(file)).unwrap();
+    let (rest, srr) = srr::Srr::new(&input).expect(file);
+    assert!(rest.is_empty());
+    srr
+}
+
+/// Writes the packed data of every file in `volumes` into a directory, which
+/// for stored archives are the archived files themselves.
+fn extract(volumes: &[Vec<u8>]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let mut files = HashMap::<String, Vec<u8>>::new();
+    for volume in volumes {
+        let (_rest, volume) = srr::RarVolume::parse(volume).unwrap();
+        for (file, data) in volume.packed_files() {
+            files
+                .entry(file.file_name.replace('\\', "/"))
+                .or_default()
+                .extend_from_slice(data);
+        }
+    }
+    for (name, data) in files {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+    dir
+}
+
+fn assert_reconstructs(srr_file: &str, rar_files: &[&str]) {
+    let dir = Path::new(srr_file).parent().unwrap();
+    let volumes = rar_files
+        .iter()
+        .map(|name| std::fs::read(root().join(dir).join(name)).unwrap())
+        .collect::<Vec<_>>();
+    let input = extract(&volumes);
+    let output = tempfile::tempdir().unwrap();
+
+    srr::Reconstructor::new(input.path())
+        .reconstruct_to_dir(&load(srr_file), output.path())
+        .unwrap();
+    for (name, expected) in rar_files.iter().zip(volumes) {
+        let actual = std::fs::read(output.path().join(name)).unwrap();
+        assert!(actual == expected, "{name} differs");
+    }
+}
+
+#[test]
+fn stored_split_folder() {
+    assert_reconstructs(
+        "store_split_folder_old_srrsfv_windows/store_split_folder.srr",
+        &[
+            "store_split_folder.rar",
+            "store_split_folder.r00",
+            "store_split_folder.r01",
+        ],
+    );
+}
+
+#[test]
+fn stored_with_recovery_record() {
+    assert_reconstructs(
+        "store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr",
+        &[
+            "store_rr_solid_auth.part1.rar",
+            "store_rr_solid_auth.part2.rar",
+            "store_rr_solid_auth.part3.rar",
+        ],
+    );
+}
+
+#[test]
+fn invalid_recovery_record() {
+    let srr_file = "store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr";
+    let input = std::fs::read(root().join(srr_file)).unwrap();
+    let volumes = (1..=3)
+        .map(|part| {
+            let name =
+                format!("store_rr_solid_auth_unicode_new/store_rr_solid_auth.part{part}.rar");
+            std::fs::read(root().join(name)).unwrap()
+        })
+        .collect::<Vec<_>>();
+    let files = extract(&volumes);
+    let tag = input
+        .windows(8)
+        .position(|window| window == b"Protect+")
+        .unwrap();
+    for (offset, value) in [(4, u64::MAX), (0, u32::MAX as u64), (4, 1)] {
+        let mut input = input.clone();
+        let start = tag + 8 + offset;
+        let len = if offset == 0 { 4 } else { 8 };
+        input[start..start + len].copy_from_slice(&value.to_le_bytes()[..len]);
+        let (_rest, srr) = srr::Srr::new(&input).unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let err = srr::Reconstructor::new(files.path())
+            .reconstruct_to_dir(&srr, output.path())
+            .unwrap_err();
+        assert!(
+            matches!(err, srr::ReconstructError::InvalidRecoveryRecord),
+            "{err}"
+        );
+    }
+}
+
+#[test]
+fn missing_archived_file() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    let err = srr::Reconstructor::new(input.path())
+        .reconstruct_to_dir(
+            &load("store_split_folder_old_srrsfv_windows/store_split_folder.srr"),
+            output.path(),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        srr::ReconstructError::MissingFile { file_name, .. } if file_name == "txt\\little_file.txt"
+    ));
+}
+
+#[test]
+fn unsafe_names() {
+    let volume = std::fs::read(root().join("store_little/store_little.rar")).unwrap();
+    let input = extract(std::slice::from_ref(&volume));
+    let output = tempfile::tempdir().unwrap();
+    let inside = output.path().join("inside");
+    for name in [
+        "../store_little.rar",
+        "/tmp/store_little.rar",
+        "a/../../b.rar",
+        "",
+    ] {
+        let srr = srr::Srr::create("srr", [(name, volume.as_slice())]).unwrap();
+        let err = srr::Reconstructor::new(input.path())
+            .reconstruct_to_dir(&srr, &inside)
+            .unwrap_err();
+        assert!(matches!(err, srr::ReconstructError::UnsafePath(path) if path == name));
+    }
+    assert!(!output.path().join("store_little.rar").exists());
+
+    // and archived files outside of the input directory
+    let start = volume
+        .windows(15)
+        .position(|name| name == b"little_file.txt")
+        .unwrap();
+    let mut outside = volume.clone();
+    outside[start..start + 15].copy_from_slice(b"../tle_file.txt");
+    let srr = srr::Srr::create("srr", [("store_little.rar", outside.as_slice())]).unwrap();
+    let err = srr::Reconstructor::new(input.path())
+        .reconstruct_to_dir(&srr, &inside)
+        .unwrap_err();
+    assert!(matches!(err, srr::ReconstructError::UnsafePath(path) if path == "../tle_file.txt"));
+}
+
+#[test]
+fn verify_unsafe_names() {
+    let volume = std::fs::read(root().join("store_little/store_little.rar")).unwrap();
+    // the files the names point to exist, but are outside of the directory
+    let outside = extract(std::slice::from_ref(&volume));
+    std::fs::write(outside.path().join("store_little.rar"), &volume).unwrap();
+    let dir = outside.path().join("inside");
+    std::fs::create_dir(&dir).unwrap();
+
+    let name = "../store_little.rar";
+    let srr = srr::Srr::create("srr", [(name, volume.as_slice())]).unwrap();
+    assert_eq!(srr.missing_volumes(&dir), [name]);
+    assert_eq!(
+        srr.verify_volumes(&dir).unwrap(),
+        [srr::Mismatch::UnsafePath(name.to_owned())]
+    );
+
+    let start = volume
+        .windows(15)
+        .position(|name| name == b"little_file.txt")
+        .unwrap();
+    let mut renamed = volume.clone();
+    renamed[start..start + 15].copy_from_slice(b"../tle_file.txt");
+    std::fs::copy(
+        outside.path().join("little_file.txt"),
+        outside.path().join("tle_file.txt"),
+    )
+    .unwrap();
+    let srr = srr::Srr::create("srr", [("store_little.rar", renamed.as_slice())]).unwrap();
+    assert_eq!(
+        srr.verify_files(&dir).unwrap(),
+        [srr::Mismatch::UnsafePath("../tle_file.txt".to_owned())]
+    );
+}
+
+/// Hands out packed data taken from the original archive.
+struct FakeCompressor {
+    packed: HashMap<String, Vec<u8>>,
+    calls: Vec<Vec<(String, u8, u32)>>,
+}
+
+impl srr::Compressor for FakeCompressor {
+    fn compress(&mut self, files: &[srr::CompressedFile<'_>]) -> std::io::Result<Vec<Vec<u8>>> {
+        self.calls.push(
+            files
+                .iter()
+                .map(|file| {
+                    (
+                        file.file.file_name.clone(),
+                        file.file.compression_method,
+                        file.dictionary_size(),
+                    )
+                })
+                .collect(),
+        );
+        Ok(files
+            .iter()
+            .map(|file| self.packed[&file.file.file_name].clone())
+            .collect())
+    }
+}
+
+/// There is no srr for best_little.rar, so build one from the archive's headers.
+fn best_little() -> (Vec<u8>, srr::Srr) {
+    let rar = std::fs::read(root().join("best_little/best_little.rar")).unwrap();
+    let (_rest, volume) = srr::RarVolume::parse(&rar).unwrap();
+    let mut input = vec![0x69, 0x69, 0x69, 0x00, 0x00, 0x07, 0x00];
+    input.extend([0x71, 0x71, 0x71, 0x01, 0x00, 24, 0x00, 15, 0x00]);
+    input.extend(b"best_little.rar");
+    for block in &volume.blocks {
+        input.extend(block.raw);
+    }
+    let (_rest, srr) = srr::Srr::new(&input).unwrap();
+    (rar, srr)
+}
+
+#[test]
+fn compressed_with_backend() {
+    let (rar, srr) = best_little();
+    let (_rest, volume) = srr::RarVolume::parse(&rar).unwrap();
+    let packed = volume
+        .packed_files()
+        .map(|(file, data)| (file.file_name.clone(), data.to_vec()))
+        .collect();
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+
+    let mut compressor = FakeCompressor {
+        packed,
+        calls: vec![],
+    };
+    srr::Reconstructor::new(input.path())
+        .with_compressor(&mut compressor)
+        .reconstruct_to_dir(&srr, output.path())
+        .unwrap();
+    assert_eq!(
+        std::fs::read(output.path().join("best_little.rar")).unwrap(),
+        rar
+    );
+    assert_eq!(
+        compressor.calls,
+        [[("little_file.txt".to_owned(), 0x35, 128)]]
+    );
+}
+
+#[test]
+fn compressed_without_backend() {
+    let (_rar, srr) = best_little();
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    let err = srr::Reconstructor::new(input.path())
+        .reconstruct_to_dir(&srr, output.path())
+        .unwrap_err();
+    assert!(matches!(err, srr::ReconstructError::Compression(_)));
+}
+
+#[test]
+fn compressed_size_mismatch() {
+    let (_rar, srr) = best_little();
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    let compressor = FakeCompressor {
+        packed: [("little_file.txt".to_owned(), vec![0; 3])].into(),
+        calls: vec![],
+    };
+    let err = srr::Reconstructor::new(input.path())
+        .with_compressor(compressor)
+        .reconstruct_to_dir(&srr, output.path())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        srr::ReconstructError::PackedSizeMismatch {
+            expected: 26,
+            actual: 3,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn no_matching_rar_executable() {
+    let (_rar, srr) = best_little();
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    // best_little.rar needs RAR 2.9 or later
+    let compressor = srr::RarCompressor::new(vec![srr::RarExecutable {
+        path: "rar-2.80".into(),
+        version: 28,
+    }]);
+    let err = srr::Reconstructor::new(input.path())
+        .with_compressor(compressor)
+        .reconstruct_to_dir(&srr, output.path())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        srr::ReconstructError::Compression(err) if err.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+/// A `rar` that records its arguments and writes `best_little.rar` as the archive.
+#[cfg(unix)]
+fn fake_rar(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join("rar");
+    let script = format!(
+        "#!/bin/sh\n\
+         printf '%s\\n' \"$@\" > \"{args}\"\n\
+         shift\n\
+         while [ \"${{1#-}}\" != \"$1\" ]; do shift; done\n\
+         cp \"{rar}\" \"$1\"\n",
+        args = dir.join("args").display(),
+        rar = root().join("best_little/best_little.rar").display(),
+    );
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[cfg(unix)]
+#[test]
+fn rar_executable_arguments() {
+    let (rar, srr) = best_little();
+    for (version, switches) in [
+        (39, &["a", "-y", "-m5", "-md128"][..]),
+        (50, &["a", "-y", "-m5", "-md128k", "-ma4"][..]),
+    ] {
+        let bin = tempfile::tempdir().unwrap();
+        let input = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let compressor = srr::RarCompressor::new(vec![srr::RarExecutable {
+            path: fake_rar(bin.path()),
+            version,
+        }]);
+        srr::Reconstructor::new(input.path())
+            .with_compressor(compressor)
+            .reconstruct_to_dir(&srr, output.path())
+            .unwrap();
+        assert!(std::fs::read(output.path().join("best_little.rar")).unwrap() == rar);
+
+        let args = std::fs::read_to_string(bin.path().join("args")).unwrap();
+        let args = args.lines().collect::<Vec<_>>();
+        let [archive, file] = args[switches.len()..] else {
+            panic!("unexpected arguments {args:?}");
+        };
+        assert_eq!(args[..switches.len()], *switches);
+        assert!(Path::new(archive).is_absolute() && archive.ends_with(".rar"));
+        assert_eq!(file, "little_file.txt");
+        // nothing is left next to the input files
+        assert_eq!(std::fs::read_dir(input.path()).unwrap().count(), 0);
+    }
+}
+
+fn volume_blocks(srr: &srr::Srr) -> Vec<(String, Vec<u8>)> {
+    srr.rar_volumes()
+        .into_iter()
+        .map(|(file, blocks)| {
+            let raw = blocks.iter().flat_map(|block| block.raw.clone()).collect();
+            (file.file_name.clone(), raw)
+        })
+        .collect()
+}
+
+#[test]
+fn create_from_volumes() {
+    for (srr_file, rar_files) in [
+        (
+            "store_split_folder_old_srrsfv_windows/store_split_folder.srr",
+            &[
+                "store_split_folder.rar",
+                "store_split_folder.r00",
+                "store_split_folder.r01",
+            ][..],
+        ),
+        (
+            "store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr",
+            &[
+                "store_rr_solid_auth.part1.rar",
+                "store_rr_solid_auth.part2.rar",
+                "store_rr_solid_auth.part3.rar",
+            ][..],
+        ),
+        (
+            "store_utf8_comment/store_utf8_comment.srr",
+            &["store_utf8_comment.rar"][..],
+        ),
+    ] {
+        let dir = root().join(srr_file).parent().unwrap().to_owned();
+        let volumes = rar_files
+            .iter()
+            .map(|name| std::fs::read(dir.join(name)).unwrap())
+            .collect::<Vec<_>>();
+        let created = srr::Srr::create(
+            "srr",
+            rar_files
+                .iter()
+                .copied()
+                .zip(volumes.iter().map(Vec::as_slice)),
+        )
+        .unwrap();
+        assert_eq!(volume_blocks(&created), volume_blocks(&load(srr_file)));
+    }
+}
+
+#[test]
+fn create_rejects_other_files() {
+    let sfv = std::fs::read(root().join("store_little/store_little.srr")).unwrap();
+    assert!(matches!(
+        srr::Srr::create("srr", [("store_little.srr", sfv.as_slice())]),
+        Err(srr::CreateError::InvalidVolume(name)) if name == "store_little.srr"
+    ));
+}
+
+#[test]
+fn verify_volumes() {
+    let srr_file = "store_split_folder_old_srrsfv_windows/store_split_folder.srr";
+    let srr = load(srr_file);
+    let dir = root().join("store_split_folder_old_srrsfv_windows");
+    assert!(srr.verify_volumes(&dir).unwrap().is_empty());
+
+    let copy = tempfile::tempdir().unwrap();
+    std::fs::copy(
+        dir.join("store_split_folder.rar"),
+        copy.path().join("store_split_folder.rar"),
+    )
+    .unwrap();
+    let mut volume = std::fs::read(dir.join("store_split_folder.r00")).unwrap();
+    let last = volume.len() - 1;
+    volume.truncate(last);
+    std::fs::write(copy.path().join("store_split_folder.r00"), volume).unwrap();
+    assert_eq!(
+        srr.verify_volumes(copy.path()).unwrap(),
+        [
+            srr::Mismatch::Headers("store_split_folder.r00".to_owned()),
+            srr::Mismatch::Missing("store_split_folder.r01".to_owned()),
+        ]
+    );
+}
+
+/// Copies the volumes of `dir` into a new directory, with the last byte of the
+/// packed data in `damaged` flipped.
+fn damage(dir: &Path, names: &[&str], damaged: &str) -> tempfile::TempDir {
+    let copy = tempfile::tempdir().unwrap();
+    for name in names {
+        let mut volume = std::fs::read(dir.join(name)).unwrap();
+        if *name == damaged {
+            let (_rest, parsed) = srr::RarVolume::parse(&volume).unwrap();
+            let (_file, data) = parsed.packed_files().last().unwrap();
+            let offset = data.as_ptr() as usize - volume.as_ptr() as usize + data.len() - 1;
+            volume[offset] ^= 0xFF;
+        }
+        std::fs::write(copy.path().join(name), volume).unwrap();
+    }
+    copy
+}
+
+#[test]
+fn verify_volume_data() {
+    let names = [
+        "store_split_folder.rar",
+        "store_split_folder.r00",
+        "store_split_folder.r01",
+    ];
+    let srr = load("store_split_folder_old_srrsfv_windows/store_split_folder.srr");
+    let dir = root().join("store_split_folder_old_srrsfv_windows");
+
+    // the stored .sfv has the CRC of every volume
+    let copy = damage(&dir, &names, "store_split_folder.rar");
+    assert!(matches!(
+        &srr.verify_volumes(copy.path()).unwrap()[..],
+        [srr::Mismatch::Crc { file_name, expected: 0x9D9E1810, .. }]
+            if file_name == "store_split_folder.rar"
+    ));
+
+    // without it the packed data is checked against the CRCs in the file headers
+    let srr = srr::Srr {
+        blocks: srr
+            .blocks
+            .into_iter()
+            .filter(|block| !matches!(block.inner, Some(srr::BlockImpl::SrrStoredFile(_))))
+            .collect(),
+    };
+    assert!(srr.verify_volumes(&dir).unwrap().is_empty());
+    for damaged in names {
+        let copy = damage(&dir, &names, damaged);
+        assert!(
+            matches!(
+                &srr.verify_volumes(copy.path()).unwrap()[..],
+                [srr::Mismatch::Crc { file_name, .. }] if file_name == damaged
+            ),
+            "{damaged}"
+        );
+    }
+
+    let srr = load("store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr");
+    let dir = root().join("store_rr_solid_auth_unicode_new");
+    assert!(srr.verify_volumes(&dir).unwrap().is_empty());
+}
+
+#[test]
+fn verify_files() {
+    let srr = load("store_rr_solid_auth_unicode_new/store_rr_solid_auth.part1.srr");
+    let volumes = [
+        "store_rr_solid_auth.part1.rar",
+        "store_rr_solid_auth.part2.rar",
+        "store_rr_solid_auth.part3.rar",
+    ]
+    .map(|name| std::fs::read(root().join("store_rr_solid_auth_unicode_new").join(name)).unwrap());
+    let input = extract(&volumes);
+    assert!(srr.verify_files(input.path()).unwrap().is_empty());
+
+    let files = srr.archived_files();
+    let file = files
+        .iter()
+        .find(|file| file.file_name == "little_file.txt")
+        .unwrap();
+    let path = input.path().join(&file.file_name);
+    let mut data = std::fs::read(&path).unwrap();
+    data[0] ^= 0xFF;
+    std::fs::write(&path, data).unwrap();
+    assert!(matches!(
+        &srr.verify_files(input.path()).unwrap()[..],
+        [srr::Mismatch::Crc { file_name, .. }] if *file_name == file.file_name
+    ));
+}
@@ -0,0 +1,553 @@
+//! Samples cut from synthetic media files, with their SRS files built the way ReSample
+//! lays them out.
+
+use srr::{rebuild_sample, SampleError, Srs, SrsContainer, SrsFile};
+use std::io::Cursor;
+
+/// Media data that does not repeat itself.
+fn media_data(seed: u32, len: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9E3779B9) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn crc(data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+fn file_record(name: &str, sample: &[u8]) -> Vec<u8> {
+    let mut record = vec![0, 0];
+    for name in ["test", name] {
+        record.extend((name.len() as u16).to_le_bytes());
+        record.extend(name.as_bytes());
+    }
+    record.extend((sample.len() as u64).to_le_bytes());
+    record.extend(crc(sample).to_le_bytes());
+    record
+}
+
+fn track_record(number: u16, data: &[u8], match_offset: u64) -> Vec<u8> {
+    let signature = &data[..data.len().min(256)];
+    let mut record = vec![0, 0];
+    record.extend(number.to_le_bytes());
+    record.extend((data.len() as u32).to_le_bytes());
+    record.extend(match_offset.to_le_bytes());
+    record.extend((signature.len() as u16).to_le_bytes());
+    record.extend(signature);
+    record
+}
+
+fn rebuild(srs: &[u8], media: &[u8]) -> Result<Vec<u8>, SampleError> {
+    let mut out = vec![];
+    rebuild_sample(srs, Cursor::new(media), &mut out)?;
+    Ok(out)
+}
+
+fn chunk(fourcc: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = fourcc.to_vec();
+    chunk.extend((data.len() as u32).to_le_bytes());
+    chunk.extend(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn list(ty: &[u8], kind: &[u8], children: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = kind.to_vec();
+    data.extend(children.concat());
+    let mut list = chunk(ty, &data);
+    list.truncate(8 + data.len());
+    list
+}
+
+/// An AVI with interleaved video and audio frames, the video frames have odd sizes.
+fn avi(
+    frames: &[(&[u8; 4], Vec<u8>)],
+    mut movi: impl FnMut(&[u8; 4], &[u8]) -> Vec<u8>,
+) -> Vec<u8> {
+    let movi = frames
+        .iter()
+        .map(|(fourcc, data)| movi(fourcc, data))
+        .collect::<Vec<_>>();
+    let movi = list(b"LIST", b"movi", &movi);
+    let header = list(b"LIST", b"hdrl", &[chunk(b"avih", &[7; 56])]);
+    list(b"RIFF", b"AVI ", &[header, movi, chunk(b"idx1", &[1; 32])])
+}
+
+#[test]
+fn avi_sample() {
+    let frames = (0..40)
+        .map(|i| {
+            if i % 2 == 0 {
+                (b"00dc", media_data(i, 301 + i as usize * 10))
+            } else {
+                (b"01wb", media_data(i, 200))
+            }
+        })
+        .collect::<Vec<_>>();
+    let media = avi(&frames, |fourcc, data| chunk(fourcc, data));
+    let sample_frames = &frames[10..20];
+    let sample = avi(sample_frames, |fourcc, data| chunk(fourcc, data));
+
+    let track = |fourcc: &[u8]| {
+        let data = sample_frames
+            .iter()
+            .filter(|(frame, _data)| frame.as_slice() == fourcc)
+            .flat_map(|(_frame, data)| data.clone())
+            .collect::<Vec<_>>();
+        // the signature may continue in the next chunk
+        let match_offset = media
+            .windows(100)
+            .position(|window| window == &data[..100])
+            .unwrap() as u64;
+        (data, match_offset)
+    };
+    let (video, video_offset) = track(b"00dc");
+    let (audio, _audio_offset) = track(b"01wb");
+    let mut first = true;
+    let mut srs = avi(sample_frames, |fourcc, data| {
+        let mut stripped = vec![];
+        if std::mem::take(&mut first) {
+            stripped.extend(chunk(b"SRSF", &file_record("sample.avi", &sample)));
+            stripped.extend(chunk(b"SRST", &track_record(0, &video, video_offset)));
+            // an unknown match offset has to be searched for
+            stripped.extend(chunk(b"SRST", &track_record(1, &audio, 0)));
+        }
+        let mut header = chunk(fourcc, data);
+        header.truncate(8);
+        if data.len() % 2 == 1 {
+            header.push(0);
+        }
+        stripped.extend(header);
+        stripped
+    });
+    // the lists keep the sizes of the sample
+    let movi = sample
+        .windows(4)
+        .position(|fourcc| fourcc == b"movi")
+        .unwrap()
+        - 8;
+    for size in [4..8, movi + 4..movi + 8] {
+        srs[size.clone()].copy_from_slice(&sample[size]);
+    }
+
+    let mut out = vec![];
+    let srs = rebuild_sample(&srs, Cursor::new(&media), &mut out).unwrap();
+    assert_eq!(srs.container, SrsContainer::Avi);
+    assert_eq!(srs.file.file_name, "sample.avi");
+    assert_eq!(out, sample);
+}
+
+/// An element that claims to be `size` bytes, stripped elements hold less.
+fn element_sized(id: &[u8], size: usize, data: &[u8]) -> Vec<u8> {
+    let mut element = id.to_vec();
+    element.push(0x01);
+    element.extend(&(size as u64).to_be_bytes()[1..]);
+    element.extend(data);
+    element
+}
+
+fn element(id: &[u8], data: &[u8]) -> Vec<u8> {
+    element_sized(id, data.len(), data)
+}
+
+const SEGMENT: &[u8] = &[0x18, 0x53, 0x80, 0x67];
+const CLUSTER: &[u8] = &[0x1F, 0x43, 0xB6, 0x75];
+
+/// A Matroska file with a video track of simple blocks and a Xiph laced audio track
+/// in block groups. With a ReSample element it is the SRS of that file instead.
+fn mkv(clusters: &[Vec<(u8, Vec<u8>)>], resample: Option<&[u8]>) -> Vec<u8> {
+    let header = element(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x86, 0x81, 0x01]);
+    let tracks = element(&[0x16, 0x54, 0xAE, 0x6B], &[0xAE, 0x80]);
+    let (mut segment, mut stripped_segment) = (tracks.clone(), tracks);
+    for blocks in clusters {
+        let (mut cluster, mut stripped_cluster) = (vec![0xE7, 0x81, 0x00], vec![0xE7, 0x81, 0x00]);
+        for (track, frames) in blocks {
+            let (id, header): (&[u8], &[u8]) = match track {
+                1 => (&[0xA3], &[0x81, 0, 0, 0x80]),
+                // 3 frames of 300, 16 and the remaining bytes
+                _ => (&[0xA1], &[0x82, 0, 0, 0x02, 2, 0xFF, 0x2D, 0x10]),
+            };
+            let data = [header, frames].concat();
+            let mut block = element(id, &data);
+            let mut stripped = element_sized(id, data.len(), header);
+            if *track != 1 {
+                stripped = element_sized(&[0xA0], block.len(), &stripped);
+                block = element(&[0xA0], &block);
+            }
+            cluster.extend(block);
+            stripped_cluster.extend(stripped);
+        }
+        segment.extend(element(CLUSTER, &cluster));
+        stripped_segment.extend(element_sized(CLUSTER, cluster.len(), &stripped_cluster));
+    }
+    match resample {
+        None => [header, element(SEGMENT, &segment)].concat(),
+        Some(resample) => {
+            let srs = [resample, &stripped_segment].concat();
+            [header, element_sized(SEGMENT, segment.len(), &srs)].concat()
+        }
+    }
+}
+
+#[test]
+fn mkv_sample() {
+    let clusters = (0..6)
+        .map(|cluster| {
+            (0..6)
+                .map(|block| {
+                    let seed = cluster * 10 + block;
+                    if block % 3 == 2 {
+                        (2, media_data(seed, 300 + 16 + 32))
+                    } else {
+                        (1, media_data(seed, 1000 + seed as usize))
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let media = mkv(&clusters, None);
+    let sample_clusters = &clusters[2..4];
+    let sample = mkv(sample_clusters, None);
+
+    let mut resample = element(&[0x6A, 0x75], &file_record("sample.mkv", &sample));
+    for number in [1, 2] {
+        let data = sample_clusters
+            .iter()
+            .flatten()
+            .filter(|(track, _frames)| *track == number)
+            .flat_map(|(_track, frames)| frames.clone())
+            .collect::<Vec<_>>();
+        resample.extend(element(
+            &[0x6B, 0x75],
+            &track_record(number as u16, &data, 0),
+        ));
+    }
+    let resample = element(&[0x1F, 0x69, 0x75, 0x76], &resample);
+    let srs = mkv(sample_clusters, Some(&resample));
+
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+}
+
+fn block(marker: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut block = marker.to_vec();
+    block.extend((data.len() as u32 + 8).to_le_bytes());
+    block.extend(data);
+    block
+}
+
+#[test]
+fn mp3_sample() {
+    let id3v2 = b"ID3\x03\0\0\0\0\0\x04tag!".to_vec();
+    let mut id3v1 = b"TAG".to_vec();
+    id3v1.extend([b' '; 125]);
+    let audio = media_data(1, 20000);
+    let sample = [&id3v2[..], &audio, &id3v1].concat();
+    let media = [
+        &b"ID3\x03\0\0\0\0\0\x02xx"[..],
+        &media_data(2, 5000),
+        &audio,
+        &id3v1,
+    ]
+    .concat();
+
+    let srs = [
+        &id3v2[..],
+        &block(b"SRSF", &file_record("sample.mp3", &sample)),
+        &block(b"SRST", &track_record(1, &audio, 0)),
+        &block(b"SRSP", &[0; 12]),
+        &id3v1,
+    ]
+    .concat();
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+
+    // the signature is not in another file
+    let other = media_data(3, 30000);
+    assert!(matches!(
+        rebuild(&srs, &other),
+        Err(SampleError::TrackNotFound { track_number: 1 })
+    ));
+    // or only the start of the track is
+    let truncated = &media[..media.len() - 1000];
+    assert!(matches!(
+        rebuild(&srs, truncated),
+        Err(SampleError::TrackTooShort { track_number: 1 })
+    ));
+}
+
+#[test]
+fn stream_sample() {
+    let sample = media_data(4, 3_000_000);
+    let media = [&media_data(5, 1_500_000)[..], &sample, &media_data(6, 1000)].concat();
+    let mut srs = [
+        &b"STRM\x08\0\0\0"[..],
+        &block(b"SRSF", &file_record("sample.vob", &sample)),
+        &block(b"SRST", &track_record(1, &sample, 1_500_000)),
+    ]
+    .concat();
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+    // without a match offset the signature is searched past the first buffer
+    let match_offset = srs.len() - 256 - 2 - 8;
+    srs[match_offset..match_offset + 8].fill(0);
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+
+    // the wrong CRC
+    srs[8 + 8 + 2 + 2 + 4 + 2 + 10 + 8] ^= 1;
+    assert!(matches!(
+        rebuild(&srs, &media),
+        Err(SampleError::Mismatch {
+            expected_size: 3_000_000,
+            actual_size: 3_000_000,
+            ..
+        })
+    ));
+}
+
+fn metadata(ty: u8, data: &[u8]) -> Vec<u8> {
+    let mut block = vec![ty];
+    block.extend(&(data.len() as u32).to_be_bytes()[1..]);
+    block.extend(data);
+    block
+}
+
+#[test]
+fn flac_sample() {
+    let frames = media_data(7, 10000);
+    let stream_info = metadata(0x00, &[1; 34]);
+    let comment = metadata(0x84, b"comment");
+    let sample = [&b"fLaC"[..], &stream_info, &comment, &frames].concat();
+    let media = [
+        &b"fLaC"[..],
+        &stream_info,
+        &comment,
+        &media_data(8, 3000),
+        &frames,
+        &media_data(9, 3000),
+    ]
+    .concat();
+    let srs = [
+        &b"fLaC"[..],
+        &stream_info,
+        &metadata(0x04, b"comment"),
+        &metadata(0x73, &file_record("sample.flac", &sample)),
+        &metadata(0x80 | 0x74, &track_record(1, &frames, 0)),
+    ]
+    .concat();
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+}
+
+fn atom(ty: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut atom = (data.len() as u32 + 8).to_be_bytes().to_vec();
+    atom.extend(ty);
+    atom.extend(data);
+    atom
+}
+
+/// An MP4 with the chunks of two tracks in one mdat and two samples per chunk. Track 2
+/// has 64 bit chunk offsets.
+fn mp4(chunks: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let ftyp = atom(b"ftyp", b"isom\0\0\x02\0");
+    let mdat = atom(
+        b"mdat",
+        &chunks
+            .iter()
+            .flat_map(|(_track, data)| data.clone())
+            .collect::<Vec<_>>(),
+    );
+    let mut offset = (ftyp.len() + 8) as u64;
+    let mut traks = vec![];
+    for track_id in [1, 2] {
+        let mut stsz = vec![0; 8];
+        let mut stco = vec![];
+        let mut count = 0u32;
+        for (track, data) in chunks {
+            if *track == track_id {
+                let half = data.len() as u32 / 2;
+                stsz.extend(half.to_be_bytes());
+                stsz.extend((data.len() as u32 - half).to_be_bytes());
+                if track_id == 1 {
+                    stco.extend((offset as u32).to_be_bytes());
+                } else {
+                    stco.extend(offset.to_be_bytes());
+                }
+                count += 1;
+            }
+            offset += data.len() as u64;
+        }
+        offset -= chunks
+            .iter()
+            .map(|(_track, data)| data.len() as u64)
+            .sum::<u64>();
+        stsz[4..8].copy_from_slice(&(count * 2).to_be_bytes());
+        let stsz = [vec![0; 4], stsz].concat();
+        let stsc = [
+            &[0; 4][..],
+            &1u32.to_be_bytes(),
+            &1u32.to_be_bytes(),
+            &2u32.to_be_bytes(),
+            &1u32.to_be_bytes(),
+        ]
+        .concat();
+        let stco = [&[0; 4][..], &count.to_be_bytes(), &stco].concat();
+        let offsets: &[u8] = if track_id == 1 { b"stco" } else { b"co64" };
+        let stbl = [
+            atom(b"stsd", &[0; 8]),
+            atom(b"stsz", &stsz),
+            atom(b"stsc", &stsc),
+            atom(offsets, &stco),
+        ]
+        .concat();
+        let minf = atom(b"minf", &atom(b"stbl", &stbl));
+        let mdia = atom(b"mdia", &[atom(b"mdhd", &[0; 24]), minf].concat());
+        let mut tkhd = vec![0; 84];
+        tkhd[12..16].copy_from_slice(&track_id.to_be_bytes());
+        traks.push(atom(b"trak", &[atom(b"tkhd", &tkhd), mdia].concat()));
+    }
+    let moov = atom(
+        b"moov",
+        &[atom(b"mvhd", &[0; 100]), traks.concat()].concat(),
+    );
+    [ftyp, mdat, moov].concat()
+}
+
+#[test]
+fn create_avi() {
+    let frames = (0..40)
+        .map(|i| {
+            let fourcc = if i % 3 == 0 { b"01wb" } else { b"00dc" };
+            (fourcc, media_data(i, 51 + i as usize * 20))
+        })
+        .collect::<Vec<_>>();
+    let media = avi(&frames, |fourcc, data| chunk(fourcc, data));
+    let sample = avi(&frames[20..30], |fourcc, data| chunk(fourcc, data));
+    let srs = Srs::create("srr test", "sample.avi", &sample).unwrap();
+    assert!(srs.len() < 1000);
+
+    let parsed = Srs::parse(&srs).unwrap();
+    assert_eq!(parsed.container, SrsContainer::Avi);
+    assert_eq!(parsed.file.app_name, "srr test");
+    assert_eq!(parsed.file.size, sample.len() as u64);
+    assert_eq!(parsed.file.flags, SrsFile::SIMPLE_BLOCK_FIX);
+    let tracks = parsed
+        .tracks
+        .iter()
+        .map(|track| (track.track_number, track.signature.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(tracks, [(0, 256), (1, 256)]);
+    // the records start the movi list, like ReSample puts them
+    let movi = srs.windows(4).position(|fourcc| fourcc == b"movi").unwrap();
+    assert_eq!(&srs[movi + 4..movi + 8], b"SRSF");
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+}
+
+#[test]
+fn create_mkv() {
+    let clusters = (0..5)
+        .map(|cluster| {
+            (0..4)
+                .map(|block| {
+                    let seed = cluster * 10 + block;
+                    if block == 3 {
+                        (2, media_data(seed, 400))
+                    } else {
+                        (1, media_data(seed, 700 + seed as usize))
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let media = mkv(&clusters, None);
+    let sample = mkv(&clusters[1..3], None);
+    let srs = Srs::create("srr test", "sample.mkv", &sample).unwrap();
+
+    let parsed = Srs::parse(&srs).unwrap();
+    assert_eq!(parsed.container, SrsContainer::Mkv);
+    assert_eq!(parsed.tracks.len(), 2);
+    assert_eq!(parsed.tracks[1].data_length, 800);
+    // the ReSample element starts the Segment
+    assert_eq!(&srs[28..32], [0x1F, 0x69, 0x75, 0x76]);
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+}
+
+#[test]
+fn create_mp4() {
+    let chunks = (0..30)
+        .map(|i| (1 + i % 2, media_data(i, 300 + i as usize * 2)))
+        .collect::<Vec<_>>();
+    let media = mp4(&chunks);
+    let sample = mp4(&chunks[10..20]);
+    let srs = Srs::create("srr test", "sample.mp4", &sample).unwrap();
+
+    let parsed = Srs::parse(&srs).unwrap();
+    assert_eq!(parsed.container, SrsContainer::Mp4);
+    let tracks = parsed
+        .tracks
+        .iter()
+        .map(|track| (track.track_number, track.data_length))
+        .collect::<Vec<_>>();
+    assert_eq!(tracks, [(1, 1640), (2, 1650)]);
+    assert_eq!(rebuild(&srs, &media).unwrap(), sample);
+
+    // the chunk tables have to cover the mdat atom
+    let mut gap = sample.clone();
+    let mdat = gap.windows(4).position(|ty| ty == b"mdat").unwrap() - 4;
+    let size = u32::from_be_bytes(gap[mdat..mdat + 4].try_into().unwrap());
+    gap[mdat..mdat + 4].copy_from_slice(&(size + 4).to_be_bytes());
+    gap.splice(mdat + size as usize..mdat + size as usize, [0; 4]);
+    assert!(Srs::create("srr test", "sample.mp4", &gap).is_err());
+}
+
+#[test]
+fn oversized_mp4_atoms() {
+    let chunks = (0..4)
+        .map(|i| (1 + i % 2, media_data(i, 300)))
+        .collect::<Vec<_>>();
+    let media = mp4(&chunks);
+    let srs = Srs::create("srr test", "sample.mp4", &media).unwrap();
+    // a 64 bit mdat size that runs past the end of any file
+    let mdat = srs.windows(4).position(|ty| ty == b"mdat").unwrap() - 4;
+    for size in [u64::MAX, i64::MAX as u64, i64::MAX as u64 + 2] {
+        let mut oversized = srs[..mdat].to_vec();
+        oversized.extend(1u32.to_be_bytes());
+        oversized.extend(b"mdat");
+        oversized.extend(size.to_be_bytes());
+        oversized.extend(&srs[mdat + 8..]);
+        assert!(matches!(
+            rebuild(&oversized, &media),
+            Err(SampleError::Srs(_))
+        ));
+    }
+}
+
+#[test]
+fn create_unsupported() {
+    assert!(matches!(
+        Srs::create("srr test", "sample.mp3", &media_data(1, 1000)),
+        Err(SampleError::Srs(srr::SrsError::UnknownContainer))
+    ));
+}
+
+#[test]
+fn unsupported_samples() {
+    let mut srs = vec![
+        0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE,
+        0x6C,
+    ];
+    let file = file_record("sample.wmv", b"");
+    srs.extend(b"SRSFSRSFSRSFSRSF");
+    srs.extend((file.len() as u64 + 24).to_le_bytes());
+    srs.extend(file);
+    assert!(matches!(
+        rebuild(&srs, b""),
+        Err(SampleError::Unsupported(_))
+    ));
+}
@@ -0,0 +1,202 @@
+use srr::{ParseOptions, Srr};
+
+fn load(file: &str) -> (Vec<u8>, Srr) {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let input = std::fs::read(root.join(file)).unwrap();
+    let (_rest, srr) = Srr::new(&input).unwrap();
+    (input, srr)
+}
+
+fn volume_names(srr: &Srr) -> Vec<&str> {
+    srr.rar_volumes()
+        .into_iter()
+        .map(|(file, _blocks)| file.file_name.as_str())
+        .collect()
+}
+
+#[test]
+fn sets_in_folders() {
+    let (_input, srr) = load(
+        "pyrescene_test_files/other/007.Die.Another.Day.2002.iNTERNAL.DVDRip.XviD-iNCiTE.2CD_paths_stored.srr",
+    );
+    let sets = srr.rar_sets();
+    assert_eq!(sets.len(), 2);
+    for (set, cd) in sets.iter().zip(["cd1", "cd2"]) {
+        let names = volume_names(set);
+        assert_eq!(names.len(), 49);
+        assert_eq!(
+            names[0],
+            format!("{}/incite-die.another.day.xvid.{cd}.rar", cd.to_uppercase())
+        );
+        assert_eq!(set.app_name(), srr.app_name());
+        let stored = set
+            .stored_files()
+            .map(|(file, _data)| file.file_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stored,
+            [format!(
+                "{}/incite-die.another.day.xvid.{cd}.sfv",
+                cd.to_uppercase()
+            )]
+        );
+        let archived = set.archived_files();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(
+            archived[0].file_name,
+            format!("incite-die.another.day.xvid.{cd}.avi")
+        );
+    }
+}
+
+/// Names of the files the OSO hash blocks are for.
+fn oso_hash_names(srr: &Srr) -> Vec<String> {
+    srr.blocks
+        .iter()
+        .filter(|block| block.header.ty == srr::BlockType::SrrOsoHash)
+        .map(|block| String::from_utf8_lossy(&block.raw[25..]).into_owned())
+        .collect()
+}
+
+#[test]
+fn sets_with_oso_hashes() {
+    let (_input, srr) =
+        load("Harry.Potter.And.The.Chamber.Of.Secrets.2002.DVDRip.XViD-iNTERNAL-TDF.srr");
+    assert_eq!(
+        oso_hash_names(&srr),
+        ["tdf-hpatcos.avi", "tdf-hpatcos2.avi"]
+    );
+    let sets = srr.rar_sets();
+    assert_eq!(sets.len(), 2);
+    for (set, (cd, stem)) in sets
+        .iter()
+        .zip([("CD1", "tdf-hpatcos"), ("CD2", "tdf-hpatcos2")])
+    {
+        let names = volume_names(set);
+        assert_eq!(names.len(), 49);
+        assert_eq!(names[0], format!("{cd}/{stem}.rar"));
+        assert_eq!(names[48], format!("{cd}/{stem}.r47"));
+        let stored = set
+            .stored_files()
+            .map(|(file, _data)| file.file_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(stored, [format!("{cd}/{stem}.sfv")]);
+        assert_eq!(oso_hash_names(set), [format!("{stem}.avi")]);
+        let archived = set.archived_files();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].file_name, format!("{stem}.avi"));
+        // the set is a valid srr on its own
+        assert_eq!(
+            Srr::new(&set.to_bytes()).unwrap().1.blocks.len(),
+            set.blocks.len()
+        );
+    }
+}
+
+#[test]
+fn single_set() {
+    let (input, srr) =
+        load("pyrescene_test_files/other/24.S08E03.720p.BluRay.X264-WASABi_srrpathhack.srr");
+    let sets = srr.rar_sets();
+    assert_eq!(sets.len(), 1);
+    assert_eq!(volume_names(&sets[0]), volume_names(&srr));
+    // the files in the root directory stay with the volumes, the sample doesn't
+    let sample = srr
+        .blocks
+        .iter()
+        .filter(|block| {
+            matches!(&block.inner, Some(srr::BlockImpl::SrrStoredFile(file))
+                if file.file_name.starts_with("Sample/"))
+        })
+        .map(|block| block.raw.len())
+        .sum::<usize>();
+    assert_eq!(sets[0].to_bytes().len(), input.len() - sample);
+
+    let (_input, srr) = load("Thickos.scen0r.zine.Issue.01-THiCK0S.srr");
+    assert!(srr.rar_sets().is_empty());
+}
+
+#[test]
+fn nested_srrs() {
+    let (_input, mut srr) = load("Thickos.scen0r.zine.Issue.01-THiCK0S.srr");
+    let (little, _srr) = load("pyrescene_test_files/store_little/store_little.srr");
+    let (_input, mut subs) =
+        load("pyrescene_test_files/store_split_folder_old_srrsfv_windows/store_split_folder.srr");
+    subs.add_stored_file("Extras/little.srr", &little).unwrap();
+    srr.add_stored_file("Subs/subs.SRR", &subs.to_bytes())
+        .unwrap();
+    srr.add_stored_file("broken.srr", b"not an srr").unwrap();
+
+    let nested = srr.nested_srrs(&ParseOptions::default());
+    let names = nested
+        .iter()
+        .map(|(name, _srr)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["Subs/subs.SRR", "Subs/Extras/little.srr", "broken.srr"]
+    );
+    let (_name, subs) = &nested[0];
+    assert_eq!(volume_names(subs.as_ref().unwrap()).len(), 3);
+    let (_name, little) = &nested[1];
+    assert_eq!(volume_names(little.as_ref().unwrap()), ["store_little.rar"]);
+    assert!(nested[2].1.is_err());
+
+    // limits apply to the nested srrs too
+    let options = ParseOptions {
+        max_stored_file_size: 300,
+        ..Default::default()
+    };
+    let nested = srr.nested_srrs(&options);
+    assert!(nested[0].1.is_err());
+}
+
+/// store_little.srr with `depth` levels of srrs stored in it, each one of them
+/// storing the next.
+fn nest(depth: usize) -> srr::Srr {
+    let (little, srr) = load("pyrescene_test_files/store_little/store_little.srr");
+    let mut inner = little.clone();
+    for _ in 0..depth {
+        let (_rest, mut outer) = srr::Srr::new(&little).unwrap();
+        outer.add_stored_file("inner.srr", &inner).unwrap();
+        inner = outer.to_bytes();
+    }
+    let (_rest, mut srr) = srr::Srr::new(&srr.to_bytes()).unwrap();
+    srr.add_stored_file("inner.srr", &inner).unwrap();
+    srr
+}
+
+#[test]
+fn deeply_nested_srrs() {
+    let nested = nest(40).nested_srrs(&ParseOptions::default());
+    assert_eq!(nested.len(), 17);
+    assert!(nested[..16].iter().all(|(_name, srr)| srr.is_ok()));
+    assert_eq!(
+        nested[16].1.as_ref().unwrap_err(),
+        &srr::ParseError::LimitExceeded {
+            limit: srr::Limit::NestingDepth,
+            max: 16,
+            actual: 17
+        }
+    );
+
+    // the limits are shared by all levels, though each one would be within them
+    let (little, _srr) = load("pyrescene_test_files/store_little/store_little.srr");
+    let blocks = srr::Srr::new(&little).unwrap().1.blocks.len() + 1;
+    let options = ParseOptions {
+        max_blocks: 3 * blocks,
+        ..Default::default()
+    };
+    let srr = nest(10);
+    let (_name, deepest) = &srr.nested_srrs(&ParseOptions::default())[9];
+    assert!(srr::Srr::parse_with_options(&deepest.as_ref().unwrap().to_bytes(), &options).is_ok());
+    let nested = srr.nested_srrs(&options);
+    assert!(nested[..3].iter().all(|(_name, srr)| srr.is_ok()));
+    assert!(matches!(
+        nested[3].1,
+        Err(srr::ParseError::LimitExceeded {
+            limit: srr::Limit::Blocks,
+            ..
+        })
+    ));
+}
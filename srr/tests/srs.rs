@@ -0,0 +1,258 @@
+use srr::{Srs, SrsContainer, SrsError, SrsTrack};
+
+fn load(file: &str) -> srr::Srr {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let input = std::fs::read(root.join(file)).unwrap();
+    let (_rest, srr) = srr::Srr::new(&input).unwrap();
+    srr
+}
+
+fn sample(file: &str, name: &str) -> (Vec<u8>, Srs) {
+    let srr = load(file);
+    let data = srr.stored_file(name).unwrap().to_vec();
+    let srs = Srs::parse(&data).unwrap();
+    (data, srs)
+}
+
+#[test]
+fn stream() {
+    let (_data, srs) = sample(
+        "Britney_Spears-Stronger-DVDRip-IVTC-SVCD-cHiPs-mVz.srr",
+        "britney_spears-stronger-dvdrip-ivtc-svcd-chips-mvz.srs",
+    );
+    assert_eq!(srs.container, SrsContainer::Stream);
+    assert_eq!(srs.file.app_name, "pyReSample 0.7");
+    assert_eq!(
+        srs.file.file_name,
+        "britney_spears-stronger-dvdrip-ivtc-svcd-chips-mvz.m2v"
+    );
+    assert_eq!(srs.file.size, 73080504);
+    assert_eq!(srs.file.crc, 0xC9460A9F);
+    assert_eq!(srs.tracks.len(), 1);
+    assert_eq!(srs.tracks[0].data_length, 73080504);
+    assert_eq!(srs.tracks[0].signature.len(), 256);
+    assert_eq!(&srs.tracks[0].signature[..4], [0x00, 0x00, 0x01, 0xBA]);
+}
+
+#[test]
+fn mp3() {
+    let srr = load("DJ_Melvin-L.O.I.S.-CDM-2002-TGX.srr");
+    let samples = srr
+        .samples()
+        .map(|(file, srs)| (file.file_name.clone(), srs.unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(samples.len(), 4);
+    let (name, srs) = &samples[0];
+    assert_eq!(name, "01-dj_melvin-l.o.i.s.__radio_edit_club_mix-tgx.srs");
+    assert_eq!(srs.container, SrsContainer::Mp3);
+    assert_eq!(
+        srs.file.file_name,
+        "01-dj_melvin-l.o.i.s.__radio_edit_club_mix-tgx.mp3"
+    );
+    assert_eq!(srs.file.size, 5039513);
+    assert_eq!(srs.file.crc, 0x40AE0AF6);
+    assert_eq!(srs.tracks.len(), 1);
+    assert_eq!(srs.tracks[0].track_number, 1);
+    assert_eq!(srs.tracks[0].data_length, 5039333);
+}
+
+#[test]
+fn mkv() {
+    let (_data, srs) = sample(
+        "Bobs.Burgers.S02E08.720p.HDTV.X264-DIMENSION.srr",
+        "Sample/boobs.boogers.208.720p-dimension.sample.srs",
+    );
+    assert_eq!(srs.container, SrsContainer::Mkv);
+    assert_eq!(
+        srs.file.file_name,
+        "boobs.boogers.208.720p-dimension.sample.mkv"
+    );
+    assert_eq!(srs.file.size, 11480536);
+    assert_eq!(srs.file.crc, 0xA8C930C1);
+    let tracks = srs
+        .tracks
+        .iter()
+        .map(|track| (track.track_number, track.data_length, track.match_offset))
+        .collect::<Vec<_>>();
+    assert_eq!(tracks, [(1, 7747495, 60816294), (2, 3713024, 60805142)]);
+    assert_eq!(srs.track(2).unwrap().data_length, 3713024);
+    assert!(srs.track(3).is_none());
+}
+
+#[test]
+fn avi() {
+    let (_data, srs) = sample(
+        "Harry.Potter.And.The.Chamber.Of.Secrets.2002.DVDRip.XViD-iNTERNAL-TDF.srr",
+        "Sample/tdf-hpatcos-sample.srs",
+    );
+    assert_eq!(srs.container, SrsContainer::Avi);
+    assert_eq!(srs.file.app_name, "ReScene .NET 1.3.3 GUI (beta)");
+    assert_eq!(srs.file.file_name, "tdf-hpatcos-sample.avi");
+    assert_eq!(srs.file.size, 11327488);
+    assert_eq!(srs.file.crc, 0xB22084EB);
+    let tracks = srs
+        .tracks
+        .iter()
+        .map(|track| (track.track_number, track.data_length, track.match_offset))
+        .collect::<Vec<_>>();
+    assert_eq!(tracks, [(0, 7940819, 99796232), (1, 3292800, 99735008)]);
+}
+
+#[test]
+fn truncated_samples() {
+    let (data, srs) = sample(
+        "Bobs.Burgers.S02E08.720p.HDTV.X264-DIMENSION.srr",
+        "Sample/boobs.boogers.208.720p-dimension.sample.srs",
+    );
+    for len in 0..data.len() {
+        if let Ok(parsed) = Srs::parse(&data[..len]) {
+            assert_eq!(parsed, srs, "{len}");
+        }
+    }
+    assert_eq!(Srs::parse(&data[..100]), Err(SrsError::Incomplete));
+}
+
+fn file_record() -> Vec<u8> {
+    let mut record = vec![0, 0];
+    for name in ["pyReSample 0.7", "sample.ext"] {
+        record.extend((name.len() as u16).to_le_bytes());
+        record.extend(name.as_bytes());
+    }
+    record.extend(1234u64.to_le_bytes());
+    record.extend(0xDEADBEEFu32.to_le_bytes());
+    record
+}
+
+/// A track with 64 bit sizes, as written for tracks over 4 GiB.
+fn track_record() -> Vec<u8> {
+    let flags = SrsTrack::BIG_FILE | SrsTrack::BIG_TRACK_NUMBER;
+    let mut record = flags.to_le_bytes().to_vec();
+    record.extend(70000u32.to_le_bytes());
+    record.extend(0x1_0000_0000u64.to_le_bytes());
+    record.extend(42u64.to_le_bytes());
+    record.extend(3u16.to_le_bytes());
+    record.extend(b"sig");
+    record
+}
+
+fn assert_synthetic(input: &[u8], container: SrsContainer) {
+    let srs = Srs::parse(input).unwrap();
+    assert_eq!(srs.container, container);
+    assert_eq!(srs.file.app_name, "pyReSample 0.7");
+    assert_eq!(srs.file.file_name, "sample.ext");
+    assert_eq!(srs.file.size, 1234);
+    assert_eq!(srs.file.crc, 0xDEADBEEF);
+    assert_eq!(
+        srs.tracks,
+        [SrsTrack {
+            flags: SrsTrack::BIG_FILE | SrsTrack::BIG_TRACK_NUMBER,
+            track_number: 70000,
+            data_length: 0x1_0000_0000,
+            match_offset: 42,
+            signature: b"sig".to_vec(),
+        }]
+    );
+}
+
+#[test]
+fn nested_avi_lists() {
+    let chunk = |input: &mut Vec<u8>, fourcc: &[u8], data: &[u8]| {
+        input.extend(fourcc);
+        input.extend((data.len() as u32).to_le_bytes());
+        input.extend(data);
+        if data.len() % 2 == 1 {
+            input.push(0);
+        }
+    };
+    let mut records = vec![];
+    chunk(&mut records, b"SRSF", &file_record());
+    chunk(&mut records, b"SRST", &track_record());
+
+    // far deeper than the stack allows for recursion
+    let depth = 200_000;
+    let mut input = vec![];
+    for level in 0..=depth {
+        let size = 4 + 12 * (depth - level) + records.len();
+        input.extend(if level == 0 { b"RIFF" } else { b"LIST" });
+        input.extend((size as u32).to_le_bytes());
+        input.extend(if level == 0 { b"AVI " } else { b"hdrl" });
+    }
+    input.extend(records);
+    assert_synthetic(&input, SrsContainer::Avi);
+}
+
+#[test]
+fn mp4() {
+    let mut input = vec![];
+    let atom = |input: &mut Vec<u8>, ty: &[u8], data: &[u8]| {
+        input.extend((data.len() as u32 + 8).to_be_bytes());
+        input.extend(ty);
+        input.extend(data);
+    };
+    atom(&mut input, b"ftyp", b"isom\0\0\x02\0");
+    atom(&mut input, b"SRSF", &file_record());
+    atom(&mut input, b"SRST", &track_record());
+    // only the header of mdat is kept
+    input.extend(1000u32.to_be_bytes());
+    input.extend(b"mdat");
+    atom(&mut input, b"moov", &[0; 16]);
+    assert_synthetic(&input, SrsContainer::Mp4);
+}
+
+#[test]
+fn wmv() {
+    let object = |input: &mut Vec<u8>, guid: &[u8], data: &[u8]| {
+        input.extend(guid);
+        input.extend((data.len() as u64 + 24).to_le_bytes());
+        input.extend(data);
+    };
+    let mut header = 4u32.to_le_bytes().to_vec();
+    header.extend([1, 2]);
+    object(&mut header, &[0x11; 16], &[0; 10]);
+    object(&mut header, b"SRSFSRSFSRSFSRSF", &file_record());
+    object(&mut header, b"SRSTSRSTSRSTSRST", &track_record());
+    object(&mut header, b"PADDINGBYTESDATA", &[0; 8]);
+    let mut input = vec![];
+    object(
+        &mut input,
+        &[
+            0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62,
+            0xCE, 0x6C,
+        ],
+        &header,
+    );
+    assert_synthetic(&input, SrsContainer::Wmv);
+}
+
+#[test]
+fn flac() {
+    let block = |input: &mut Vec<u8>, ty: u8, data: &[u8]| {
+        input.push(ty);
+        input.extend(&(data.len() as u32).to_be_bytes()[1..]);
+        input.extend(data);
+    };
+    let mut input = b"ID3\x04\0\0\0\0\0\x05tag..".to_vec();
+    input.extend(b"fLaC");
+    block(&mut input, 0x00, &[0; 34]);
+    block(&mut input, 0x73, &file_record());
+    block(&mut input, 0x80 | 0x74, &track_record());
+    input.extend([0xFF, 0xF8, 0x69, 0x08]);
+    assert_synthetic(&input, SrsContainer::Flac);
+}
+
+#[test]
+fn invalid_samples() {
+    assert_eq!(Srs::parse(b""), Err(SrsError::UnknownContainer));
+    assert_eq!(
+        Srs::parse(b"Rar!\x1a\x07\0"),
+        Err(SrsError::UnknownContainer)
+    );
+    assert_eq!(
+        Srs::parse(b"STRM\x08\0\0\0SRSP\x08\0\0\0"),
+        Err(SrsError::MissingFile)
+    );
+    assert_eq!(
+        Srs::parse(b"STRM\x08\0\0\0SRSF\x04\0\0\0"),
+        Err(SrsError::Invalid { offset: 8 })
+    );
+}
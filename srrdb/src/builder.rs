@@ -0,0 +1,127 @@
+use crate::{Client, Error};
+use std::time::Duration;
+
+/// Configures a [`Client`], e.g. to use a mirror or a test server:
+///
+/// ```
+/// let client = srrdb::Client::builder()
+///     .api_url("http://127.0.0.1:8080/v1/".parse().unwrap())
+///     .timeout(std::time::Duration::from_secs(10))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    api_url: reqwest::Url,
+    site_url: reqwest::Url,
+    client: Option<reqwest::Client>,
+    user_agent: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    session: Option<String>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            api_url: reqwest::Url::parse("https://api.srrdb.com/v1/").unwrap(),
+            site_url: reqwest::Url::parse("https://www.srrdb.com/").unwrap(),
+            client: None,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_owned(),
+            timeout: None,
+            connect_timeout: None,
+            session: None,
+        }
+    }
+
+    /// Where the `details`, `search`, `imdb` and `nfo` endpoints are, `https://api.srrdb.com/v1/` by default.
+    pub fn api_url(mut self, url: reqwest::Url) -> Self {
+        self.api_url = directory(url);
+        self
+    }
+
+    /// Where files and srrs are downloaded from, `https://www.srrdb.com/` by default.
+    pub fn site_url(mut self, url: reqwest::Url) -> Self {
+        self.site_url = directory(url);
+        self
+    }
+
+    /// Sends the requests with this client. The user agent and timeouts are then
+    /// the ones it was built with, except for logging in.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        user_agent.clone_into(&mut self.user_agent);
+        self
+    }
+
+    /// The longest a request may take, from connecting until the body is read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Starts logged in, with the `Cookie` header of a session, e.g. `uid=1; hash=2`.
+    pub fn session_cookie(mut self, cookie: &str) -> Self {
+        self.session = Some(cookie.to_owned());
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let configured = || {
+            let mut builder = reqwest::Client::builder().user_agent(&self.user_agent);
+            if let Some(timeout) = self.timeout {
+                builder = builder.timeout(timeout);
+            }
+            if let Some(timeout) = self.connect_timeout {
+                builder = builder.connect_timeout(timeout);
+            }
+            builder
+        };
+        let session_client = configured()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let inner = match self.client {
+            Some(client) => client,
+            None => configured().build()?,
+        };
+        // the paths are all valid relative urls
+        let join = |base: &reqwest::Url, path| base.join(path).unwrap();
+        Ok(Client {
+            inner,
+            details_url: join(&self.api_url, "details/"),
+            download_url: join(&self.site_url, "download/file/"),
+            search_url: join(&self.api_url, "search/"),
+            srr_url: join(&self.site_url, "download/srr/"),
+            imdb_url: join(&self.api_url, "imdb/"),
+            nfo_url: join(&self.api_url, "nfo/"),
+            login_url: join(&self.site_url, "account/login"),
+            upload_url: join(&self.site_url, "upload"),
+            store_url: join(&self.site_url, "release/add/"),
+            session_client,
+            session: std::sync::Arc::new(std::sync::Mutex::new(self.session)),
+        })
+    }
+}
+
+/// Makes relative urls join onto the whole path: `/v1` would join as `/`.
+fn directory(mut url: reqwest::Url) -> reqwest::Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
@@ -0,0 +1,223 @@
+use crate::{DetailsRequest, DetailsResponse, Error, FileRequest};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::path::{Path, PathBuf};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tower_service::Service;
+
+/// Keeps details and downloaded files in a directory, for a service of
+/// [`DetailsRequest`]s and [`FileRequest`]s like [`crate::Client`].
+///
+/// Details are fetched again once they are older than the TTL, but if that fails
+/// with a transient error the stale copy is used. Files are stored by release, CRC
+/// and name and checked against the CRC of their [`crate::FileDetails`], so they
+/// never expire. Once populated the cache works without a connection.
+#[derive(Debug, Clone)]
+pub struct CacheLayer {
+    directory: PathBuf,
+    details_ttl: Duration,
+}
+
+impl CacheLayer {
+    /// A cache in `directory`, which is created when needed. Details are kept for a
+    /// day.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+            details_ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    pub fn details_ttl(mut self, ttl: Duration) -> Self {
+        self.details_ttl = ttl;
+        self
+    }
+}
+
+impl<S> tower_layer::Layer<S> for CacheLayer {
+    type Service = Cache<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Cache {
+            inner,
+            directory: self.directory.clone(),
+            details_ttl: self.details_ttl,
+        }
+    }
+}
+
+/// See [`CacheLayer`].
+#[derive(Debug, Clone)]
+pub struct Cache<S> {
+    inner: S,
+    directory: PathBuf,
+    details_ttl: Duration,
+}
+
+impl<S> Cache<S> {
+    fn details_path(&self, release_name: &str) -> PathBuf {
+        self.directory
+            .join("details")
+            .join(format!("{}.json", file_name(release_name)))
+    }
+
+    fn file_path(&self, request: &FileRequest) -> PathBuf {
+        let name = format!(
+            "{:08x}-{}",
+            request.details.crc,
+            file_name(&request.details.name)
+        );
+        self.directory
+            .join("files")
+            .join(file_name(request.base))
+            .join(name)
+    }
+}
+
+/// `name` as a single path component: `%XX` for anything but ASCII letters, digits,
+/// `-`, `_` and dots after the first character.
+fn file_name(name: &str) -> String {
+    name.bytes()
+        .enumerate()
+        .map(|(index, byte)| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => (byte as char).to_string(),
+            b'.' if index > 0 => ".".to_owned(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The contents of a cached file, `None` if there is none or it can't be read, which
+/// only means fetching it again.
+async fn read(path: &Path) -> Option<Vec<u8>> {
+    tokio::fs::read(path).await.ok()
+}
+
+/// Writes through a temporary file, so readers never see half of it.
+async fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    tokio::fs::write(&partial, data).await?;
+    tokio::fs::rename(&partial, path).await
+}
+
+/// Whether the file at `path` was written less than `ttl` ago.
+async fn is_fresh(path: &Path, ttl: Duration) -> bool {
+    let modified = tokio::fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified());
+    modified.is_ok_and(|modified| {
+        SystemTime::now()
+            .duration_since(modified)
+            .is_ok_and(|age| age < ttl)
+    })
+}
+
+/// Calls `service` once it is ready.
+fn oneshot<'a, S, Req>(mut service: S, req: Req) -> BoxFuture<'a, Result<S::Response, Error>>
+where
+    S: Service<Req, Error = Error> + Send + 'a,
+    S::Future: Send,
+    Req: Send + 'a,
+{
+    async move {
+        futures::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+        service.call(req).await
+    }
+    .boxed()
+}
+
+impl<'a, S> Service<DetailsRequest<'a>> for Cache<S>
+where
+    S: Service<DetailsRequest<'a>, Response = DetailsResponse, Error = Error> + Clone + Send + 'a,
+    S::Future: Send,
+{
+    type Response = DetailsResponse;
+    type Error = Error;
+    type Future = BoxFuture<'a, Result<Self::Response, Self::Error>>;
+
+    /// Always ready, the inner service is only asked when the cache can't answer.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: DetailsRequest<'a>) -> Self::Future {
+        let path = self.details_path(&req.release_name);
+        let ttl = self.details_ttl;
+        let fetched = oneshot(self.inner.clone(), req);
+        async move {
+            // an unreadable copy is as good as none
+            let cached = read(&path)
+                .await
+                .and_then(|data| serde_json::from_slice::<DetailsResponse>(&data).ok());
+            let stale = match cached {
+                Some(details) if is_fresh(&path, ttl).await => return Ok(details),
+                stale => stale,
+            };
+            match (fetched.await, stale) {
+                (Ok(details), _) => {
+                    let data = serde_json::to_vec(&details).unwrap();
+                    // the details were fetched, not keeping them only costs a request
+                    let _ = write(&path, &data).await;
+                    Ok(details)
+                }
+                (Err(error), Some(stale)) if error.is_transient() => Ok(stale),
+                (Err(error), _) => Err(error),
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<'a, S> Service<FileRequest<'a>> for Cache<S>
+where
+    S: Service<FileRequest<'a>, Response = Vec<u8>, Error = Error> + Clone + Send + 'a,
+    S::Future: Send,
+{
+    type Response = Vec<u8>;
+    type Error = Error;
+    type Future = BoxFuture<'a, Result<Self::Response, Self::Error>>;
+
+    /// Always ready, the inner service is only asked when the cache can't answer.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: FileRequest<'a>) -> Self::Future {
+        let path = self.file_path(&req);
+        let crc = req.details.crc;
+        let fetched = oneshot(self.inner.clone(), req);
+        async move {
+            if let Some(data) = read(&path).await {
+                if crc32fast::hash(&data) == crc {
+                    return Ok(data);
+                }
+            }
+            let data = fetched.await?;
+            // a file that doesn't match its CRC is returned but not kept, and neither
+            // is one the cache can't write
+            if crc32fast::hash(&data) == crc {
+                let _ = write(&path, &data).await;
+            }
+            Ok(data)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("Some.Release-GRP"), "Some.Release-GRP");
+        assert_eq!(file_name("Sample/grp.srs"), "Sample%2Fgrp.srs");
+        assert_eq!(file_name(".."), "%2E.");
+        assert_eq!(file_name("a b\\c"), "a%20b%5Cc");
+    }
+}
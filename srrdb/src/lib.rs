@@ -1,10 +1,42 @@
+mod builder;
+mod cache;
+mod metadata;
+mod search;
+mod throttle;
+mod upload;
+
+pub use builder::*;
+pub use cache::*;
 use futures::{FutureExt, TryFutureExt};
+pub use metadata::*;
 pub use reqwest::Error as ReqwestError;
+pub use search::*;
+pub use throttle::*;
+pub use upload::*;
 
 #[derive(Debug)]
 pub enum Error {
     NotFound,
     Request(ReqwestError),
+    /// srrDB answered 429 Too Many Requests, `retry_after` is its `Retry-After`, at
+    /// most an hour.
+    Throttled {
+        retry_after: Option<std::time::Duration>,
+    },
+    /// srrDB did not accept the username and password.
+    LoginFailed,
+    /// Uploading needs a session, see [`Client::login`].
+    NotLoggedIn,
+    /// A downloaded file doesn't have the size and CRC of its [`FileDetails`].
+    IntegrityMismatch {
+        file_name: String,
+        expected_size: u64,
+        expected_crc: u32,
+        size: u64,
+        crc: u32,
+    },
+    #[cfg(feature = "parse")]
+    Parse(srr::ParseError),
 }
 
 impl From<ReqwestError> for Error {
@@ -18,16 +50,80 @@ impl std::fmt::Display for Error {
         match self {
             Error::NotFound => f.write_str("Error(NotFound)"),
             Error::Request(error) => error.fmt(f),
+            Error::Throttled { .. } => f.write_str("srrDB is throttling requests"),
+            Error::LoginFailed => f.write_str("the srrDB login failed"),
+            Error::NotLoggedIn => f.write_str("not logged in to srrDB"),
+            Error::IntegrityMismatch {
+                file_name,
+                expected_size,
+                expected_crc,
+                size,
+                crc,
+            } => write!(
+                f,
+                "{file_name} has {size} bytes and CRC {crc:08X}, \
+                 expected {expected_size} bytes and CRC {expected_crc:08X}"
+            ),
+            #[cfg(feature = "parse")]
+            Error::Parse(error) => write!(f, "invalid srr: {error}"),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// Whether the same request may succeed later: throttling, timeouts, failed
+    /// connections, server errors and truncated or damaged downloads.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Throttled { .. } | Error::IntegrityMismatch { .. } => true,
+            Error::Request(error) => {
+                error.is_timeout()
+                    || error.is_connect()
+                    || error
+                        .status()
+                        .is_some_and(|status| status.is_server_error())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The longest `Retry-After` taken from a response, anything longer is a server error.
+const MAX_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Turns a 429 into [`Error::Throttled`] and other error statuses into
+/// [`Error::Request`].
+fn check_status(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(|secs| std::time::Duration::from_secs(secs).min(MAX_RETRY_AFTER));
+        return Err(Error::Throttled { retry_after });
+    }
+    Ok(response.error_for_status()?)
+}
+
+#[derive(Clone)]
 pub struct Client {
     inner: reqwest::Client,
     details_url: reqwest::Url,
     download_url: reqwest::Url,
+    search_url: reqwest::Url,
+    srr_url: reqwest::Url,
+    imdb_url: reqwest::Url,
+    nfo_url: reqwest::Url,
+    login_url: reqwest::Url,
+    upload_url: reqwest::Url,
+    store_url: reqwest::Url,
+    /// Logs in without following the redirect, which would lose the cookies.
+    session_client: reqwest::Client,
+    /// The `Cookie` header of the logged in session, shared by clones.
+    session: std::sync::Arc<std::sync::Mutex<Option<String>>>,
 }
 
 impl Default for Client {
@@ -37,15 +133,14 @@ impl Default for Client {
 }
 
 impl Client {
+    /// A client for srrdb.com, panics like `reqwest::Client::new` if TLS can't be
+    /// initialized.
     pub fn new() -> Self {
-        let inner = reqwest::Client::new();
-        let details_url = reqwest::Url::parse("https://api.srrdb.com/v1/details/").unwrap();
-        let download_url = reqwest::Url::parse("https://www.srrdb.com/download/file/").unwrap();
-        Self {
-            inner,
-            details_url,
-            download_url,
-        }
+        Self::builder().build().unwrap()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
     }
 
     pub fn details_request<'a, R: Into<DetailsRequest<'a>>>(
@@ -57,31 +152,80 @@ impl Client {
         let request = self.inner.get(url).build().unwrap();
         self.inner
             .execute(request)
-            .and_then(|response| response.json::<DetailsOrNotFound>())
             .err_into::<Error>()
-            .and_then(|response| {
-                futures::future::ready(match response {
-                    DetailsOrNotFound::NotFound(_) => Err(Error::NotFound),
-                    DetailsOrNotFound::Details(details_response) => Ok(details_response),
-                })
-            })
+            .and_then(|response| futures::future::ready(check_status(response)))
+            .and_then(|response| response.json::<OrNotFound<DetailsResponse>>().err_into())
+            .and_then(|response| futures::future::ready(response.found()))
     }
 
+    /// A stored file of a release, checked against the size and CRC of its details.
     pub fn file_request(
         &self,
         request: FileRequest,
     ) -> impl futures::Future<Output = Result<Vec<u8>, Error>> {
         let input = format!("{}/{}", request.base, request.details.name);
         let url = self.download_url.join(&input).unwrap();
+        let details = request.details;
+        let (file_name, expected_size, expected_crc) =
+            (details.name.clone(), details.size, details.crc);
         let request = self.inner.get(url).build().unwrap();
         self.inner
             .execute(request)
-            .and_then(|response| response.bytes())
-            .map_ok(Into::into)
-            .err_into()
+            .err_into::<Error>()
+            .and_then(|response| futures::future::ready(check_status(response)))
+            .and_then(|response| response.bytes().err_into())
+            .and_then(move |data| {
+                let (size, crc) = (data.len() as u64, crc32fast::hash(&data));
+                futures::future::ready(if (size, crc) == (expected_size, expected_crc) {
+                    Ok(data.into())
+                } else {
+                    Err(Error::IntegrityMismatch {
+                        file_name,
+                        expected_size,
+                        expected_crc,
+                        size,
+                        crc,
+                    })
+                })
+            })
+    }
+
+    /// The .srr file of a release.
+    pub fn srr_request<'a, R: Into<SrrRequest<'a>>>(
+        &self,
+        request: R,
+    ) -> impl futures::Future<Output = Result<Vec<u8>, Error>> {
+        let request = request.into();
+        let url = self.srr_url.join(&request.release_name).unwrap();
+        let request = self.inner.get(url).build().unwrap();
+        self.inner
+            .execute(request)
+            .err_into::<Error>()
+            .and_then(|response| async move {
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Err(Error::NotFound);
+                }
+                let bytes = check_status(response)?.bytes().await?;
+                Ok(bytes.into())
+            })
+    }
+
+    /// The .srr file of a release, parsed within the limits of `options`.
+    #[cfg(feature = "parse")]
+    pub fn parsed_srr_request<'a, R: Into<SrrRequest<'a>>>(
+        &self,
+        request: R,
+        options: srr::ParseOptions,
+    ) -> impl futures::Future<Output = Result<srr::Srr, Error>> {
+        self.srr_request(request).and_then(move |data| {
+            futures::future::ready(
+                srr::Srr::parse_with_options(&data, &options).map_err(Error::Parse),
+            )
+        })
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct DetailsRequest<'a> {
     pub release_name: std::borrow::Cow<'a, str>,
 }
@@ -94,23 +238,115 @@ impl<'a> From<&'a str> for DetailsRequest<'a> {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct SrrRequest<'a> {
+    pub release_name: std::borrow::Cow<'a, str>,
+}
+
+impl<'a> From<&'a str> for SrrRequest<'a> {
+    fn from(release_name: &'a str) -> Self {
+        Self {
+            release_name: std::borrow::Cow::Borrowed(release_name),
+        }
+    }
+}
+
+/// The API answers with an empty list for releases it doesn't know.
 #[derive(Debug, serde::Deserialize)]
 #[serde(untagged)]
-enum DetailsOrNotFound {
+enum OrNotFound<T> {
     NotFound([(); 0]),
-    Details(DetailsResponse),
+    Found(T),
 }
 
-#[derive(Debug, serde::Deserialize)]
+impl<T> OrNotFound<T> {
+    fn found(self) -> Result<T, Error> {
+        match self {
+            OrNotFound::NotFound(_) => Err(Error::NotFound),
+            OrNotFound::Found(response) => Ok(response),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(u64),
+    String(String),
+}
+
+/// The API sends some numbers as strings.
+fn number_or_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match serde::Deserialize::deserialize(deserializer)? {
+        NumberOrString::Number(number) => Ok(number),
+        NumberOrString::String(string) => string.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// A CRC32 as 8 hex digits.
+fn hex_crc<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    optional_hex_crc(deserializer)?.ok_or_else(|| serde::de::Error::custom("missing CRC32"))
+}
+
+fn optional_hex_crc<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u32>, D::Error> {
+    let crc: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    match crc.as_deref() {
+        None | Some("") => Ok(None),
+        Some(crc) => u32::from_str_radix(crc, 16)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+fn to_hex_crc<S: serde::Serializer>(crc: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{crc:08X}"))
+}
+
+fn to_optional_hex_crc<S: serde::Serializer>(
+    crc: &Option<u32>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match crc {
+        Some(crc) => to_hex_crc(crc, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A number that may be missing, null or an empty string.
+fn optional_number<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    match serde::Deserialize::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrString::Number(number)) => Ok(Some(number)),
+        Some(NumberOrString::String(string)) if string.is_empty() => Ok(None),
+        Some(NumberOrString::String(string)) => {
+            string.parse().map(Some).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+/// Serializes to the JSON the API sends, which is what [`Cache`] stores.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct DetailsResponse {
     pub name: String,
+    /// The files stored in the srr.
     pub files: Vec<FileDetails>,
+    /// The files inside the RAR volumes.
     #[serde(rename = "archived-files")]
     pub archived_files: Vec<FileDetails>,
+    /// Files added to the release after its srr, like proofs and samples.
+    #[serde(default)]
+    pub adds: Vec<AddedFile>,
+    /// The files the SFVs of the release list.
+    #[serde(default)]
+    pub sfv: Vec<SfvEntry>,
 }
 
 impl DetailsResponse {
-    pub fn file_request(&self, name: &str) -> Option<FileRequest> {
+    pub fn file_request(&self, name: &str) -> Option<FileRequest<'_>> {
         self.files.iter().find_map(|details| {
             (details.name == name).then_some(FileRequest {
                 base: &self.name,
@@ -118,19 +354,57 @@ impl DetailsResponse {
             })
         })
     }
+
+    pub fn archived_file(&self, name: &str) -> Option<&FileDetails> {
+        self.archived_files
+            .iter()
+            .find(|details| details.name == name)
+    }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct FileRequest<'a> {
     base: &'a str,
     details: &'a FileDetails,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct FileDetails {
     pub name: String,
-    pub size: u32,
-    // I think this is the u16 hex encoded
-    pub crc: String,
+    /// The folder of an archived file inside the RAR volumes.
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(deserialize_with = "number_or_string")]
+    pub size: u64,
+    #[serde(deserialize_with = "hex_crc", serialize_with = "to_hex_crc")]
+    pub crc: u32,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct AddedFile {
+    pub name: String,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub size: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "optional_hex_crc",
+        serialize_with = "to_optional_hex_crc"
+    )]
+    pub crc: Option<u32>,
+    /// When it was added, as `2012-09-26 17:05:34`.
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct SfvEntry {
+    pub name: String,
+    #[serde(
+        default,
+        deserialize_with = "optional_hex_crc",
+        serialize_with = "to_optional_hex_crc"
+    )]
+    pub crc: Option<u32>,
 }
 
 impl<'a> tower_service::Service<DetailsRequest<'a>> for Client {
@@ -167,11 +441,57 @@ impl<'a> tower_service::Service<FileRequest<'a>> for Client {
     }
 }
 
+impl<'a> tower_service::Service<SrrRequest<'a>> for Client {
+    type Response = Vec<u8>;
+    type Error = Error;
+    type Future = futures::future::BoxFuture<'a, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SrrRequest<'a>) -> Self::Future {
+        self.srr_request(req).boxed()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn details_response() {
+        let json = r#"{
+            "name": "Some.Movie.2012.COMPLETE.BLURAY-GRP",
+            "files": [{"name": "grp.nfo", "size": "3051", "crc": "ac26b4fb"}],
+            "archived-files": [
+                {"name": "00000.m2ts", "path": "BDMV/STREAM", "size": 25000000000, "crc": "0053CA13"}
+            ],
+            "adds": [{"name": "Proof/grp-proof.jpg", "size": 1000, "crc": "", "date": "2012-09-26 17:05:34"}],
+            "sfv": [{"name": "grp.r00", "crc": "D3AF3304"}]
+        }"#;
+        let details: DetailsResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(details.files[0].crc, 0xAC26B4FB);
+        assert_eq!(details.files[0].size, 3051);
+        let m2ts = details.archived_file("00000.m2ts").unwrap();
+        assert_eq!(m2ts.size, 25_000_000_000);
+        assert_eq!(m2ts.path.as_deref(), Some("BDMV/STREAM"));
+        assert_eq!(m2ts.crc, 0x0053CA13);
+        assert_eq!(details.adds[0].crc, None);
+        assert_eq!(details.sfv[0].crc, Some(0xD3AF3304));
+        assert!(details.archived_file("00001.m2ts").is_none());
+
+        // older responses only have the names, files and archived files
+        let json = r#"{"name": "A-GRP", "files": [], "archived-files": []}"#;
+        let details: DetailsResponse = serde_json::from_str(json).unwrap();
+        assert!(details.adds.is_empty());
+    }
+
     #[tokio::test]
+    #[ignore = "needs srrdb.com"]
     async fn it_works() {
         let client = Client::new();
         let response = client
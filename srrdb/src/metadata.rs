@@ -0,0 +1,179 @@
+use crate::{check_status, optional_number, Client, Error, NumberOrString, OrNotFound};
+use futures::{FutureExt, TryFutureExt};
+
+#[derive(Debug, Clone)]
+pub struct ImdbRequest<'a> {
+    pub release_name: std::borrow::Cow<'a, str>,
+}
+
+impl<'a> From<&'a str> for ImdbRequest<'a> {
+    fn from(release_name: &'a str) -> Self {
+        Self {
+            release_name: std::borrow::Cow::Borrowed(release_name),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ImdbResponse {
+    pub releases: Vec<ImdbRelease>,
+}
+
+/// A release and the IMDb title it is linked to.
+#[derive(Debug, serde::Deserialize)]
+pub struct ImdbRelease {
+    pub release: String,
+    /// The numeric part of the IMDb id, 295297 for `tt0295297`.
+    #[serde(default, deserialize_with = "imdb_id")]
+    pub imdb: Option<u32>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "year")]
+    pub year: Option<u16>,
+}
+
+impl ImdbRelease {
+    /// The IMDb id as written in urls, like `tt0295297`.
+    pub fn imdb_id(&self) -> Option<String> {
+        self.imdb.map(|id| format!("tt{id:07}"))
+    }
+}
+
+/// An IMDb id, as `tt0295297`, `0295297` or a number.
+fn imdb_id<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let id = match serde::Deserialize::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(NumberOrString::Number(id)) => id.to_string(),
+        Some(NumberOrString::String(id)) => id,
+    };
+    let digits = id.strip_prefix("tt").unwrap_or(&id);
+    if digits.is_empty() {
+        return Ok(None);
+    }
+    digits.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn year<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    optional_number(deserializer)?
+        .map(|year| u16::try_from(year).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[derive(Debug, Clone)]
+pub struct NfoRequest<'a> {
+    pub release_name: std::borrow::Cow<'a, str>,
+}
+
+impl<'a> From<&'a str> for NfoRequest<'a> {
+    fn from(release_name: &'a str) -> Self {
+        Self {
+            release_name: std::borrow::Cow::Borrowed(release_name),
+        }
+    }
+}
+
+/// The NFO files of a release and where to download them.
+#[derive(Debug, serde::Deserialize)]
+pub struct NfoResponse {
+    pub release: String,
+    #[serde(rename = "nfo")]
+    pub nfos: Vec<String>,
+    #[serde(rename = "nfolink")]
+    pub nfo_links: Vec<String>,
+}
+
+impl Client {
+    /// The IMDb title linked to a release.
+    pub fn imdb_request<'a, R: Into<ImdbRequest<'a>>>(
+        &self,
+        request: R,
+    ) -> impl futures::Future<Output = Result<ImdbResponse, Error>> {
+        let request = request.into();
+        let url = self.imdb_url.join(&request.release_name).unwrap();
+        let request = self.inner.get(url).build().unwrap();
+        self.inner
+            .execute(request)
+            .err_into::<Error>()
+            .and_then(|response| futures::future::ready(check_status(response)))
+            .and_then(|response| response.json::<OrNotFound<ImdbResponse>>().err_into())
+            .and_then(|response| futures::future::ready(response.found()))
+    }
+
+    pub fn nfo_request<'a, R: Into<NfoRequest<'a>>>(
+        &self,
+        request: R,
+    ) -> impl futures::Future<Output = Result<NfoResponse, Error>> {
+        let request = request.into();
+        let url = self.nfo_url.join(&request.release_name).unwrap();
+        let request = self.inner.get(url).build().unwrap();
+        self.inner
+            .execute(request)
+            .err_into::<Error>()
+            .and_then(|response| futures::future::ready(check_status(response)))
+            .and_then(|response| response.json::<OrNotFound<NfoResponse>>().err_into())
+            .and_then(|response| futures::future::ready(response.found()))
+    }
+}
+
+impl<'a> tower_service::Service<ImdbRequest<'a>> for Client {
+    type Response = ImdbResponse;
+    type Error = Error;
+    type Future = futures::future::BoxFuture<'a, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ImdbRequest<'a>) -> Self::Future {
+        self.imdb_request(req).boxed()
+    }
+}
+
+impl<'a> tower_service::Service<NfoRequest<'a>> for Client {
+    type Response = NfoResponse;
+    type Error = Error;
+    type Future = futures::future::BoxFuture<'a, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: NfoRequest<'a>) -> Self::Future {
+        self.nfo_request(req).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imdb_ids() {
+        let json = r#"{"releases":[
+            {"release":"A-GRP","imdb":"tt0295297","title":"A","year":"2002"},
+            {"release":"B-GRP","imdb":"0289043","year":2003},
+            {"release":"C-GRP","imdb":"","title":"","year":""}
+        ]}"#;
+        let response: ImdbResponse = serde_json::from_str(json).unwrap();
+        let ids = response
+            .releases
+            .iter()
+            .map(|release| (release.imdb, release.year))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                (Some(295297), Some(2002)),
+                (Some(289043), Some(2003)),
+                (None, None)
+            ]
+        );
+        assert_eq!(response.releases[0].imdb_id().as_deref(), Some("tt0295297"));
+    }
+}
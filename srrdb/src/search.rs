@@ -0,0 +1,438 @@
+use crate::{check_status, number_or_string, Client, Error};
+use futures::{FutureExt, TryFutureExt, TryStreamExt};
+
+/// The most results the server skips over.
+pub const MAX_SKIP: u32 = 50_000;
+/// The most results the server returns for one query.
+pub const MAX_COUNT: u32 = 1000;
+
+/// A `/v1/search` query. Every method adds one term, the terms all have to match:
+///
+/// ```
+/// let query = srrdb::SearchQuery::new()
+///     .archive_crc(0x3D7C3182)
+///     .category(srrdb::Category::X264)
+///     .nfo(srrdb::YesNo::Yes);
+/// assert_eq!(query.terms(), ["archive-crc:3D7C3182", "category:x264", "nfo:yes"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    terms: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn terms(&self) -> &[String] {
+        &self.terms
+    }
+
+    /// A term the builder has no method for, e.g. `term("genre", "action")`.
+    pub fn term(mut self, key: &str, value: impl std::fmt::Display) -> Self {
+        self.terms.push(format!("{key}:{value}"));
+        self
+    }
+
+    /// Words that have to be in the release name.
+    pub fn words(mut self, words: &str) -> Self {
+        self.terms
+            .extend(words.split_whitespace().map(ToOwned::to_owned));
+        self
+    }
+
+    /// The release with exactly this name, faster than searching for its words.
+    pub fn release(self, name: &str) -> Self {
+        self.term("r", name)
+    }
+
+    pub fn group(self, group: &str) -> Self {
+        self.term("group", group)
+    }
+
+    /// The day the srr was added to the database, as `2025-02-07`.
+    pub fn date(self, date: &str) -> Self {
+        self.term("date", date)
+    }
+
+    pub fn nfo(self, nfo: YesNo) -> Self {
+        self.term("nfo", nfo)
+    }
+
+    pub fn srs(self, srs: SrsState) -> Self {
+        self.term("srs", srs)
+    }
+
+    /// Whether the release is not in English.
+    pub fn foreign(self, foreign: YesNo) -> Self {
+        self.term("foreign", foreign)
+    }
+
+    pub fn confirmed(self, confirmed: YesNo) -> Self {
+        self.term("confirmed", confirmed)
+    }
+
+    /// The hash of the set of RAR volumes of the release.
+    pub fn rar_hash(self, hash: &str) -> Self {
+        self.term("rarhash", hash)
+    }
+
+    pub fn category(self, category: Category) -> Self {
+        self.term("category", category)
+    }
+
+    /// The numeric part of an IMDb id, 289043 for `tt0289043`.
+    pub fn imdb(self, id: u32) -> Self {
+        self.term("imdb", format_args!("tt{id:07}"))
+    }
+
+    /// Release names starting with `start`.
+    pub fn start(self, start: &str) -> Self {
+        self.term("start", start)
+    }
+
+    pub fn genre(self, genre: &str) -> Self {
+        self.term("genre", genre)
+    }
+
+    pub fn language(self, language: &str) -> Self {
+        self.term("language", language)
+    }
+
+    pub fn country(self, country: &str) -> Self {
+        self.term("country", country)
+    }
+
+    /// The CRC32 of a file archived in the RAR volumes.
+    pub fn archive_crc(self, crc: u32) -> Self {
+        self.term("archive-crc", format_args!("{crc:08X}"))
+    }
+
+    /// The size of a file archived in the RAR volumes.
+    pub fn archive_size(self, size: u64) -> Self {
+        self.term("archive-size", size)
+    }
+
+    /// The Internet Subtitles Database hash of an archived file.
+    pub fn isdb_hash(self, hash: &str) -> Self {
+        self.term("isdbhash", hash)
+    }
+
+    /// Release names that are all lower case.
+    pub fn lower(self, lower: YesNo) -> Self {
+        self.term("lower", lower)
+    }
+
+    /// Release names that end or start with a dot.
+    pub fn end_or_start_with_dot(self, dot: YesNo) -> Self {
+        self.term("endorstartwithdot", dot)
+    }
+
+    /// Release names starting with an upper case letter.
+    pub fn first_upper(self, first_upper: YesNo) -> Self {
+        self.term("firstupper", first_upper)
+    }
+
+    /// Whether the archive was created with compression.
+    pub fn compressed(self, compressed: YesNo) -> Self {
+        self.term("compressed", compressed)
+    }
+
+    /// Skips the first `skip` results.
+    pub fn skip(self, skip: u32) -> Self {
+        self.term("skip", skip)
+    }
+
+    /// Skips the first `skip` results and returns at most `count`, the server allows
+    /// up to [`MAX_SKIP`] and [`MAX_COUNT`].
+    pub fn skip_count(self, skip: u32, count: u32) -> Self {
+        self.term("skipr", format_args!("{skip}.{count}"))
+    }
+
+    /// The name of the sample kept in an srs.
+    pub fn sample_name(self, name: &str) -> Self {
+        self.term("store-real-filename", name)
+    }
+
+    /// The CRC32 of the complete sample kept in an srs.
+    pub fn sample_crc(self, crc: u32) -> Self {
+        self.term("store-real-crc", format_args!("{crc:08x}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum YesNo {
+    Yes,
+    No,
+}
+
+impl std::fmt::Display for YesNo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            YesNo::Yes => "yes",
+            YesNo::No => "no",
+        })
+    }
+}
+
+/// Whether a release has a sample srs, and whether it can rebuild the sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SrsState {
+    Yes,
+    No,
+    Broken,
+}
+
+impl std::fmt::Display for SrsState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SrsState::Yes => "yes",
+            SrsState::No => "no",
+            SrsState::Broken => "broken",
+        })
+    }
+}
+
+macro_rules! categories {
+    ($($variant:ident => $name:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Category {
+            $($variant,)*
+        }
+
+        impl Category {
+            pub const ALL: &[Category] = &[$(Category::$variant,)*];
+
+            /// The name used in queries.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Category::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+categories! {
+    Unknown => "unknown",
+    Tv => "tv",
+    Xvid => "xvid",
+    X264 => "x264",
+    Dvdr => "dvdr",
+    Xxx => "xxx",
+    Pc => "pc",
+    Ngc => "ngc",
+    Wii => "wii",
+    N3ds => "3ds",
+    Nds => "nds",
+    Xbox360 => "xbox360",
+    Xbox => "xbox",
+    Ps3 => "ps3",
+    Psp => "psp",
+    CompleteBluray => "complete+bluray",
+    Wmv => "wmv",
+    Subpack => "subpack",
+    Apps => "apps",
+    Game => "game",
+    Flac => "flac",
+    Mp3 => "mp3",
+    Mvid => "mvid",
+    WiiU => "wiiu",
+    Ps4 => "ps4",
+    XboxOne => "xboxone",
+    Psv => "psv",
+    Nsw => "nsw",
+    Gba => "gba",
+    Gbc => "gbc",
+    Gizmondo => "gizmondo",
+    Gp32 => "gp32",
+    NGage => "ngage",
+    Ngp => "ngp",
+    TapwaveZodiac => "tapwave+zodiac",
+    Ws => "ws",
+    Ebook => "ebook",
+    SceneNotice => "scene+notice",
+    N64 => "n64",
+    Psx => "psx",
+    Ps2 => "ps2",
+    Dc => "dc",
+    Cover => "cover",
+    Ogg => "ogg",
+    Remux => "remux",
+    Ps5 => "ps5",
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Category::ALL
+            .iter()
+            .find(|category| category.name().eq_ignore_ascii_case(name))
+            .copied()
+            .ok_or(())
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    /// All results of the query, not only the ones returned.
+    #[serde(rename = "resultsCount", deserialize_with = "number_or_string")]
+    pub results_count: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SearchResult {
+    pub release: String,
+    /// When the srr was added, as `2012-09-26 17:05:34`.
+    pub date: String,
+    #[serde(rename = "hasNFO")]
+    pub has_nfo: YesNo,
+    #[serde(rename = "hasSRS")]
+    pub has_srs: SrsState,
+}
+
+impl Client {
+    fn search_url(&self, query: &SearchQuery) -> reqwest::Url {
+        let mut url = self.search_url.clone();
+        url.path_segments_mut()
+            .unwrap()
+            .pop_if_empty()
+            .extend(query.terms());
+        url
+    }
+
+    pub fn search_request(
+        &self,
+        query: &SearchQuery,
+    ) -> impl futures::Future<Output = Result<SearchResponse, Error>> {
+        let url = self.search_url(query);
+        let request = self.inner.get(url).build().unwrap();
+        self.inner
+            .execute(request)
+            .err_into::<Error>()
+            .and_then(|response| futures::future::ready(check_status(response)))
+            .and_then(|response| response.json::<SearchResponse>().err_into())
+    }
+
+    /// Every result of the query, fetched a page of [`MAX_COUNT`] results at a time.
+    /// The server skips at most [`MAX_SKIP`] results, so the stream ends there. The
+    /// query should not have `skip` terms of its own, and the stream ends after the
+    /// first error.
+    pub fn search_stream(
+        &self,
+        query: SearchQuery,
+    ) -> impl futures::Stream<Item = Result<SearchResult, Error>> + '_ {
+        futures::stream::unfold(Some(0), move |skip| {
+            let page = skip.map(|skip| {
+                let query = query.clone().skip_count(skip, MAX_COUNT);
+                self.search_request(&query).map(move |page| (skip, page))
+            });
+            async move {
+                let (skip, page) = page?.await;
+                Some(match page {
+                    Ok(page) => {
+                        let next = next_skip(skip, page.results.len(), page.results_count);
+                        (Ok(page.results), next)
+                    }
+                    Err(error) => (Err(error), None),
+                })
+            }
+        })
+        .map_ok(|results| futures::stream::iter(results.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+/// Where the page after the one at `skip` starts, if there is one.
+fn next_skip(skip: u32, returned: usize, total: u64) -> Option<u32> {
+    let next = skip.checked_add(u32::try_from(returned).ok()?)?;
+    let full = returned == MAX_COUNT as usize;
+    (full && u64::from(next) < total && next <= MAX_SKIP).then_some(next)
+}
+
+impl tower_service::Service<SearchQuery> for Client {
+    type Response = SearchResponse;
+    type Error = Error;
+    type Future = futures::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SearchQuery) -> Self::Future {
+        self.search_request(&req).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_terms() {
+        let query = SearchQuery::new()
+            .words("harry  potter")
+            .category(Category::CompleteBluray)
+            .srs(SrsState::Broken)
+            .imdb(289043)
+            .skip_count(200, 10)
+            .sample_crc(0xB177CC14);
+        assert_eq!(
+            query.terms(),
+            [
+                "harry",
+                "potter",
+                "category:complete+bluray",
+                "srs:broken",
+                "imdb:tt0289043",
+                "skipr:200.10",
+                "store-real-crc:b177cc14"
+            ]
+        );
+        assert_eq!("X264".parse(), Ok(Category::X264));
+        assert_eq!("3ds".parse(), Ok(Category::N3ds));
+    }
+
+    #[test]
+    fn search_url() {
+        let query = SearchQuery::new().release("Some.Release-GRP").group("a/b");
+        assert_eq!(
+            Client::new().search_url(&query).as_str(),
+            "https://api.srrdb.com/v1/search/r:Some.Release-GRP/group:a%2Fb"
+        );
+    }
+
+    #[test]
+    fn paging() {
+        assert_eq!(next_skip(0, 1000, 2500), Some(1000));
+        assert_eq!(next_skip(1000, 1000, 2500), Some(2000));
+        assert_eq!(next_skip(2000, 500, 2500), None);
+        assert_eq!(next_skip(0, 1000, 1000), None);
+        assert_eq!(next_skip(0, 0, 2500), None);
+        assert_eq!(next_skip(49_000, 1000, 100_000), Some(50_000));
+        assert_eq!(next_skip(50_000, 1000, 100_000), None);
+    }
+
+    #[test]
+    fn search_response() {
+        let json = r#"{"results":[{"release":"Harry.Potter.And.The.Chamber.Of.Secrets.2002.DVDRip.XViD-iNTERNAL-TDF","date":"2012-09-26 17:05:34","hasNFO":"yes","hasSRS":"yes"}],"query":["archive-crc:B22084EB"],"resultsCount":"1"}"#;
+        let response: SearchResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.results_count, 1);
+        assert_eq!(response.results[0].has_nfo, YesNo::Yes);
+        assert_eq!(response.results[0].has_srs, SrsState::Yes);
+    }
+}
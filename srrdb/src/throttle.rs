@@ -0,0 +1,304 @@
+use crate::{DetailsRequest, Error, FileRequest, ImdbRequest, NfoRequest, SearchQuery, SrrRequest};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+use tower_service::Service;
+
+/// Requests that are safe to send again, which [`Retry`] is limited to.
+pub trait Idempotent {}
+
+impl Idempotent for DetailsRequest<'_> {}
+impl Idempotent for FileRequest<'_> {}
+impl Idempotent for SrrRequest<'_> {}
+impl Idempotent for ImdbRequest<'_> {}
+impl Idempotent for NfoRequest<'_> {}
+impl Idempotent for SearchQuery {}
+
+/// Limits a service to a number of requests per second. After a 429 it holds
+/// every request back for the `Retry-After` of the response.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitLayer {
+    interval: Duration,
+}
+
+impl RateLimitLayer {
+    /// Panics unless `requests_per_second` is positive and finite.
+    pub fn new(requests_per_second: f64) -> Self {
+        assert!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "invalid rate: {requests_per_second}"
+        );
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+        }
+    }
+}
+
+impl<S> tower_layer::Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            interval: self.interval,
+            next: Arc::new(Mutex::new(None)),
+            sleep: None,
+            reserved: false,
+        }
+    }
+}
+
+/// See [`RateLimitLayer`]. Clones share the limit.
+#[derive(Debug)]
+pub struct RateLimit<S> {
+    inner: S,
+    interval: Duration,
+    /// When the next request may start.
+    next: Arc<Mutex<Option<Instant>>>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    /// Whether `poll_ready` took the slot for the next call.
+    reserved: bool,
+}
+
+impl<S: Clone> Clone for RateLimit<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            interval: self.interval,
+            next: self.next.clone(),
+            sleep: None,
+            reserved: false,
+        }
+    }
+}
+
+impl<S, Req> Service<Req> for RateLimit<S>
+where
+    S: Service<Req, Error = Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = RateLimitFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        while !self.reserved {
+            if let Some(sleep) = &mut self.sleep {
+                ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+            let now = Instant::now();
+            let mut next = self.next.lock().unwrap();
+            match *next {
+                Some(next) if next > now => {
+                    self.sleep = Some(Box::pin(tokio::time::sleep_until(next)));
+                }
+                _ => {
+                    *next = Some(now + self.interval);
+                    self.reserved = true;
+                }
+            }
+        }
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        assert!(self.reserved, "call before poll_ready");
+        self.reserved = false;
+        RateLimitFuture {
+            inner: Box::pin(self.inner.call(req)),
+            interval: self.interval,
+            next: self.next.clone(),
+        }
+    }
+}
+
+pub struct RateLimitFuture<F> {
+    inner: Pin<Box<F>>,
+    interval: Duration,
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+impl<F, T> Future for RateLimitFuture<F>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = ready!(self.inner.as_mut().poll(cx));
+        if let Err(Error::Throttled { retry_after }) = &result {
+            let delay = retry_after.unwrap_or(self.interval);
+            if let Some(until) = Instant::now().checked_add(delay) {
+                let mut next = self.next.lock().unwrap();
+                *next = Some(next.map_or(until, |next| next.max(until)));
+            }
+        }
+        Poll::Ready(result)
+    }
+}
+
+/// Retries [`Idempotent`] requests that failed with a transient error (see
+/// [`Error::is_transient`]), with exponential backoff. A 429 waits at least its
+/// `Retry-After`, but never longer than the maximum delay.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryLayer {
+    retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryLayer {
+    /// Retries up to `retries` times, first after half a second, doubling up to a
+    /// minute.
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// How long to wait before retry number `attempt`, counting from 0.
+    fn delay(&self, attempt: u32, error: &Error) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        match error {
+            Error::Throttled {
+                retry_after: Some(retry_after),
+            } => backoff.max(*retry_after).min(self.max_delay),
+            _ => backoff,
+        }
+    }
+}
+
+impl<S> tower_layer::Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            policy: *self,
+        }
+    }
+}
+
+/// See [`RetryLayer`].
+#[derive(Debug, Clone)]
+pub struct Retry<S> {
+    inner: S,
+    policy: RetryLayer,
+}
+
+impl<S, Req> Service<Req> for Retry<S>
+where
+    S: Service<Req, Error = Error> + Clone + Unpin,
+    Req: Idempotent + Clone + Unpin,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = RetryFuture<S, Req>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // the ready service makes the call, the clone waits for the next poll_ready
+        let clone = self.inner.clone();
+        let mut service = std::mem::replace(&mut self.inner, clone);
+        let future = Box::pin(service.call(req.clone()));
+        RetryFuture {
+            service,
+            request: req,
+            policy: self.policy,
+            attempt: 0,
+            state: RetryState::Calling(future),
+        }
+    }
+}
+
+pub struct RetryFuture<S: Service<Req>, Req> {
+    service: S,
+    request: Req,
+    policy: RetryLayer,
+    attempt: u32,
+    state: RetryState<S::Future>,
+}
+
+enum RetryState<F> {
+    Calling(Pin<Box<F>>),
+    Waiting(Pin<Box<Sleep>>),
+    Readying,
+}
+
+impl<S, Req> Future for RetryFuture<S, Req>
+where
+    S: Service<Req, Error = Error> + Unpin,
+    Req: Clone + Unpin,
+{
+    type Output = Result<S::Response, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            match &mut this.state {
+                RetryState::Calling(future) => match ready!(future.as_mut().poll(cx)) {
+                    Err(error) if this.attempt < this.policy.retries && error.is_transient() => {
+                        let delay = this.policy.delay(this.attempt, &error);
+                        this.attempt += 1;
+                        this.state = RetryState::Waiting(Box::pin(tokio::time::sleep(delay)));
+                    }
+                    result => return Poll::Ready(result),
+                },
+                RetryState::Waiting(sleep) => {
+                    ready!(sleep.as_mut().poll(cx));
+                    this.state = RetryState::Readying;
+                }
+                RetryState::Readying => {
+                    ready!(this.service.poll_ready(cx))?;
+                    let future = this.service.call(this.request.clone());
+                    this.state = RetryState::Calling(Box::pin(future));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let policy = RetryLayer::new(10).max_delay(Duration::from_secs(3));
+        let error = Error::NotFound;
+        let delays = (0..5)
+            .map(|attempt| policy.delay(attempt, &error).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [500, 1000, 2000, 3000, 3000]);
+        assert_eq!(policy.delay(40, &error), Duration::from_secs(3));
+
+        let throttled = |seconds| Error::Throttled {
+            retry_after: Some(Duration::from_secs(seconds)),
+        };
+        assert_eq!(policy.delay(0, &throttled(2)), Duration::from_secs(2));
+        assert_eq!(policy.delay(2, &throttled(1)), Duration::from_secs(2));
+        // a Retry-After longer than the maximum delay is cut short
+        assert_eq!(policy.delay(0, &throttled(5)), Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{check_status, Client, Error};
+use futures::TryFutureExt;
+use reqwest::multipart::{Form, Part};
+
+/// What srrDB did with an uploaded file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Added,
+    /// The database already has the file.
+    Duplicate,
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadResult {
+    pub file_name: String,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UploadResponse {
+    files: Vec<UploadedFile>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UploadedFile {
+    name: String,
+    message: String,
+    /// The color the site shows the message in: 0 for errors, 1 for added files and
+    /// 2 for files it already had.
+    color: u8,
+}
+
+impl From<UploadedFile> for UploadResult {
+    fn from(file: UploadedFile) -> Self {
+        let verdict = match file.color {
+            1 => Verdict::Added,
+            2 => Verdict::Duplicate,
+            _ => Verdict::Rejected(file.message),
+        };
+        Self {
+            file_name: file.name,
+            verdict,
+        }
+    }
+}
+
+/// The `Cookie` header of the session a login response starts: srrDB sets `uid` and
+/// `hash` once the password is right, and only a session id when it isn't.
+fn session(response: &reqwest::Response) -> Option<String> {
+    let value = |name| {
+        response
+            .cookies()
+            .filter(|cookie| cookie.name() == name)
+            .map(|cookie| cookie.value().to_owned())
+            .last()
+            .filter(|value| !value.is_empty() && value != "deleted")
+    };
+    Some(format!("uid={}; hash={}", value("uid")?, value("hash")?))
+}
+
+fn file(file_name: &str, data: &[u8]) -> Part {
+    Part::bytes(data.to_vec())
+        .file_name(file_name.to_owned())
+        .mime_str("application/octet-stream")
+        .unwrap()
+}
+
+impl Client {
+    /// Logs in to srrDB, keeping the session cookie for uploads.
+    pub fn login<'a>(
+        &'a self,
+        username: &str,
+        password: &str,
+    ) -> impl futures::Future<Output = Result<(), Error>> + 'a {
+        let form = [
+            ("username", username),
+            ("password", password),
+            ("login", "Login"),
+        ];
+        let request = self
+            .session_client
+            .post(self.login_url.clone())
+            .form(&form)
+            .build();
+        async move {
+            let response = self.session_client.execute(request?).await?;
+            let session = session(&response).ok_or(Error::LoginFailed)?;
+            *self.session.lock().unwrap() = Some(session);
+            Ok(())
+        }
+    }
+
+    pub fn logout(&self) {
+        *self.session.lock().unwrap() = None;
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.session.lock().unwrap().is_some()
+    }
+
+    fn upload(
+        &self,
+        url: reqwest::Url,
+        form: reqwest::multipart::Form,
+    ) -> impl futures::Future<Output = Result<Vec<UploadResult>, Error>> {
+        let session = self.session.lock().unwrap().clone();
+        let pending = session.ok_or(Error::NotLoggedIn).and_then(|session| {
+            let request = self
+                .inner
+                .post(url)
+                .header(reqwest::header::COOKIE, session)
+                .multipart(form)
+                .build()?;
+            Ok(self.inner.execute(request))
+        });
+        async move {
+            let response = check_status(pending?.await?)?;
+            let response = response.json::<UploadResponse>().await?;
+            Ok(response.files.into_iter().map(Into::into).collect())
+        }
+    }
+
+    /// Uploads new srrs, with a verdict for every one.
+    pub fn upload_srrs_request<'a, I>(
+        &self,
+        srrs: I,
+    ) -> impl futures::Future<Output = Result<Vec<UploadResult>, Error>>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let form = srrs
+            .into_iter()
+            .fold(Form::new(), |form, (file_name, data)| {
+                form.part("files[]", file(file_name, data))
+            });
+        self.upload(self.upload_url.clone(), form)
+    }
+
+    /// Adds a stored file (.nfo, .sfv, proof, .srs ...) to a release, replacing the
+    /// one of the same name. `file_name` may start with a folder, like `Sample/x.srs`.
+    pub fn store_file_request(
+        &self,
+        release_name: &str,
+        file_name: &str,
+        data: &[u8],
+    ) -> impl futures::Future<Output = Result<UploadResult, Error>> {
+        let (folder, name) = file_name.rsplit_once('/').unwrap_or(("", file_name));
+        let url = self.store_url.join(release_name).unwrap();
+        let form = Form::new()
+            .text("folder", folder.to_owned())
+            .part("file", file(name, data));
+        self.upload(url, form).and_then(|results| {
+            futures::future::ready(results.into_iter().next().ok_or(Error::NotFound))
+        })
+    }
+}
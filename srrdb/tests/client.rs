@@ -0,0 +1,625 @@
+use futures::{StreamExt, TryStreamExt};
+use srrdb::{
+    CacheLayer, Client, DetailsRequest, Error, RateLimitLayer, RetryLayer, SearchQuery, Verdict,
+};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tower_layer::Layer;
+use tower_service::Service;
+
+const RELEASE: &str = "Harry.Potter.And.The.Chamber.Of.Secrets.2002.DVDRip.XViD-iNTERNAL-TDF";
+
+/// A response of the server: status, extra header lines and body.
+type Response = (u16, String, Vec<u8>);
+
+/// Answers requests for the paths of `routes` with their response, and anything
+/// else with 404. A path with several routes gets their responses in turn, the last
+/// one from then on. Keeps every request, body included.
+struct Server {
+    url: reqwest::Url,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    async fn start(mut routes: Vec<(String, Response)>) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url =
+            reqwest::Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _address)) = listener.accept().await {
+                let request = read_request(&mut socket).await;
+                let path = request.split(' ').nth(1).unwrap_or_default().to_owned();
+                log.lock().unwrap().push(request);
+                let matching = routes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_index, (route, _response))| *route == path)
+                    .map(|(index, _route)| index)
+                    .collect::<Vec<_>>();
+                let (status, headers, body) = match matching[..] {
+                    [] => (404, String::new(), b"Not Found".to_vec()),
+                    [index] => routes[index].1.clone(),
+                    [index, ..] => routes.remove(index).1,
+                };
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn client(&self) -> Client {
+        Client::builder()
+            .api_url(self.url.join("v1").unwrap())
+            .site_url(self.url.clone())
+            .user_agent("srrdb-tests")
+            .build()
+            .unwrap()
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    std::fs::read(root.join(name)).unwrap()
+}
+
+fn route(path: &str, body: Vec<u8>) -> (String, Response) {
+    (path.to_owned(), (200, String::new(), body))
+}
+
+/// The head and the body of a request.
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut request = vec![];
+    let mut buffer = [0; 4096];
+    loop {
+        if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+            let length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .map_or(0, |length| length.parse().unwrap());
+            if request.len() >= end + 4 + length {
+                break;
+            }
+        }
+        match socket.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    }
+    String::from_utf8_lossy(&request).into_owned()
+}
+
+#[tokio::test]
+async fn details_and_file() {
+    let server = Server::start(vec![
+        route(&format!("/v1/details/{RELEASE}"), fixture("details.json")),
+        route("/v1/details/Unknown-GRP", fixture("not_found.json")),
+        route(
+            &format!("/download/file/{RELEASE}/tdf-hpatcos.nfo"),
+            fixture("tdf-hpatcos.nfo"),
+        ),
+        route(
+            &format!("/download/file/{RELEASE}/tdf-hpatcos.nfo"),
+            fixture("tdf-hpatcos.nfo")[..1000].to_vec(),
+        ),
+    ])
+    .await;
+    let client = server.client();
+
+    let details = client.details_request(RELEASE).await.unwrap();
+    assert_eq!(details.name, RELEASE);
+    assert_eq!(details.files.len(), 6);
+    assert_eq!(details.archived_files[1].name, "tdf-hpatcos2.avi");
+    assert_eq!(
+        details.archived_file("tdf-hpatcos.avi").unwrap().crc,
+        0x0053CA13
+    );
+    let nfo = client
+        .file_request(details.file_request("tdf-hpatcos.nfo").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(nfo, fixture("tdf-hpatcos.nfo"));
+    // then the server cuts it short
+    assert!(matches!(
+        client
+            .file_request(details.file_request("tdf-hpatcos.nfo").unwrap())
+            .await,
+        Err(Error::IntegrityMismatch {
+            expected_size: 3051,
+            size: 1000,
+            ..
+        })
+    ));
+
+    assert!(matches!(
+        client.details_request("Unknown-GRP").await,
+        Err(Error::NotFound)
+    ));
+    assert!(server.requests()[0].contains("user-agent: srrdb-tests\r\n"));
+}
+
+#[tokio::test]
+async fn search() {
+    let server = Server::start(vec![route(
+        "/v1/search/archive-crc:0053CA13",
+        fixture("search.json"),
+    )])
+    .await;
+    let query = SearchQuery::new().archive_crc(0x0053CA13);
+    let response = server.client().search_request(&query).await.unwrap();
+    assert_eq!(response.results_count, 1);
+    assert_eq!(response.results[0].release, RELEASE);
+}
+
+fn search_page(releases: std::ops::Range<u32>, total: u32) -> Vec<u8> {
+    let results = releases
+        .map(|release| {
+            format!(r#"{{"release":"Release.{release}-GRP","date":"2025-02-07 12:00:00","hasNFO":"yes","hasSRS":"no"}}"#)
+        })
+        .collect::<Vec<_>>();
+    format!(
+        r#"{{"results":[{}],"resultsCount":"{total}"}}"#,
+        results.join(",")
+    )
+    .into_bytes()
+}
+
+#[tokio::test]
+async fn search_pages() {
+    let server = Server::start(vec![
+        route(
+            "/v1/search/group:GRP/skipr:0.1000",
+            search_page(0..1000, 1005),
+        ),
+        route(
+            "/v1/search/group:GRP/skipr:1000.1000",
+            search_page(1000..1005, 1005),
+        ),
+    ])
+    .await;
+    let client = server.client();
+    let releases = client
+        .search_stream(SearchQuery::new().group("GRP"))
+        .map_ok(|result| result.release)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(releases.len(), 1005);
+    assert_eq!(releases[1004], "Release.1004-GRP");
+    assert_eq!(server.requests().len(), 2);
+
+    // errors end the stream
+    let results = client
+        .search_stream(SearchQuery::new().group("OTHER"))
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_err());
+}
+
+#[tokio::test]
+async fn download_srr() {
+    let srr = include_bytes!(
+        "../../srr/tests/Harry.Potter.And.The.Chamber.Of.Secrets.2002.DVDRip.XViD-iNTERNAL-TDF.srr"
+    );
+    let server = Server::start(vec![
+        route(&format!("/download/srr/{RELEASE}"), srr.to_vec()),
+        (
+            "/download/srr/Broken-GRP".to_owned(),
+            (500, String::new(), vec![]),
+        ),
+    ])
+    .await;
+    let client = server.client();
+    assert_eq!(client.srr_request(RELEASE).await.unwrap(), srr);
+    assert!(matches!(
+        client.srr_request("Unknown-GRP").await,
+        Err(Error::NotFound)
+    ));
+    assert!(matches!(
+        client.srr_request("Broken-GRP").await,
+        Err(Error::Request(_))
+    ));
+
+    #[cfg(feature = "parse")]
+    {
+        let parsed = client
+            .parsed_srr_request(RELEASE, srr::ParseOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(parsed.to_bytes(), srr);
+        let options = srr::ParseOptions {
+            max_blocks: 10,
+            ..Default::default()
+        };
+        assert!(matches!(
+            client.parsed_srr_request(RELEASE, options).await,
+            Err(Error::Parse(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn upload() {
+    let verdicts = br#"{"files":[
+        {"name":"Added-GRP.srr","message":"Added","color":1},
+        {"name":"Old-GRP.srr","message":"Already in the database","color":2},
+        {"name":"Bad-GRP.srr","message":"Not a valid srr","color":0}
+    ]}"#;
+    let stored = br#"{"files":[{"name":"added-grp.srs","message":"Stored","color":1}]}"#;
+    let server = Server::start(vec![
+        (
+            "/account/login".to_owned(),
+            (
+                302,
+                "Set-Cookie: uid=5; path=/\r\nSet-Cookie: hash=abc; path=/\r\nLocation: /\r\n"
+                    .to_owned(),
+                vec![],
+            ),
+        ),
+        route("/upload", verdicts.to_vec()),
+        route("/release/add/Added-GRP", stored.to_vec()),
+    ])
+    .await;
+    let client = server.client();
+    let srrs = [
+        ("Added-GRP.srr", &b"srr 1"[..]),
+        ("Old-GRP.srr", b"srr 2"),
+        ("Bad-GRP.srr", b"srr 3"),
+    ];
+    assert!(matches!(
+        client.upload_srrs_request(srrs).await,
+        Err(Error::NotLoggedIn)
+    ));
+    assert!(server.requests().is_empty());
+
+    client.login("user", "secret").await.unwrap();
+    assert!(client.is_logged_in());
+    let login = &server.requests()[0];
+    assert!(login.starts_with("POST /account/login "));
+    assert!(login.ends_with("username=user&password=secret&login=Login"));
+
+    let results = client.upload_srrs_request(srrs).await.unwrap();
+    let verdicts = results
+        .iter()
+        .map(|result| (result.file_name.as_str(), result.verdict.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        verdicts,
+        [
+            ("Added-GRP.srr", Verdict::Added),
+            ("Old-GRP.srr", Verdict::Duplicate),
+            (
+                "Bad-GRP.srr",
+                Verdict::Rejected("Not a valid srr".to_owned())
+            )
+        ]
+    );
+    let upload = &server.requests()[1];
+    assert!(upload.contains("cookie: uid=5; hash=abc\r\n"));
+    assert!(upload.contains("name=\"files[]\"; filename=\"Old-GRP.srr\""));
+
+    let result = client
+        .store_file_request("Added-GRP", "Sample/added-grp.srs", b"srs")
+        .await
+        .unwrap();
+    assert_eq!(result.verdict, Verdict::Added);
+    let store = &server.requests()[2];
+    assert!(store.contains("name=\"folder\"\r\n\r\nSample\r\n"));
+    assert!(store.contains("filename=\"added-grp.srs\""));
+
+    client.logout();
+    assert!(!client.is_logged_in());
+}
+
+#[tokio::test]
+async fn login_failed() {
+    let server = Server::start(vec![route("/account/login", b"Wrong password".to_vec())]).await;
+    let client = server.client();
+    assert!(matches!(
+        client.login("user", "wrong").await,
+        Err(Error::LoginFailed)
+    ));
+    assert!(!client.is_logged_in());
+
+    let client = Client::builder()
+        .site_url(server.url.clone())
+        .session_cookie("uid=5; hash=abc")
+        .build()
+        .unwrap();
+    assert!(client.is_logged_in());
+}
+
+#[tokio::test]
+async fn login_failed_with_session_cookie() {
+    // a wrong password shows the login form again, in a new PHP session
+    let server = Server::start(vec![(
+        "/account/login".to_owned(),
+        (
+            200,
+            "Set-Cookie: PHPSESSID=9s8d7f6g; path=/\r\n".to_owned(),
+            b"<form id=\"login\">".to_vec(),
+        ),
+    )])
+    .await;
+    let client = server.client();
+    assert!(matches!(
+        client.login("user", "wrong").await,
+        Err(Error::LoginFailed)
+    ));
+    assert!(!client.is_logged_in());
+}
+
+#[tokio::test]
+async fn metadata() {
+    let server = Server::start(vec![
+        route(&format!("/v1/imdb/{RELEASE}"), fixture("imdb.json")),
+        route(&format!("/v1/nfo/{RELEASE}"), fixture("nfo.json")),
+        route("/v1/nfo/Unknown-GRP", fixture("not_found.json")),
+    ])
+    .await;
+    let client = server.client();
+
+    let imdb = client.imdb_request(RELEASE).await.unwrap();
+    let release = &imdb.releases[0];
+    assert_eq!(release.imdb, Some(295297));
+    assert_eq!(
+        release.title.as_deref(),
+        Some("Harry Potter and the Chamber of Secrets")
+    );
+    assert_eq!(release.year, Some(2002));
+
+    let nfo = client.nfo_request(RELEASE).await.unwrap();
+    assert_eq!(nfo.nfos, ["tdf-hpatcos.nfo"]);
+    assert!(nfo.nfo_links[0].ends_with("/tdf-hpatcos.nfo"));
+    assert!(matches!(
+        client.nfo_request("Unknown-GRP").await,
+        Err(Error::NotFound)
+    ));
+}
+
+/// Calls `service` once it is ready.
+async fn call<S: Service<R>, R>(service: &mut S, request: R) -> Result<S::Response, S::Error> {
+    futures::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+    service.call(request).await
+}
+
+#[tokio::test(start_paused = true)]
+async fn rate_limit() {
+    let path = format!("/v1/details/{RELEASE}");
+    let throttled = (429, "Retry-After: 10\r\n".to_owned(), vec![]);
+    let server = Server::start(vec![
+        route(&path, fixture("details.json")),
+        (path.clone(), throttled),
+        route(&path, fixture("details.json")),
+    ])
+    .await;
+    let mut service = RateLimitLayer::new(2.0).layer(server.client());
+    let start = tokio::time::Instant::now();
+    call(&mut service, DetailsRequest::from(RELEASE))
+        .await
+        .unwrap();
+    assert!(matches!(
+        call(&mut service, DetailsRequest::from(RELEASE)).await,
+        Err(Error::Throttled {
+            retry_after: Some(retry_after)
+        }) if retry_after.as_secs() == 10
+    ));
+    assert!(start.elapsed().as_millis() >= 500);
+    call(&mut service, DetailsRequest::from(RELEASE))
+        .await
+        .unwrap();
+    assert!(start.elapsed().as_secs() >= 10);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry() {
+    let path = format!("/v1/details/{RELEASE}");
+    let server = Server::start(vec![
+        (path.clone(), (429, "Retry-After: 3\r\n".to_owned(), vec![])),
+        (path.clone(), (503, String::new(), vec![])),
+        route(&path, fixture("details.json")),
+        (
+            "/v1/details/Broken-GRP".to_owned(),
+            (500, String::new(), vec![]),
+        ),
+    ])
+    .await;
+    let mut service = RetryLayer::new(2)
+        .base_delay(std::time::Duration::from_secs(1))
+        .layer(RateLimitLayer::new(5.0).layer(server.client()));
+    let start = tokio::time::Instant::now();
+    let details = call(&mut service, DetailsRequest::from(RELEASE))
+        .await
+        .unwrap();
+    assert_eq!(details.name, RELEASE);
+    // 3 seconds for the 429, then 2 of backoff for the 503
+    assert!(start.elapsed().as_secs() >= 5);
+    assert_eq!(server.requests().len(), 3);
+
+    // gives up after the retries
+    let error = call(&mut service, DetailsRequest::from("Broken-GRP"))
+        .await
+        .unwrap_err();
+    assert!(error.is_transient());
+    assert_eq!(server.requests().len(), 6);
+
+    // and doesn't retry what won't change
+    assert!(matches!(
+        call(&mut service, DetailsRequest::from("Unknown-GRP")).await,
+        Err(Error::Request(_))
+    ));
+    assert_eq!(server.requests().len(), 7);
+}
+
+#[tokio::test(start_paused = true)]
+async fn oversized_retry_after() {
+    let path = format!("/v1/details/{RELEASE}");
+    let throttled = (
+        429,
+        "Retry-After: 18446744073709551615\r\n".to_owned(),
+        vec![],
+    );
+    let server = Server::start(vec![
+        (path.clone(), throttled.clone()),
+        (path.clone(), throttled),
+        route(&path, fixture("details.json")),
+    ])
+    .await;
+    let mut service = RateLimitLayer::new(2.0).layer(server.client());
+    assert!(matches!(
+        call(&mut service, DetailsRequest::from(RELEASE)).await,
+        Err(Error::Throttled {
+            retry_after: Some(retry_after)
+        }) if retry_after.as_secs() == 60 * 60
+    ));
+
+    // and a retry no longer than the maximum delay
+    let mut service = RetryLayer::new(1)
+        .max_delay(std::time::Duration::from_secs(10))
+        .layer(server.client());
+    let start = tokio::time::Instant::now();
+    call(&mut service, DetailsRequest::from(RELEASE))
+        .await
+        .unwrap();
+    assert!((10..60).contains(&start.elapsed().as_secs()));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn cache() {
+    let directory = tempfile::tempdir().unwrap();
+    let server = Server::start(vec![
+        route(&format!("/v1/details/{RELEASE}"), fixture("details.json")),
+        route(
+            &format!("/download/file/{RELEASE}/tdf-hpatcos.nfo"),
+            fixture("tdf-hpatcos.nfo"),
+        ),
+    ])
+    .await;
+    let cache = CacheLayer::new(directory.path());
+    let mut service = cache.clone().layer(server.client());
+    for _ in 0..2 {
+        let details = call(&mut service, DetailsRequest::from(RELEASE))
+            .await
+            .unwrap();
+        let nfo = call(
+            &mut service,
+            details.file_request("tdf-hpatcos.nfo").unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(nfo, fixture("tdf-hpatcos.nfo"));
+    }
+    assert_eq!(server.requests().len(), 2);
+
+    // nothing listens on port 1
+    let offline = Client::builder()
+        .api_url("http://127.0.0.1:1/v1".parse().unwrap())
+        .site_url("http://127.0.0.1:1/".parse().unwrap())
+        .build()
+        .unwrap();
+    let mut offline = cache
+        .clone()
+        .details_ttl(std::time::Duration::ZERO)
+        .layer(offline);
+    let details = call(&mut offline, DetailsRequest::from(RELEASE))
+        .await
+        .unwrap();
+    assert_eq!(details.archived_files[1].name, "tdf-hpatcos2.avi");
+    let nfo = call(
+        &mut offline,
+        details.file_request("tdf-hpatcos.nfo").unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(nfo, fixture("tdf-hpatcos.nfo"));
+    assert!(matches!(
+        call(&mut offline, DetailsRequest::from("Unknown-GRP")).await,
+        Err(Error::Request(_))
+    ));
+
+    // a file that doesn't match its CRC is downloaded again
+    let cached = directory
+        .path()
+        .join(format!("files/{RELEASE}/ac26b4fb-tdf-hpatcos.nfo"));
+    std::fs::write(&cached, b"corrupt").unwrap();
+    let nfo = call(
+        &mut service,
+        details.file_request("tdf-hpatcos.nfo").unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(nfo, fixture("tdf-hpatcos.nfo"));
+    assert_eq!(std::fs::read(&cached).unwrap(), nfo);
+    assert_eq!(server.requests().len(), 3);
+
+    // and expired details are
+    let mut expired = cache
+        .details_ttl(std::time::Duration::ZERO)
+        .layer(server.client());
+    call(&mut expired, DetailsRequest::from(RELEASE))
+        .await
+        .unwrap();
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn cache_not_writable() {
+    // directories where the temporary files would go, and then where the cached
+    // copies would be, which can't be read either
+    for suffix in [".part", ""] {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(
+            directory
+                .path()
+                .join(format!("details/{RELEASE}.json{suffix}")),
+        )
+        .unwrap();
+        std::fs::create_dir_all(
+            directory
+                .path()
+                .join(format!("files/{RELEASE}/ac26b4fb-tdf-hpatcos.nfo{suffix}")),
+        )
+        .unwrap();
+        let server = Server::start(vec![
+            route(&format!("/v1/details/{RELEASE}"), fixture("details.json")),
+            route(
+                &format!("/download/file/{RELEASE}/tdf-hpatcos.nfo"),
+                fixture("tdf-hpatcos.nfo"),
+            ),
+        ])
+        .await;
+        let mut service = CacheLayer::new(directory.path()).layer(server.client());
+        for _ in 0..2 {
+            let details = call(&mut service, DetailsRequest::from(RELEASE))
+                .await
+                .unwrap();
+            let nfo = call(
+                &mut service,
+                details.file_request("tdf-hpatcos.nfo").unwrap(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(nfo, fixture("tdf-hpatcos.nfo"));
+        }
+        // nothing was kept, so everything was fetched twice
+        assert_eq!(server.requests().len(), 4);
+        assert!(!directory
+            .path()
+            .join(format!("details/{RELEASE}.json"))
+            .is_file());
+    }
+}